@@ -0,0 +1,21 @@
+#!/bin/sh
+# Entry point when installed through moonraker's update_manager (see README),
+# zip extraction drops exec permissions so they are restored here
+
+dir=$(dirname "$0")
+case "$(uname -m)" in
+    aarch64) bin="$dir/serial-screen-aarch64-gnu" ;;
+    armv6l|armv7l) bin="$dir/serial-screen-arm-gnueabihf" ;;
+    x86_64) bin="$dir/serial-screen-x86_64-gnu" ;;
+    *)
+        echo "Unsupported architecture: $(uname -m)"
+        exit 1
+        ;;
+esac
+
+chmod +x "$bin"
+# config.toml kept next to the binaries (persistent_files) is used if present
+if [ -f "$dir/config.toml" ]; then
+    exec "$bin" --config "$dir/config.toml" "$@"
+fi
+exec "$bin" "$@"
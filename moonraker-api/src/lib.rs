@@ -6,7 +6,9 @@ pub mod websocket;
 
 pub use methods::{get_method_id, MoonrakerMethod};
 pub use params::MoonrakerParam;
-pub use websocket::connect;
+pub use websocket::{
+    connect, connect_with_options, ConnectOptions, ConnectionState, MoonrakerConnection,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -12,9 +12,24 @@ pub enum MoonrakerMethod {
     #[serde(rename = "notify_klippy_ready")]
     NotifyKlippyReady,
 
+    #[serde(rename = "notify_klippy_shutdown")]
+    NotifyKlippyShutdown,
+
+    #[serde(rename = "notify_klippy_disconnected")]
+    NotifyKlippyDisconnected,
+
+    #[serde(rename = "server.info")]
+    ServerInfo,
+
+    #[serde(rename = "printer.info")]
+    PrinterInfo,
+
     #[serde(rename = "printer.objects.subscribe")]
     PrinterObjectsSubscribe,
 
+    #[serde(rename = "printer.objects.query")]
+    PrinterObjectsQuery,
+
     #[serde(rename = "server.files.metadata")]
     FilesMetadata,
 
@@ -27,6 +42,9 @@ pub enum MoonrakerMethod {
     #[serde(rename = "printer.print.cancel")]
     PrintCancel,
 
+    #[serde(rename = "printer.print.start")]
+    PrintStart,
+
     #[serde(rename = "printer.firmware_restart")]
     FirmwareRestart,
 
@@ -41,23 +59,89 @@ pub enum MoonrakerMethod {
 
     #[serde(rename = "printer.objects.list")]
     PrinterObjectsList,
+
+    #[serde(rename = "server.database.get_item")]
+    DatabaseGetItem,
+
+    #[serde(rename = "server.history.get_job")]
+    HistoryGetJob,
+
+    #[serde(rename = "server.temperature_store")]
+    TemperatureStore,
+
+    #[serde(rename = "machine.update.status")]
+    MachineUpdateStatus,
+
+    #[serde(rename = "machine.update.refresh")]
+    MachineUpdateRefresh,
+
+    #[serde(rename = "machine.update.upgrade")]
+    MachineUpdateUpgrade,
+
+    #[serde(rename = "notify_update_response")]
+    NotifyUpdateResponse,
+
+    #[serde(rename = "notify_update_refreshed")]
+    NotifyUpdateRefreshed,
+
+    #[serde(rename = "notify_gcode_response")]
+    NotifyGcodeResponse,
+
+    #[serde(rename = "machine.device_power.devices")]
+    PowerDevices,
+
+    #[serde(rename = "machine.device_power.get_device")]
+    PowerGetDevice,
+
+    #[serde(rename = "machine.device_power.post_device")]
+    PowerPostDevice,
+
+    #[serde(rename = "notify_power_changed")]
+    NotifyPowerChanged,
+
+    #[serde(rename = "machine.system_info")]
+    MachineSystemInfo,
+
+    #[serde(rename = "machine.proc_stats")]
+    MachineProcStats,
 }
 
 pub fn get_method_id(method: &MoonrakerMethod) -> u16 {
     match method {
         MoonrakerMethod::FilesMetadata => 3545,
         MoonrakerMethod::PrinterObjectsSubscribe => 5434,
+        MoonrakerMethod::PrinterObjectsQuery => 5435,
         MoonrakerMethod::PrintPause => 4564,
         MoonrakerMethod::PrintResume => 1485,
         MoonrakerMethod::PrintCancel => 2578,
+        MoonrakerMethod::PrintStart => 2579,
         MoonrakerMethod::PrinterRestart => 4894,
         MoonrakerMethod::FirmwareRestart => 8463,
         MoonrakerMethod::EmergencyStop => 4564,
         MoonrakerMethod::GcodeScript => 4645,
         MoonrakerMethod::PrinterObjectsList => 1454,
+        MoonrakerMethod::ServerInfo => 7214,
+        MoonrakerMethod::PrinterInfo => 7215,
+        MoonrakerMethod::DatabaseGetItem => 7301,
+        MoonrakerMethod::HistoryGetJob => 7302,
+        MoonrakerMethod::TemperatureStore => 7303,
+        MoonrakerMethod::PowerDevices => 7304,
+        MoonrakerMethod::PowerGetDevice => 7305,
+        MoonrakerMethod::PowerPostDevice => 7306,
+        MoonrakerMethod::MachineSystemInfo => 7307,
+        MoonrakerMethod::MachineProcStats => 7308,
+        MoonrakerMethod::MachineUpdateStatus => 6731,
+        MoonrakerMethod::MachineUpdateRefresh => 6732,
+        MoonrakerMethod::MachineUpdateUpgrade => 6733,
         MoonrakerMethod::NotifyKlippyReady => 0,
+        MoonrakerMethod::NotifyKlippyShutdown => 0,
+        MoonrakerMethod::NotifyKlippyDisconnected => 0,
         MoonrakerMethod::NotifyStatusUpdate => 0,
         MoonrakerMethod::NotifyProcStatUpdate => 0,
+        MoonrakerMethod::NotifyUpdateResponse => 0,
+        MoonrakerMethod::NotifyUpdateRefreshed => 0,
+        MoonrakerMethod::NotifyGcodeResponse => 0,
+        MoonrakerMethod::NotifyPowerChanged => 0,
     }
 }
 
@@ -65,14 +149,29 @@ pub fn get_method_from_id(id: u16) -> Result<MoonrakerMethod> {
     match id {
         3545 => Ok(MoonrakerMethod::FilesMetadata),
         5434 => Ok(MoonrakerMethod::PrinterObjectsSubscribe),
+        5435 => Ok(MoonrakerMethod::PrinterObjectsQuery),
         4564 => Ok(MoonrakerMethod::PrintPause),
         1485 => Ok(MoonrakerMethod::PrintResume),
         2578 => Ok(MoonrakerMethod::PrintCancel),
+        2579 => Ok(MoonrakerMethod::PrintStart),
         4894 => Ok(MoonrakerMethod::PrinterRestart),
         8463 => Ok(MoonrakerMethod::FirmwareRestart),
         //4564 => Ok(MoonrakerMethod::EmergencyStop), // WHY THE FUCK MOONRAKER???
         4645 => Ok(MoonrakerMethod::GcodeScript),
         1454 => Ok(MoonrakerMethod::PrinterObjectsList),
+        7214 => Ok(MoonrakerMethod::ServerInfo),
+        7215 => Ok(MoonrakerMethod::PrinterInfo),
+        7301 => Ok(MoonrakerMethod::DatabaseGetItem),
+        7302 => Ok(MoonrakerMethod::HistoryGetJob),
+        7303 => Ok(MoonrakerMethod::TemperatureStore),
+        7304 => Ok(MoonrakerMethod::PowerDevices),
+        7305 => Ok(MoonrakerMethod::PowerGetDevice),
+        7306 => Ok(MoonrakerMethod::PowerPostDevice),
+        7307 => Ok(MoonrakerMethod::MachineSystemInfo),
+        7308 => Ok(MoonrakerMethod::MachineProcStats),
+        6731 => Ok(MoonrakerMethod::MachineUpdateStatus),
+        6732 => Ok(MoonrakerMethod::MachineUpdateRefresh),
+        6733 => Ok(MoonrakerMethod::MachineUpdateUpgrade),
         _ => Err(anyhow::anyhow!("Unknown method id: {}", id)),
     }
 }
@@ -7,24 +7,134 @@ use serde_json::Value;
 #[serde(untagged)]
 pub enum MoonrakerParam {
     None,
+    /// Also used for `printer.objects.query`
     PrinterObjectsSubscribe {
         objects: HashMap<String, Option<Vec<String>>>,
     },
 
     NotifyProcStatUpdate(NotifyProcStatUpdateRes),
     NotifyStatusUpdate(HashMap<String, HashMap<String, Value>>, f64),
+    NotifyUpdateResponse(UpdateResponse),
+    NotifyUpdateRefreshed(UpdateStatus),
+    /// Console output, `["// message"]`. Must be before the single-field structs,
+    /// those can be parsed from a one-element array too
+    NotifyGcodeResponse(Vec<String>),
+    /// Must be before `PowerDevice`, that one would match the notification too
+    NotifyPowerChanged(PowerDevice),
+    /// Also used for `printer.print.start`
     FilesMetadata {
         filename: String,
     },
     GcodeScript {
         script: String,
     },
+    DatabaseItem {
+        namespace: String,
+        key: String,
+    },
+    HistoryJob {
+        uid: String,
+    },
+    /// `machine.device_power.get_device` without action, `post_device` with it
+    PowerDevice {
+        device: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        action: Option<PowerAction>,
+    },
+
+    // Only optional fields, must stay after the notifications
+    // (untagged enum picks the first variant that matches)
+    /// Without name every component is refreshed/upgraded
+    MachineUpdate {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+}
+
+/// Result of `server.database.get_item`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatabaseItem {
+    pub namespace: String,
+    pub key: String,
+    pub value: Value,
+}
+
+/// Result of `machine.update.status` and param of `notify_update_refreshed`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpdateStatus {
+    pub busy: bool,
+    pub version_info: HashMap<String, UpdateComponentInfo>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpdateComponentInfo {
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub remote_version: Option<String>,
+    /// Only for `system` (OS packages)
+    #[serde(default)]
+    pub package_count: Option<u64>,
+    #[serde(default)]
+    pub is_valid: Option<bool>,
+}
+
+impl UpdateComponentInfo {
+    pub fn has_update(&self) -> bool {
+        if let Some(package_count) = self.package_count {
+            return package_count > 0;
+        }
+
+        match (&self.version, &self.remote_version) {
+            (Some(version), Some(remote_version)) => {
+                remote_version != "?" && version != remote_version
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerAction {
+    On,
+    Off,
+    Toggle,
+}
+
+/// Result of `machine.device_power.devices`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PowerDevices {
+    pub devices: Vec<PowerDevice>,
+}
+
+/// Device in `machine.device_power.devices` and param of `notify_power_changed`.
+/// `get_device`/`post_device` return only `{"<device>": "<status>"}`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PowerDevice {
+    pub device: String,
+    /// "on", "off", "init" or "error"
+    pub status: String,
+    #[serde(default)]
+    pub locked_while_printing: bool,
+    /// "gpio", "tplink_smartplug", "klipper_device" ...
+    #[serde(rename = "type", default)]
+    pub kind: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpdateResponse {
+    pub application: String,
+    pub proc_id: u64,
+    pub message: String,
+    pub complete: bool,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct NotifyProcStatUpdateRes {
     pub moonraker_stats: MoonrakerStats,
-    pub cpu_temp: f64,
+    /// None when the host has no CPU temperature sensor
+    pub cpu_temp: Option<f64>,
     pub system_cpu_usage: SystemCpuUsage,
     pub system_memory: SystemMemory,
     pub websocket_connections: u64,
@@ -64,11 +174,29 @@ pub struct NetworkStats {
     pub bandwidth: f64,
 }
 
+/// `cpu` is the total, every core is reported as `cpu0`, `cpu1` ...
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
 pub struct SystemCpuUsage {
     pub cpu: f64,
-    pub cpu0: f64,
+    #[serde(flatten)]
+    pub cores: HashMap<String, f64>,
+}
+
+impl SystemCpuUsage {
+    /// Usage of every core ordered by core number
+    pub fn cores(&self) -> Vec<f64> {
+        let mut cores = self
+            .cores
+            .iter()
+            .filter_map(|(name, usage)| {
+                let index = name.strip_prefix("cpu")?.parse::<usize>().ok()?;
+                Some((index, *usage))
+            })
+            .collect::<Vec<_>>();
+        cores.sort_by_key(|(index, _)| *index);
+
+        cores.into_iter().map(|(_, usage)| usage).collect()
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -78,3 +206,42 @@ pub struct SystemMemory {
     pub available: i64,
     pub used: i64,
 }
+
+/// Result of `machine.proc_stats`, `moonraker_stats` is a history there so it's left out
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcStats {
+    pub cpu_temp: Option<f64>,
+    pub system_cpu_usage: SystemCpuUsage,
+    pub system_memory: SystemMemory,
+    pub network: HashMap<String, NetworkStats>,
+    /// Seconds since the host booted
+    pub system_uptime: f64,
+}
+
+/// Result of `machine.system_info`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SystemInfoRoot {
+    pub system_info: SystemInfo,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SystemInfo {
+    pub network: HashMap<String, NetworkInterface>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkInterface {
+    pub mac_address: String,
+    pub ip_addresses: Vec<IpAddress>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IpAddress {
+    /// "ipv4" or "ipv6"
+    pub family: String,
+    pub address: String,
+    pub is_link_local: bool,
+}
@@ -7,7 +7,7 @@ use hyper::{
     Body, Request,
 };
 use std::future::Future;
-use tokio::net::TcpStream;
+use tokio::{net::TcpStream, sync::watch};
 
 struct SpawnExecutor;
 impl<Fut> hyper::rt::Executor<Fut> for SpawnExecutor
@@ -23,35 +23,82 @@ where
 type MoonrakerMsgRx = tokio::sync::mpsc::UnboundedReceiver<MoonrakerMsg>;
 type MoonrakerMsgTx = tokio::sync::mpsc::UnboundedSender<MoonrakerMsg>;
 
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+    /// Sent as `X-Api-Key` header when moonraker requires authorization
+    pub api_key: Option<String>,
+    /// Forward `notify_proc_stat_update` (sent every second), dropped when false
+    pub proc_stats: bool,
+}
+
+/// State of the websocket connection itself (not klippy)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    /// Last connection error, reconnect is attempted every 5 seconds
+    Disconnected(String),
+}
+
+pub struct MoonrakerConnection {
+    pub tx: MoonrakerMsgTx,
+    pub rx: MoonrakerMsgRx,
+    pub state: watch::Receiver<ConnectionState>,
+}
+
 pub async fn connect(moonraker_api_url: &str) -> Result<(MoonrakerMsgTx, MoonrakerMsgRx)> {
+    let connection = connect_with_options(moonraker_api_url, ConnectOptions::default()).await?;
+    Ok((connection.tx, connection.rx))
+}
+
+pub async fn connect_with_options(
+    moonraker_api_url: &str,
+    options: ConnectOptions,
+) -> Result<MoonrakerConnection> {
     let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<MoonrakerMsg>();
     let (out_tx, out_rx) = tokio::sync::mpsc::unbounded_channel::<MoonrakerMsg>();
+    let (state_tx, state_rx) = watch::channel(ConnectionState::Connecting);
 
     let moonraker_api_url = moonraker_api_url.to_string();
 
     tokio::spawn(async move {
         loop {
-            println!("DBG: Attempting to connect to moonraker websocket...");
-            let res = ws_connection(&moonraker_api_url, &out_tx, &mut rx).await;
+            let res =
+                ws_connection(&moonraker_api_url, &options, &out_tx, &mut rx, &state_tx).await;
             if let Err(e) = res {
-                println!("DBG Error: {}", e);
+                let state = ConnectionState::Disconnected(e.to_string());
+
+                // log only changes, not every reconnect attempt
+                if *state_tx.borrow() != state {
+                    println!(
+                        "DBG: Moonraker connection error: {}, reconnecting every 5 seconds",
+                        e
+                    );
+                }
+                _ = state_tx.send(state);
             }
 
-            println!("DBG: Reconnecting in 5 seconds...");
             tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
         }
     });
 
-    Ok((tx, out_rx))
+    Ok(MoonrakerConnection {
+        tx,
+        rx: out_rx,
+        state: state_rx,
+    })
 }
 
 async fn ws_connection(
     moonraker_api_url: &str,
+    options: &ConnectOptions,
     tx: &MoonrakerMsgTx,
     rx: &mut MoonrakerMsgRx,
+    state_tx: &watch::Sender<ConnectionState>,
 ) -> Result<()> {
-    let mut ws = connect_to_ws(moonraker_api_url).await?;
+    let mut ws = connect_to_ws(moonraker_api_url, options).await?;
     println!("DBG: Connected to moonraker websocket");
+    _ = state_tx.send(ConnectionState::Connected);
 
     loop {
         tokio::select! {
@@ -59,7 +106,7 @@ async fn ws_connection(
                 let json = msg.to_json();
                 let payload = json.as_bytes();
 
-                ws.write_frame(Frame::text(payload.into())).await.unwrap();
+                ws.write_frame(Frame::text(payload.into())).await?;
             }
             msg = ws.read_frame() => {
                 if let Err(e) = msg {
@@ -78,12 +125,14 @@ async fn ws_connection(
                         params: _,
                     } = msg.clone()
                     {
-                        if method == crate::MoonrakerMethod::NotifyProcStatUpdate {
+                        if !options.proc_stats
+                            && method == crate::MoonrakerMethod::NotifyProcStatUpdate
+                        {
                             continue;
                         }
                     }
 
-                    tx.send(msg).unwrap();
+                    tx.send(msg)?;
                 } else {
                     //println!("DBG: {}", msg.err().unwrap());
                     //println!("DBG: Received: {}", json);
@@ -93,10 +142,18 @@ async fn ws_connection(
     }
 }
 
-async fn connect_to_ws(moonraker_api_url: &str) -> Result<FragmentCollector<Upgraded>> {
+async fn connect_to_ws(
+    moonraker_api_url: &str,
+    options: &ConnectOptions,
+) -> Result<FragmentCollector<Upgraded>> {
     let stream = TcpStream::connect(moonraker_api_url).await?;
 
-    let req = Request::builder()
+    let mut req = Request::builder();
+    if let Some(api_key) = &options.api_key {
+        req = req.header("X-Api-Key", api_key);
+    }
+
+    let req = req
         .method("GET")
         .uri("/websocket")
         .header("Host", moonraker_api_url)
@@ -1,5 +1,15 @@
 #!/bin/bash
 
+# Releases are signed with minisign, generate key once with:
+#   minisign -G -p ~/.minisign/serial-screen.pub -s ~/.minisign/serial-screen.key
+minisign_key=~/.minisign/serial-screen.key
+minisign_pub=~/.minisign/serial-screen.pub
+
+if [ ! -f "$minisign_key" ]; then
+    echo "Missing signing key: $minisign_key"
+    exit 1
+fi
+
 last=$(curl -s https://api.github.com/repos/filipton/dgus-moonraker-screen/releases/latest | jq -r .tag_name)
 echo "Last release: $last"
 
@@ -7,5 +17,19 @@ echo -n "Release version (for eg. 2.4.1): "
 read version
 
 echo "pub const VERSION: &str = \"$version\";" > ./serial-screen/src/version.rs
+export SERIAL_SCREEN_UPDATE_KEY=$(tail -n 1 "$minisign_pub")
 bash ./build-all.sh
+
+# package for moonraker's update_manager (type: web)
+mkdir ./dist/web
+cp ./dist/serial-screen-* ./launch.sh ./dist/web/
+echo "{\"project_name\": \"dgus-moonraker-screen\", \"project_owner\": \"filipton\", \"version\": \"$version\"}" > ./dist/web/release_info.json
+(cd ./dist/web && zip -q ../serial-screen.zip *)
+rm -r ./dist/web
+
+cd ./dist
+sha256sum serial-screen-* serial-screen.zip > SHA256SUMS
+minisign -S -s "$minisign_key" -m SHA256SUMS
+cd ..
+
 gh release create --latest --generate-notes $version ./dist/*
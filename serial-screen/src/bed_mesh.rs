@@ -0,0 +1,262 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use moonraker_api::{MoonrakerMethod, MoonrakerMsg, MoonrakerParam};
+use serde_json::Value;
+use tokio::sync::RwLock;
+
+use crate::{
+    layout::{BedMeshButtonKeys, Canvas},
+    moonraker::{MoonrakerTx, PrinterState},
+    screen_state::ScreenState,
+};
+
+/// Probe points drawn at most (DGUS draws everything in one go)
+const MAX_RECTANGLES: usize = 400;
+
+/// `bed_mesh` status, only with [bed_mesh] in klipper config
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BedMesh {
+    pub profile_name: String,
+    pub mesh_min: [f64; 2],
+    pub mesh_max: [f64; 2],
+    /// Rows go along Y from mesh_min, columns along X, empty when no mesh is loaded
+    pub probed_matrix: Vec<Vec<f64>>,
+    /// Saved profile names, sorted
+    pub profiles: Vec<String>,
+}
+
+impl BedMesh {
+    /// Reads active profile, mesh bounds, probed points (empty rows of a cleared mesh are dropped)
+    /// and names of saved profiles
+    pub fn update(&mut self, status: &HashMap<String, Value>) {
+        if let Some(profile_name) = status.get("profile_name") {
+            self.profile_name = profile_name.as_str().unwrap_or("").to_string();
+        }
+        if let Some(mesh_min) = status.get("mesh_min") {
+            self.mesh_min = serde_json::from_value(mesh_min.clone()).unwrap_or_default();
+        }
+        if let Some(mesh_max) = status.get("mesh_max") {
+            self.mesh_max = serde_json::from_value(mesh_max.clone()).unwrap_or_default();
+        }
+        if let Some(probed_matrix) = status.get("probed_matrix") {
+            let matrix: Vec<Vec<f64>> =
+                serde_json::from_value(probed_matrix.clone()).unwrap_or_default();
+            // cleared mesh is [[]]
+            self.probed_matrix = matrix.into_iter().filter(|row| !row.is_empty()).collect();
+        }
+        if let Some(profiles) = status.get("profiles").and_then(|p| p.as_object()) {
+            self.profiles = profiles.keys().cloned().collect();
+            self.profiles.sort();
+        }
+    }
+
+    fn values(&self) -> impl Iterator<Item = f64> + '_ {
+        self.probed_matrix.iter().flatten().copied()
+    }
+
+    /// (min, max) of probed points
+    fn min_max(&self) -> Option<(f64, f64)> {
+        self.values().fold(None, |acc, value| match acc {
+            Some((min, max)) => Some((value.min(min), value.max(max))),
+            None => Some((value, value)),
+        })
+    }
+
+    /// "Min -0.050  Max +0.075 mm"
+    pub fn range_text(&self) -> String {
+        match self.min_max() {
+            Some((min, max)) => format!("Min {:+.3}  Max {:+.3} mm", min, max),
+            None => "No mesh loaded".to_string(),
+        }
+    }
+
+    /// "Range 0.125  Deviation 0.031 mm", deviation is the standard deviation of probed points
+    pub fn deviation_text(&self) -> String {
+        let (min, max) = match self.min_max() {
+            Some(min_max) => min_max,
+            None => return String::new(),
+        };
+
+        let count = self.values().count() as f64;
+        let mean = self.values().sum::<f64>() / count;
+        let variance = self
+            .values()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / count;
+
+        format!(
+            "Range {:.3}  Deviation {:.3} mm",
+            max - min,
+            variance.sqrt()
+        )
+    }
+
+    pub fn line(&self, profile: &str) -> String {
+        match profile == self.profile_name {
+            true => format!("> {}", profile),
+            false => profile.to_string(),
+        }
+    }
+
+    /// Probe point cells scaled from bed (axis min/max) to canvas, colored from blue (lowest) to red (highest)
+    pub fn rectangles(
+        &self,
+        canvas: &Canvas,
+        axis_minimum: &[f64; 3],
+        axis_maximum: &[f64; 3],
+    ) -> Vec<([u16; 4], u16)> {
+        let (min, max) = match self.min_max() {
+            Some(min_max) => min_max,
+            None => return Vec::new(),
+        };
+        let rows = self.probed_matrix.len();
+        let columns = self.probed_matrix[0].len();
+
+        let bed_width = (axis_maximum[0] - axis_minimum[0]).max(1.0);
+        let bed_depth = (axis_maximum[1] - axis_minimum[1]).max(1.0);
+        // every probe point gets a cell around it (klipper probes at least 3 points per axis)
+        let step_x = (self.mesh_max[0] - self.mesh_min[0]) / (columns.max(2) - 1) as f64;
+        let step_y = (self.mesh_max[1] - self.mesh_min[1]) / (rows.max(2) - 1) as f64;
+        let screen_x = |x: f64| {
+            let x = (x - axis_minimum[0]) / bed_width * canvas.width as f64;
+            canvas.x + x.clamp(0.0, canvas.width as f64) as u16
+        };
+        let screen_y = |y: f64| {
+            let y = (y - axis_minimum[1]) / bed_depth * canvas.height as f64;
+            canvas.y + canvas.height - y.clamp(0.0, canvas.height as f64) as u16
+        };
+
+        let mut rectangles = Vec::new();
+        for (row_idx, row) in self.probed_matrix.iter().enumerate() {
+            for (column_idx, value) in row.iter().enumerate() {
+                let x = self.mesh_min[0] + step_x * column_idx as f64;
+                let y = self.mesh_min[1] + step_y * row_idx as f64;
+
+                rectangles.push((
+                    [
+                        screen_x(x - step_x / 2.0),
+                        screen_y(y + step_y / 2.0),
+                        screen_x(x + step_x / 2.0),
+                        screen_y(y - step_y / 2.0),
+                    ],
+                    heat_color(*value, min, max),
+                ));
+            }
+        }
+
+        rectangles.truncate(MAX_RECTANGLES);
+        rectangles
+    }
+}
+
+/// RGB565 blue -> green -> red, flat mesh is all green
+fn heat_color(value: f64, min: f64, max: f64) -> u16 {
+    let ratio = match max - min > f64::EPSILON {
+        true => ((value - min) / (max - min)).clamp(0.0, 1.0),
+        false => 0.5,
+    };
+
+    let (red, green, blue) = if ratio < 0.5 {
+        let ratio = ratio * 2.0;
+        (0.0, 63.0 * ratio, 31.0 * (1.0 - ratio))
+    } else {
+        let ratio = (ratio - 0.5) * 2.0;
+        (31.0 * ratio, 63.0 * (1.0 - ratio), 0.0)
+    };
+
+    ((red.round() as u16) << 11) | ((green.round() as u16) << 5) | blue.round() as u16
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BedMeshButton {
+    Undefined(u16),
+
+    Up,
+    Down,
+    Line(usize),
+    Clear,
+}
+
+impl BedMeshButton {
+    pub fn from_id(id: u16, keys: &BedMeshButtonKeys) -> Self {
+        if let Some(idx) = keys.lines.iter().position(|&code| code == id) {
+            return BedMeshButton::Line(idx);
+        }
+
+        match id {
+            id if id == keys.up => BedMeshButton::Up,
+            id if id == keys.down => BedMeshButton::Down,
+            id if id == keys.clear => BedMeshButton::Clear,
+            _ => BedMeshButton::Undefined(id),
+        }
+    }
+}
+
+pub async fn parse_bed_mesh_button(
+    button: BedMeshButton,
+    moonraker_tx: &MoonrakerTx,
+    screen_state: &Arc<RwLock<ScreenState>>,
+) -> Result<()> {
+    let moonraker_tx = moonraker_tx.lock().await;
+    let mut screen_state = screen_state.write().await;
+
+    // mesh is applied to the moves of the running print
+    let printing = screen_state.printer_state == PrinterState::Printing
+        || screen_state.printer_state == PrinterState::Paused;
+
+    match button {
+        BedMeshButton::Up => {
+            if screen_state.mesh_scroll > 0 {
+                screen_state.mesh_scroll -= 1;
+            }
+        }
+        BedMeshButton::Down => {
+            if screen_state.mesh_scroll + 1 < screen_state.bed_mesh.profiles.len() {
+                screen_state.mesh_scroll += 1;
+            }
+        }
+        BedMeshButton::Line(idx) => {
+            let profile = screen_state
+                .bed_mesh
+                .profiles
+                .get(screen_state.mesh_scroll + idx)
+                .cloned();
+
+            if let Some(profile) = profile {
+                if printing {
+                    screen_state.mesh_message = "Mesh can't be changed while printing".into();
+                    return Ok(());
+                }
+
+                moonraker_tx.send(MoonrakerMsg::new_param_id(
+                    MoonrakerMethod::GcodeScript,
+                    MoonrakerParam::GcodeScript {
+                        script: format!("BED_MESH_PROFILE LOAD={}", profile),
+                    },
+                ))?;
+                screen_state.mesh_message = format!("Loaded '{}'", profile);
+            }
+        }
+        BedMeshButton::Clear => {
+            if printing {
+                screen_state.mesh_message = "Mesh can't be changed while printing".into();
+                return Ok(());
+            }
+
+            moonraker_tx.send(MoonrakerMsg::new_param_id(
+                MoonrakerMethod::GcodeScript,
+                MoonrakerParam::GcodeScript {
+                    script: "BED_MESH_CLEAR".to_string(),
+                },
+            ))?;
+            screen_state.mesh_message = "Mesh cleared".into();
+        }
+        BedMeshButton::Undefined(id) => {
+            println!("Undefined bed mesh button pressed with ID: {}", id);
+        }
+    }
+
+    Ok(())
+}
@@ -4,9 +4,14 @@ use anyhow::Result;
 use tokio::sync::{mpsc::UnboundedSender, Mutex, RwLock};
 
 use crate::{
+    config::ConfigRef,
+    confirm::{self, ConfirmAction},
+    layout::{ButtonKeys, Layout},
     moonraker::{MoonrakerTx, PrinterState},
+    preheat::{self, PreheatTarget},
     screen_state::ScreenState,
-    serial_utils::construct_change_page,
+    serial_utils::{construct_change_page, construct_i16},
+    system,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -27,35 +32,60 @@ pub enum Button {
     PreheatCustom,
     MacrosUP,
     MacrosDOWN,
-    MacrosButton1,
-    MacrosButton2,
-    MacrosButton3,
-    MacrosButton4,
+    RunMacro(usize),
     Macros,
+    Updates,
+    FirmwareRestart,
+    HostRestart,
+    ErrorDismiss,
+    Extruder,
+    Tune,
+    ExcludeObjects,
+    TempGraph,
+    BedMesh,
+    Calibrate,
+    Sensors,
+    Outputs,
+    Power,
+    System,
 }
 
 impl Button {
-    pub fn from_id(id: u16) -> Self {
+    pub fn from_id(id: u16, keys: &ButtonKeys) -> Self {
+        if let Some(idx) = keys.macros_buttons.iter().position(|&code| code == id) {
+            return Button::RunMacro(idx);
+        }
+
         match id {
-            1 => Button::PrintingProgress,
-            2 => Button::EmergencyStop,
-            3 => Button::Preheat,
-            4 => Button::PrinterMovement,
-            5 => Button::Settings,
-            6 => Button::BackToMain,
-            7 => Button::Pause,
-            8 => Button::Stop,
-            9 => Button::EmergencyStopRelease,
-            10 => Button::PreheatPla,
-            11 => Button::PreheatCooldown,
-            12 => Button::PreheatCustom,
-            13 => Button::MacrosUP,
-            14 => Button::MacrosDOWN,
-            15 => Button::MacrosButton1,
-            16 => Button::MacrosButton2,
-            17 => Button::MacrosButton3,
-            18 => Button::MacrosButton4,
-            19 => Button::Macros,
+            id if id == keys.printing_progress => Button::PrintingProgress,
+            id if id == keys.emergency_stop => Button::EmergencyStop,
+            id if id == keys.preheat => Button::Preheat,
+            id if id == keys.printer_movement => Button::PrinterMovement,
+            id if id == keys.settings => Button::Settings,
+            id if id == keys.back_to_main => Button::BackToMain,
+            id if id == keys.pause => Button::Pause,
+            id if id == keys.stop => Button::Stop,
+            id if id == keys.emergency_stop_release => Button::EmergencyStopRelease,
+            id if id == keys.preheat_pla => Button::PreheatPla,
+            id if id == keys.preheat_cooldown => Button::PreheatCooldown,
+            id if id == keys.preheat_custom => Button::PreheatCustom,
+            id if id == keys.macros_up => Button::MacrosUP,
+            id if id == keys.macros_down => Button::MacrosDOWN,
+            id if id == keys.macros => Button::Macros,
+            id if id == keys.updates => Button::Updates,
+            id if id == keys.firmware_restart => Button::FirmwareRestart,
+            id if id == keys.host_restart => Button::HostRestart,
+            id if id == keys.error_dismiss => Button::ErrorDismiss,
+            id if id == keys.extruder => Button::Extruder,
+            id if id == keys.tune => Button::Tune,
+            id if id == keys.exclude_objects => Button::ExcludeObjects,
+            id if id == keys.temp_graph => Button::TempGraph,
+            id if id == keys.bed_mesh => Button::BedMesh,
+            id if id == keys.calibrate => Button::Calibrate,
+            id if id == keys.sensors => Button::Sensors,
+            id if id == keys.outputs => Button::Outputs,
+            id if id == keys.power => Button::Power,
+            id if id == keys.system => Button::System,
             _ => Button::Undefined(id),
         }
     }
@@ -66,38 +96,48 @@ pub async fn parse_button_click(
     moonraker_tx: &MoonrakerTx,
     screen_state: &Arc<RwLock<ScreenState>>,
     serial_tx: &Arc<Mutex<UnboundedSender<Vec<u8>>>>,
+    layout: &Layout,
+    config: &ConfigRef,
 ) -> Result<()> {
     let serial_tx = serial_tx.lock().await;
     let moonraker_tx = moonraker_tx.lock().await;
     let mut screen_state = screen_state.write().await;
+    let confirm_config = config.read().await.confirm.clone();
 
     match button {
         Button::PrintingProgress => {
-            serial_tx.send(construct_change_page(2))?;
+            serial_tx.send(construct_change_page(layout.pages.printing))?;
         }
         Button::EmergencyStop => {
-            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_with_method_and_id(
-                moonraker_api::MoonrakerMethod::EmergencyStop,
-            ))?;
-
-            serial_tx.send(construct_change_page(3))?;
+            confirm::request(
+                ConfirmAction::EmergencyStop,
+                &mut screen_state,
+                &confirm_config,
+                &moonraker_tx,
+                &serial_tx,
+                layout,
+            )?;
         }
         Button::Preheat => {
-            serial_tx.send(construct_change_page(4))?;
+            // presets could have been changed in Mainsail
+            preheat::request_mainsail_presets(&moonraker_tx)?;
+            screen_state.preheat_message = String::new();
+
+            serial_tx.send(construct_change_page(layout.pages.preheat))?;
         }
         Button::PrinterMovement => {
             if screen_state.printer_state != PrinterState::Printing
                 && screen_state.printer_state != PrinterState::Paused
             {
-                serial_tx.send(construct_change_page(5))?;
+                serial_tx.send(construct_change_page(layout.pages.movement))?;
                 return Ok(());
             }
         }
         Button::Settings => {
-            //serial_tx.send(construct_change_page(7))?;
+            serial_tx.send(construct_change_page(layout.pages.settings))?;
         }
         Button::BackToMain => {
-            serial_tx.send(construct_change_page(1))?;
+            serial_tx.send(construct_change_page(layout.pages.main))?;
         }
         Button::Pause => {
             if screen_state.printer_state == PrinterState::Paused {
@@ -111,9 +151,14 @@ pub async fn parse_button_click(
             }
         }
         Button::Stop => {
-            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_with_method_and_id(
-                moonraker_api::MoonrakerMethod::PrintCancel,
-            ))?;
+            confirm::request(
+                ConfirmAction::CancelPrint,
+                &mut screen_state,
+                &confirm_config,
+                &moonraker_tx,
+                &serial_tx,
+                layout,
+            )?;
         }
         Button::EmergencyStopRelease => {
             moonraker_tx.send(moonraker_api::MoonrakerMsg::new_with_method_and_id(
@@ -121,26 +166,28 @@ pub async fn parse_button_click(
             ))?;
         }
         Button::PreheatPla => {
-            if screen_state.printer_state == PrinterState::Printing
-                || screen_state.printer_state == PrinterState::Paused
-            {
-                return Ok(());
-            }
+            // mainsail presets are sorted by name, so the first one is not necessarily PLA
+            let quick_preset = config.read().await.preheat.quick_preset.clone();
+            let preset = screen_state
+                .presets
+                .iter()
+                .find(|preset| preset.name.eq_ignore_ascii_case(&quick_preset))
+                .cloned();
 
-            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
-                moonraker_api::MoonrakerMethod::GcodeScript,
-                moonraker_api::MoonrakerParam::GcodeScript {
-                    script: "SET_HEATER_TEMPERATURE HEATER=extruder TARGET=200".to_string(),
-                },
-            ))?;
+            match preset {
+                Some(preset) => preheat::apply_preset(
+                    &preset,
+                    PreheatTarget::All,
+                    &moonraker_tx,
+                    &mut screen_state,
+                )?,
+                None => {
+                    screen_state.preheat_message = format!("No '{}' preset", quick_preset);
+                    return Ok(());
+                }
+            }
 
-            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
-                moonraker_api::MoonrakerMethod::GcodeScript,
-                moonraker_api::MoonrakerParam::GcodeScript {
-                    script: "SET_HEATER_TEMPERATURE HEATER=heater_bed TARGET=45".to_string(),
-                },
-            ))?;
-            serial_tx.send(construct_change_page(1))?;
+            serial_tx.send(construct_change_page(layout.pages.main))?;
         }
         Button::PreheatCooldown => {
             if screen_state.printer_state == PrinterState::Printing
@@ -155,23 +202,36 @@ pub async fn parse_button_click(
                     script: "TURN_OFF_HEATERS".to_string(),
                 },
             ))?;
-            serial_tx.send(construct_change_page(1))?;
+            serial_tx.send(construct_change_page(layout.pages.main))?;
+        }
+        Button::PreheatCustom => {
+            // keypad starts from current targets
+            serial_tx.send(construct_i16(
+                layout.inputs.nozzle_target,
+                screen_state.target_nozzle_temp,
+            ))?;
+            serial_tx.send(construct_i16(
+                layout.inputs.bed_target,
+                screen_state.target_bed_temp,
+            ))?;
+            screen_state.preheat_message = String::new();
+
+            serial_tx.send(construct_change_page(layout.pages.custom_preheat))?;
         }
-        Button::PreheatCustom => {}
         Button::MacrosUP => {
             if screen_state.macros_scroll > 0 {
                 screen_state.macros_scroll -= 1;
-                screen_state.update_macros_list(&serial_tx).await?;
+                screen_state.update_macros_list(&serial_tx, layout).await?;
             }
         }
         Button::MacrosDOWN => {
             if screen_state.macros_scroll < screen_state.macros.len() - 1 {
                 screen_state.macros_scroll += 1;
-                screen_state.update_macros_list(&serial_tx).await?;
+                screen_state.update_macros_list(&serial_tx, layout).await?;
             }
         }
-        Button::MacrosButton1 => {
-            let macro_value = screen_state.macros.get(screen_state.macros_scroll);
+        Button::RunMacro(idx) => {
+            let macro_value = screen_state.macros.get(screen_state.macros_scroll + idx);
 
             if let Some(macro_value) = macro_value {
                 moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
@@ -181,158 +241,94 @@ pub async fn parse_button_click(
                     },
                 ))?;
 
-                serial_tx.send(construct_change_page(1))?;
+                serial_tx.send(construct_change_page(layout.pages.main))?;
             }
         }
-        Button::MacrosButton2 => {
-            let macro_value = screen_state.macros.get(screen_state.macros_scroll + 1);
-
-            if let Some(macro_value) = macro_value {
-                moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
-                    moonraker_api::MoonrakerMethod::GcodeScript,
-                    moonraker_api::MoonrakerParam::GcodeScript {
-                        script: macro_value.to_string(),
-                    },
-                ))?;
-
-                serial_tx.send(construct_change_page(1))?;
-            }
+        Button::Macros => {
+            serial_tx.send(construct_change_page(layout.pages.macros))?;
         }
-        Button::MacrosButton3 => {
-            let macro_value = screen_state.macros.get(screen_state.macros_scroll + 2);
-
-            if let Some(macro_value) = macro_value {
-                moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
-                    moonraker_api::MoonrakerMethod::GcodeScript,
-                    moonraker_api::MoonrakerParam::GcodeScript {
-                        script: macro_value.to_string(),
-                    },
-                ))?;
+        Button::Updates => {
+            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_with_method_and_id(
+                moonraker_api::MoonrakerMethod::MachineUpdateStatus,
+            ))?;
 
-                serial_tx.send(construct_change_page(1))?;
-            }
+            serial_tx.send(construct_change_page(layout.pages.updates))?;
         }
-        Button::MacrosButton4 => {
-            let macro_value = screen_state.macros.get(screen_state.macros_scroll + 3);
-
-            if let Some(macro_value) = macro_value {
-                moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
-                    moonraker_api::MoonrakerMethod::GcodeScript,
-                    moonraker_api::MoonrakerParam::GcodeScript {
-                        script: macro_value.to_string(),
-                    },
-                ))?;
-
-                serial_tx.send(construct_change_page(1))?;
-            }
+        Button::FirmwareRestart => {
+            confirm::request(
+                ConfirmAction::FirmwareRestart,
+                &mut screen_state,
+                &confirm_config,
+                &moonraker_tx,
+                &serial_tx,
+                layout,
+            )?;
         }
-        Button::Macros => {
-            serial_tx.send(construct_change_page(6))?;
+        Button::HostRestart => {
+            confirm::request(
+                ConfirmAction::HostRestart,
+                &mut screen_state,
+                &confirm_config,
+                &moonraker_tx,
+                &serial_tx,
+                layout,
+            )?;
         }
-        Button::Undefined(id) => {
-            println!("Undefined button pressed with ID: {}", id);
+        Button::ErrorDismiss => {
+            // page is changed back by the screen update task
+            screen_state.error_dismissed = true;
         }
-    }
-
-    Ok(())
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum MovementButton {
-    Undefined(u16),
-
-    ZPlus,
-    ZMinus,
-    YPlus,
-    YMinus,
-    XPlus,
-    XMinus,
-    Home,
-}
-
-impl MovementButton {
-    pub fn from_id(id: u16) -> Self {
-        match id {
-            1 => MovementButton::YPlus,
-            2 => MovementButton::XPlus,
-            3 => MovementButton::XMinus,
-            4 => MovementButton::YMinus,
-            5 => MovementButton::ZPlus,
-            6 => MovementButton::ZMinus,
-            7 => MovementButton::Home,
-            _ => MovementButton::Undefined(id),
+        Button::Extruder => {
+            // filament can be changed while paused, but not while printing
+            if screen_state.printer_state != PrinterState::Printing {
+                screen_state.extruder_message = String::new();
+                serial_tx.send(construct_change_page(layout.pages.extruder))?;
+            }
         }
-    }
-}
-
-pub async fn parse_movement_button(
-    button: MovementButton,
-    moonraker_tx: &MoonrakerTx,
-    screen_state: &Arc<RwLock<ScreenState>>,
-    serial_tx: &Arc<Mutex<UnboundedSender<Vec<u8>>>>,
-) -> Result<()> {
-    let moonraker_tx = moonraker_tx.lock().await;
-    let screen_state = screen_state.read().await;
-
-    match button {
-        MovementButton::Home => {
-            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
-                moonraker_api::MoonrakerMethod::GcodeScript,
-                moonraker_api::MoonrakerParam::GcodeScript {
-                    script: "G28".to_string(),
-                },
-            ))?;
+        Button::Tune => {
+            screen_state.tune_message = String::new();
+            serial_tx.send(construct_change_page(layout.pages.tune))?;
         }
-        MovementButton::XPlus => {
-            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
-                moonraker_api::MoonrakerMethod::GcodeScript,
-                moonraker_api::MoonrakerParam::GcodeScript {
-                    script: "G91\nG1 X+10 F6000\nG90".to_string(),
-                },
-            ))?;
+        Button::ExcludeObjects => {
+            screen_state.exclude_pending = None;
+            screen_state.exclude_message = match screen_state.exclude_objects.objects.is_empty() {
+                true => "No objects (needs [exclude_object] and labeled gcode)".to_string(),
+                false => String::new(),
+            };
+            serial_tx.send(construct_change_page(layout.pages.exclude_objects))?;
         }
-        MovementButton::XMinus => {
-            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
-                moonraker_api::MoonrakerMethod::GcodeScript,
-                moonraker_api::MoonrakerParam::GcodeScript {
-                    script: "G91\nG1 X-10 F6000\nG90".to_string(),
-                },
-            ))?;
+        Button::TempGraph => {
+            serial_tx.send(construct_change_page(layout.pages.temp_graph))?;
         }
-        MovementButton::YPlus => {
-            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
-                moonraker_api::MoonrakerMethod::GcodeScript,
-                moonraker_api::MoonrakerParam::GcodeScript {
-                    script: "G91\nG1 Y+10 F6000\nG90".to_string(),
-                },
-            ))?;
+        Button::BedMesh => {
+            screen_state.mesh_message = String::new();
+            serial_tx.send(construct_change_page(layout.pages.bed_mesh))?;
         }
-        MovementButton::YMinus => {
-            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
-                moonraker_api::MoonrakerMethod::GcodeScript,
-                moonraker_api::MoonrakerParam::GcodeScript {
-                    script: "G91\nG1 Y-10 F6000\nG90".to_string(),
-                },
-            ))?;
+        Button::Calibrate => {
+            serial_tx.send(construct_change_page(layout.pages.calibrate))?;
         }
-        MovementButton::ZPlus => {
-            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
-                moonraker_api::MoonrakerMethod::GcodeScript,
-                moonraker_api::MoonrakerParam::GcodeScript {
-                    script: "G91\nG1 Z+10 F6000\nG90".to_string(),
-                },
-            ))?;
+        Button::Sensors => {
+            screen_state.sensor_message = String::new();
+            serial_tx.send(construct_change_page(layout.pages.sensors))?;
         }
-        MovementButton::ZMinus => {
-            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
-                moonraker_api::MoonrakerMethod::GcodeScript,
-                moonraker_api::MoonrakerParam::GcodeScript {
-                    script: "G91\nG1 Z-10 F6000\nG90".to_string(),
-                },
-            ))?;
+        Button::Outputs => {
+            screen_state.output_message = String::new();
+            serial_tx.send(construct_change_page(layout.pages.outputs))?;
         }
-        MovementButton::Undefined(id) => {
-            println!("Undefined movement button pressed with ID: {}", id);
+        Button::Power => {
+            screen_state.power_message = match screen_state.power_devices.is_empty() {
+                true => "No devices (needs [power] in moonraker.conf)".to_string(),
+                false => String::new(),
+            };
+            serial_tx.send(construct_change_page(layout.pages.power))?;
+        }
+        Button::System => {
+            system::request_system_info(&moonraker_tx)?;
+
+            serial_tx.send(construct_change_page(layout.pages.system))?;
+        }
+        Button::Undefined(id) => {
+            println!("Undefined button pressed with ID: {}", id);
         }
     }
 
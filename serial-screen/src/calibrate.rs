@@ -0,0 +1,427 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use serde_json::Value;
+use tokio::sync::RwLock;
+
+use crate::{
+    config::ConfigRef,
+    layout::CalibrateButtonKeys,
+    moonraker::{HomedAxes, MoonrakerTx, PrinterState},
+    screen_state::ScreenState,
+    utils::send_gcode_script,
+};
+
+/// `manual_probe` status, active while PROBE_CALIBRATE/Z_ENDSTOP_CALIBRATE waits for TESTZ/ACCEPT
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ManualProbe {
+    pub is_active: bool,
+    pub z_position: Option<f64>,
+    /// Bisection bounds, known after moving past the position once
+    pub z_position_lower: Option<f64>,
+    pub z_position_upper: Option<f64>,
+}
+
+impl ManualProbe {
+    /// Reads whether a TESTZ session is active, the current Z and its bisection bounds
+    pub fn update(&mut self, status: &HashMap<String, Value>) {
+        if let Some(is_active) = status.get("is_active") {
+            self.is_active = is_active.as_bool().unwrap_or(false);
+        }
+        if let Some(z_position) = status.get("z_position") {
+            self.z_position = z_position.as_f64();
+        }
+        if let Some(z_position_lower) = status.get("z_position_lower") {
+            self.z_position_lower = z_position_lower.as_f64();
+        }
+        if let Some(z_position_upper) = status.get("z_position_upper") {
+            self.z_position_upper = z_position_upper.as_f64();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PidHeater {
+    Extruder,
+    HeaterBed,
+}
+
+impl PidHeater {
+    fn name(self) -> &'static str {
+        match self {
+            PidHeater::Extruder => "extruder",
+            PidHeater::HeaterBed => "heater_bed",
+        }
+    }
+}
+
+/// Calibration flow, klipper does the work, the wizard follows manual_probe and console output
+#[derive(Debug, Clone, PartialEq)]
+pub enum CalibrationWizard {
+    Idle,
+    /// Homing and probing until manual_probe is active, then TESTZ until ACCEPT/ABORT
+    ZOffset {
+        active: bool,
+    },
+    /// SCREWS_TILT_CALCULATE, (screw, "CW 00:15") in the order they were reported
+    ScrewsTilt {
+        screws: Vec<(String, String)>,
+    },
+    Pid {
+        heater: PidHeater,
+        target: i16,
+    },
+    /// Result waits for SAVE_CONFIG (it restarts klipper)
+    SaveConfig {
+        result: String,
+    },
+}
+
+impl CalibrationWizard {
+    /// Step shown on the page (0 - idle, 1 - Z offset, 2 - screws, 3 - PID, 4 - save config prompt)
+    pub fn step(&self) -> i16 {
+        match self {
+            CalibrationWizard::Idle => 0,
+            CalibrationWizard::ZOffset { .. } => 1,
+            CalibrationWizard::ScrewsTilt { .. } => 2,
+            CalibrationWizard::Pid { .. } => 3,
+            CalibrationWizard::SaveConfig { .. } => 4,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match self {
+            CalibrationWizard::Idle => "Calibration".to_string(),
+            CalibrationWizard::ZOffset { .. } => "Z offset".to_string(),
+            CalibrationWizard::ScrewsTilt { .. } => "Bed screws".to_string(),
+            CalibrationWizard::Pid { heater, target } => {
+                format!("PID {} {}", heater.name(), target)
+            }
+            CalibrationWizard::SaveConfig { .. } => "Save config?".to_string(),
+        }
+    }
+
+    /// Instructions or results for the list lines
+    pub fn lines(&self, manual_probe: &ManualProbe) -> Vec<String> {
+        match self {
+            CalibrationWizard::Idle => Vec::new(),
+            CalibrationWizard::ZOffset { active: false } => {
+                vec!["Wait until the nozzle stops above the bed".to_string()]
+            }
+            CalibrationWizard::ZOffset { active: true } => {
+                let mut lines = vec![
+                    "Put a sheet of paper under the nozzle".to_string(),
+                    "Move Z until the paper drags slightly".to_string(),
+                    "Accept keeps the position, Abort cancels".to_string(),
+                ];
+                if let (Some(lower), Some(upper)) =
+                    (manual_probe.z_position_lower, manual_probe.z_position_upper)
+                {
+                    lines.push(format!("Between {:.3} and {:.3} mm", lower, upper));
+                }
+                lines
+            }
+            CalibrationWizard::ScrewsTilt { screws } => screws
+                .iter()
+                .map(|(screw, adjust)| format!("{}: {}", screw, adjust))
+                .collect(),
+            CalibrationWizard::Pid { .. } => vec![
+                "Heater cycles around the target".to_string(),
+                "This takes a few minutes".to_string(),
+            ],
+            CalibrationWizard::SaveConfig { result } => vec![
+                result.clone(),
+                "Save config writes it to printer.cfg".to_string(),
+                "and restarts Klipper".to_string(),
+            ],
+        }
+    }
+}
+
+/// "front right : x=155.0, y=30.0, z=2.36000 : adjust CW 01:15" -> ("front right", "CW 01:15"),
+/// the reference screw is "front left (base) : x=-5.0, y=30.0, z=2.48750"
+fn parse_screw(line: &str) -> Option<(String, String)> {
+    let mut parts = line.split(" : ");
+    let name = parts.next()?.trim();
+    let position = parts.next()?;
+    if !position.trim_start().starts_with("x=") {
+        return None;
+    }
+
+    if let Some(name) = name.strip_suffix("(base)") {
+        return Some((name.trim().to_string(), "base".to_string()));
+    }
+
+    let adjust = parts.next()?.trim().strip_prefix("adjust")?.trim();
+    Some((name.to_string(), adjust.to_string()))
+}
+
+/// "PID parameters: pid_Kp=22.865 pid_Ki=1.292 pid_Kd=101.178" -> "Kp 22.865 Ki 1.292 Kd 101.178"
+fn parse_pid(line: &str) -> Option<String> {
+    let parameters = line.strip_prefix("PID parameters:")?;
+    let values = parameters
+        .split_whitespace()
+        .filter_map(|parameter| parameter.strip_prefix("pid_"))
+        .map(|parameter| parameter.replace('=', " "))
+        .collect::<Vec<_>>();
+
+    Some(values.join(" "))
+}
+
+/// Console output of klipper (`notify_gcode_response`), may have several lines
+pub fn gcode_response(screen_state: &mut ScreenState, response: &str) {
+    for line in response.lines() {
+        if let Some(error) = line.strip_prefix("!! ") {
+            if matches!(
+                screen_state.calibration_wizard,
+                CalibrationWizard::ZOffset { .. }
+                    | CalibrationWizard::ScrewsTilt { .. }
+                    | CalibrationWizard::Pid { .. }
+            ) {
+                screen_state.calibration_wizard = CalibrationWizard::Idle;
+                screen_state.calibration_message = error.trim().to_string();
+            }
+            continue;
+        }
+
+        let line = line.trim_start_matches("//").trim();
+        match &mut screen_state.calibration_wizard {
+            CalibrationWizard::ScrewsTilt { screws } => {
+                if let Some((screw, adjust)) = parse_screw(line) {
+                    screws.retain(|(name, _)| *name != screw);
+                    screws.push((screw, adjust));
+                }
+            }
+            CalibrationWizard::Pid { .. } => {
+                if let Some(result) = parse_pid(line) {
+                    screen_state.calibration_wizard = CalibrationWizard::SaveConfig { result };
+                }
+            }
+            // "probe: z_offset: 1.234" or "stepper_z: position_endstop: 0.987" after ACCEPT
+            CalibrationWizard::SaveConfig { result } => {
+                if line.starts_with("probe: z_offset:")
+                    || line.starts_with("stepper_z: position_endstop:")
+                {
+                    *result = line.to_string();
+                }
+            }
+            CalibrationWizard::Idle | CalibrationWizard::ZOffset { .. } => {}
+        }
+    }
+}
+
+/// Follows manual_probe and updates the page texts, called every screen update
+pub fn advance(screen_state: &mut ScreenState) {
+    match screen_state.calibration_wizard {
+        CalibrationWizard::ZOffset { active: false } if screen_state.manual_probe.is_active => {
+            screen_state.calibration_wizard = CalibrationWizard::ZOffset { active: true };
+        }
+        // accepted/aborted from the console
+        CalibrationWizard::ZOffset { active: true } if !screen_state.manual_probe.is_active => {
+            screen_state.calibration_wizard = CalibrationWizard::Idle;
+            screen_state.calibration_message = "Calibration ended".to_string();
+        }
+        _ => {}
+    }
+
+    let message = match &screen_state.calibration_wizard {
+        CalibrationWizard::Idle => None,
+        CalibrationWizard::ZOffset { active: false } => Some("Homing and probing...".to_string()),
+        CalibrationWizard::ZOffset { active: true } => screen_state
+            .manual_probe
+            .z_position
+            .map(|z| format!("Z {:.3} mm", z)),
+        CalibrationWizard::ScrewsTilt { screws } if screws.is_empty() => {
+            Some("Probing screws...".to_string())
+        }
+        CalibrationWizard::ScrewsTilt { .. } => {
+            Some("Turn the screws, then measure again".to_string())
+        }
+        CalibrationWizard::Pid { .. } => Some(format!(
+            "Nozzle {} Bed {}",
+            screen_state.nozzle_temp, screen_state.bed_temp
+        )),
+        CalibrationWizard::SaveConfig { .. } => Some("Save and restart Klipper?".to_string()),
+    };
+    // idle keeps the last result/error
+    if let Some(message) = message {
+        screen_state.calibration_message = message;
+    }
+
+    screen_state.calibration_lines = screen_state
+        .calibration_wizard
+        .lines(&screen_state.manual_probe);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalibrateButton {
+    Undefined(u16),
+
+    ZOffset,
+    ScrewsTilt,
+    PidNozzle,
+    PidBed,
+    ZUp,
+    ZDown,
+    ZStep(usize),
+    Accept,
+    Abort,
+    SaveConfig,
+    Close,
+}
+
+impl CalibrateButton {
+    pub fn from_id(id: u16, keys: &CalibrateButtonKeys) -> Self {
+        if let Some(idx) = keys.z_steps.iter().position(|&code| code == id) {
+            return CalibrateButton::ZStep(idx);
+        }
+
+        match id {
+            id if id == keys.z_offset => CalibrateButton::ZOffset,
+            id if id == keys.screws_tilt => CalibrateButton::ScrewsTilt,
+            id if id == keys.pid_nozzle => CalibrateButton::PidNozzle,
+            id if id == keys.pid_bed => CalibrateButton::PidBed,
+            id if id == keys.z_up => CalibrateButton::ZUp,
+            id if id == keys.z_down => CalibrateButton::ZDown,
+            id if id == keys.accept => CalibrateButton::Accept,
+            id if id == keys.abort => CalibrateButton::Abort,
+            id if id == keys.save_config => CalibrateButton::SaveConfig,
+            id if id == keys.close => CalibrateButton::Close,
+            _ => CalibrateButton::Undefined(id),
+        }
+    }
+}
+
+/// Probing needs all axes homed
+fn with_homing(screen_state: &ScreenState, command: &str) -> String {
+    match screen_state.homed_axes == HomedAxes::all() {
+        true => command.to_string(),
+        false => format!("G28\n{}", command),
+    }
+}
+
+pub async fn parse_calibrate_button(
+    button: CalibrateButton,
+    moonraker_tx: &MoonrakerTx,
+    screen_state: &Arc<RwLock<ScreenState>>,
+    config: &ConfigRef,
+) -> Result<()> {
+    let moonraker_tx = moonraker_tx.lock().await;
+    let mut screen_state = screen_state.write().await;
+    let calibrate = config.read().await.calibrate.clone();
+
+    // a new calibration can replace finished ones, not running ones
+    let can_start = matches!(
+        screen_state.calibration_wizard,
+        CalibrationWizard::Idle
+            | CalibrationWizard::ScrewsTilt { .. }
+            | CalibrationWizard::SaveConfig { .. }
+    );
+    let printing = screen_state.printer_state == PrinterState::Printing
+        || screen_state.printer_state == PrinterState::Paused;
+    let is_start = matches!(
+        button,
+        CalibrateButton::ZOffset
+            | CalibrateButton::ScrewsTilt
+            | CalibrateButton::PidNozzle
+            | CalibrateButton::PidBed
+    );
+    if is_start && (printing || !can_start) {
+        screen_state.calibration_message = match printing {
+            true => "Not while printing".to_string(),
+            false => "Finish the current calibration first".to_string(),
+        };
+        return Ok(());
+    }
+
+    let z_step = screen_state
+        .calibration_z_step
+        .unwrap_or(calibrate.default_z_step);
+    let probing = screen_state.calibration_wizard == CalibrationWizard::ZOffset { active: true };
+
+    match button {
+        CalibrateButton::ZOffset => {
+            let command = match screen_state.has_probe {
+                true => "PROBE_CALIBRATE",
+                false => "Z_ENDSTOP_CALIBRATE",
+            };
+            send_gcode_script(&moonraker_tx, with_homing(&screen_state, command))?;
+            screen_state.calibration_wizard = CalibrationWizard::ZOffset { active: false };
+        }
+        CalibrateButton::ScrewsTilt => {
+            if !screen_state.has_screws_tilt {
+                screen_state.calibration_message =
+                    "Needs [screws_tilt_adjust] in config".to_string();
+                return Ok(());
+            }
+
+            send_gcode_script(
+                &moonraker_tx,
+                with_homing(&screen_state, "SCREWS_TILT_CALCULATE"),
+            )?;
+            screen_state.calibration_wizard = CalibrationWizard::ScrewsTilt { screws: Vec::new() };
+        }
+        CalibrateButton::PidNozzle | CalibrateButton::PidBed => {
+            let (heater, target) = match button {
+                CalibrateButton::PidNozzle => (PidHeater::Extruder, calibrate.pid_nozzle_temp),
+                _ => (PidHeater::HeaterBed, calibrate.pid_bed_temp),
+            };
+
+            send_gcode_script(
+                &moonraker_tx,
+                format!("PID_CALIBRATE HEATER={} TARGET={}", heater.name(), target),
+            )?;
+            screen_state.calibration_wizard = CalibrationWizard::Pid { heater, target };
+        }
+        CalibrateButton::ZUp | CalibrateButton::ZDown => {
+            if probing {
+                let step = match button {
+                    CalibrateButton::ZUp => z_step,
+                    _ => -z_step,
+                };
+                send_gcode_script(&moonraker_tx, format!("TESTZ Z={:+.3}", step))?;
+            }
+        }
+        CalibrateButton::ZStep(idx) => {
+            if let Some(step) = calibrate.z_steps.get(idx) {
+                screen_state.calibration_z_step = Some(*step);
+            }
+        }
+        CalibrateButton::Accept => {
+            if probing {
+                send_gcode_script(&moonraker_tx, "ACCEPT".to_string())?;
+                // the new value comes in the console output
+                screen_state.calibration_wizard = CalibrationWizard::SaveConfig {
+                    result: String::new(),
+                };
+            }
+        }
+        CalibrateButton::Abort => {
+            if probing {
+                send_gcode_script(&moonraker_tx, "ABORT".to_string())?;
+                screen_state.calibration_wizard = CalibrationWizard::Idle;
+                screen_state.calibration_message = "Calibration aborted".to_string();
+            }
+        }
+        CalibrateButton::SaveConfig => {
+            if let CalibrationWizard::SaveConfig { .. } = screen_state.calibration_wizard {
+                send_gcode_script(&moonraker_tx, "SAVE_CONFIG".to_string())?;
+                screen_state.calibration_wizard = CalibrationWizard::Idle;
+                screen_state.calibration_message = "Saved, Klipper restarts".to_string();
+            }
+        }
+        CalibrateButton::Close => {
+            // manual probe would keep waiting for ACCEPT/ABORT
+            if let CalibrationWizard::ZOffset { .. } = screen_state.calibration_wizard {
+                send_gcode_script(&moonraker_tx, "ABORT".to_string())?;
+            }
+            screen_state.calibration_wizard = CalibrationWizard::Idle;
+            screen_state.calibration_message = String::new();
+        }
+        CalibrateButton::Undefined(id) => {
+            println!("Undefined calibrate button pressed with ID: {}", id);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::version::VERSION;
+
+#[derive(Debug, Parser)]
+#[command(name = "serial-screen", version = VERSION, about = "DGUS screen for Moonraker")]
+pub struct Cli {
+    /// Config file, defaults to /opt/serial-screen/config.toml
+    /// or ~/printer_data/config/serial-screen.toml
+    #[arg(short, long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Moonraker address ("localhost:7125"), overrides `moonraker.url` of the config file.
+    /// Kept for service files that still run `serial-screen <url>`
+    #[arg(value_name = "MOONRAKER_URL")]
+    pub url: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Drive the screen (default)
+    Run,
+
+    /// Validate config and layout files, then print the resolved config
+    CheckConfig,
+
+    /// Print default config, can be used as a starting point for config.toml
+    DefaultConfig,
+
+    /// Print layout of the bundled DGUS project
+    DefaultLayout,
+
+    /// Restore the binary that was replaced by the last update
+    Rollback,
+}
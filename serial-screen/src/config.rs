@@ -0,0 +1,736 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+use tokio::sync::RwLock;
+
+use crate::{confirm::ConfirmAction, moonraker::Axis};
+
+pub type ConfigRef = Arc<RwLock<Config>>;
+
+pub const DEFAULT_CONFIG_PATH: &str = "/opt/serial-screen/config.toml";
+const RELOAD_CHECK_INTERVAL: u64 = 2000;
+
+/// Baud rates supported by DGUS T5/T5L displays
+const SUPPORTED_BAUD_RATES: [u32; 8] = [9600, 19200, 38400, 57600, 115200, 230400, 460800, 921600];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Layout file for custom DGUS projects (restart required after change)
+    pub layout: Option<PathBuf>,
+
+    pub moonraker: MoonrakerConfig,
+    pub serial: SerialConfig,
+    pub timeouts: TimeoutsConfig,
+    pub preheat: PreheatConfig,
+    pub movement: MovementConfig,
+    pub extruder: ExtruderConfig,
+    pub tune: TuneConfig,
+    pub eta: EtaConfig,
+    pub status_line: StatusLineConfig,
+    pub temp_graph: TempGraphConfig,
+    pub confirm: ConfirmConfig,
+    pub calibrate: CalibrateConfig,
+    pub power: PowerConfig,
+    pub updates: UpdatesConfig,
+    pub buzzer: BuzzerConfig,
+}
+
+/// Changes in this section require restart
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MoonrakerConfig {
+    pub url: String,
+    pub api_key: Option<String>,
+    /// Receive `notify_proc_stat_update` every second for live system page values,
+    /// otherwise they are only refreshed when the page is opened
+    pub proc_stats: bool,
+}
+
+impl Default for MoonrakerConfig {
+    fn default() -> Self {
+        MoonrakerConfig {
+            url: "localhost:7125".to_string(),
+            api_key: None,
+            proc_stats: false,
+        }
+    }
+}
+
+/// Applied on the next reconnect to the screen
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SerialConfig {
+    /// Uses primary UART of the Raspberry Pi if not set
+    pub device: Option<PathBuf>,
+    pub baud: u32,
+}
+
+impl Default for SerialConfig {
+    fn default() -> Self {
+        SerialConfig {
+            device: None,
+            baud: 115200,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutsConfig {
+    /// Delay between screen reconnect attempts
+    pub retry_ms: u64,
+    /// How long to wait for the screen to answer after boot
+    pub boot_ms: u64,
+    /// Screen is considered disconnected after this much silence
+    pub alive_ms: u64,
+}
+
+impl Default for TimeoutsConfig {
+    fn default() -> Self {
+        TimeoutsConfig {
+            retry_ms: 5000,
+            boot_ms: 1000,
+            alive_ms: 2000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PreheatConfig {
+    /// Use presets saved by Mainsail in moonraker database (if there are any)
+    pub use_mainsail: bool,
+    /// Heater that gets preset `chamber` temperature
+    pub chamber_heater: String,
+    pub presets: Vec<PreheatPresetConfig>,
+    /// Preset (by name, also Mainsail ones) applied by the PLA button on preheat page
+    pub quick_preset: String,
+}
+
+impl Default for PreheatConfig {
+    fn default() -> Self {
+        PreheatConfig {
+            use_mainsail: false,
+            chamber_heater: "heater_generic chamber".to_string(),
+            presets: vec![
+                PreheatPresetConfig::new("PLA", 200, 45),
+                PreheatPresetConfig::new("PETG", 240, 70),
+                PreheatPresetConfig::new("ABS", 250, 100),
+                PreheatPresetConfig::new("TPU", 220, 50),
+            ],
+            quick_preset: "PLA".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PreheatPresetConfig {
+    pub name: String,
+    pub nozzle: i16,
+    pub bed: i16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chamber: Option<i16>,
+    /// Part cooling fan speed in percent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fan: Option<u8>,
+}
+
+impl PreheatPresetConfig {
+    fn new(name: &str, nozzle: i16, bed: i16) -> Self {
+        PreheatPresetConfig {
+            name: name.to_string(),
+            nozzle,
+            bed,
+            chamber: None,
+            fan: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MovementConfig {
+    /// Jog distances in mm, selected with step buttons on movement page
+    pub steps: Vec<f64>,
+    /// Step used until another one is selected
+    pub default_step: f64,
+    /// Jog feedrates in mm/min
+    pub feedrate: AxisFeedrates,
+}
+
+impl Default for MovementConfig {
+    fn default() -> Self {
+        MovementConfig {
+            steps: vec![0.1, 1.0, 10.0, 50.0],
+            default_step: 10.0,
+            feedrate: AxisFeedrates::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AxisFeedrates {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+impl Default for AxisFeedrates {
+    fn default() -> Self {
+        AxisFeedrates {
+            x: 6000,
+            y: 6000,
+            z: 600,
+        }
+    }
+}
+
+impl AxisFeedrates {
+    pub fn get(&self, axis: Axis) -> u32 {
+        match axis {
+            Axis::X => self.x,
+            Axis::Y => self.y,
+            Axis::Z => self.z,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExtruderConfig {
+    /// Extrude/retract lengths in mm, selected with length buttons on extruder page
+    pub lengths: Vec<f64>,
+    pub default_length: f64,
+    /// Extrude/retract speeds in mm/s
+    pub speeds: Vec<f64>,
+    pub default_speed: f64,
+    /// Used by load/unload when there are no LOAD_FILAMENT/UNLOAD_FILAMENT macros
+    pub load_length: f64,
+    pub unload_length: f64,
+    pub filament_speed: f64,
+}
+
+impl Default for ExtruderConfig {
+    fn default() -> Self {
+        ExtruderConfig {
+            lengths: vec![1.0, 5.0, 10.0, 50.0],
+            default_length: 10.0,
+            speeds: vec![2.0, 5.0, 10.0],
+            default_speed: 5.0,
+            load_length: 50.0,
+            unload_length: 80.0,
+            filament_speed: 5.0,
+        }
+    }
+}
+
+/// Steps of +/- buttons on tune page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TuneConfig {
+    /// Percent
+    pub speed_step: u16,
+    pub flow_step: u16,
+    pub fan_step: u16,
+    /// Degrees
+    pub temp_step: u16,
+    /// Babystep in mm
+    pub z_step: f64,
+}
+
+impl Default for TuneConfig {
+    fn default() -> Self {
+        TuneConfig {
+            speed_step: 10,
+            flow_step: 1,
+            fan_step: 10,
+            temp_step: 5,
+            z_step: 0.01,
+        }
+    }
+}
+
+/// Blend of remaining time estimates, sources without data are skipped
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EtaConfig {
+    /// Extrapolated from elapsed time and file progress
+    pub file_weight: f64,
+    /// Extrapolated from used vs slicer total filament
+    pub filament_weight: f64,
+    /// Slicer estimate scaled by actual print speed
+    pub slicer_weight: f64,
+    /// 0 - no smoothing, closer to 1 - slower changes
+    pub smoothing: f64,
+}
+
+impl Default for EtaConfig {
+    fn default() -> Self {
+        EtaConfig {
+            file_weight: 1.0,
+            filament_weight: 1.0,
+            slicer_weight: 1.0,
+            smoothing: 0.8,
+        }
+    }
+}
+
+/// Header line with M117/SET_DISPLAY_TEXT message (or print_stats message)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatusLineConfig {
+    /// Message is cleared after this many seconds, 0 - never
+    pub timeout_secs: u64,
+    /// Chars long messages scroll by every second
+    pub marquee_step: usize,
+}
+
+impl Default for StatusLineConfig {
+    fn default() -> Self {
+        StatusLineConfig {
+            timeout_secs: 60,
+            marquee_step: 2,
+        }
+    }
+}
+
+/// Nozzle/bed temperature curves on the graph page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TempGraphConfig {
+    /// Seconds between curve points
+    pub sample_secs: u64,
+    /// Points of Moonraker's temperature history (last 20 minutes) drawn after connecting,
+    /// 0 - start with an empty graph
+    pub backfill_points: usize,
+}
+
+impl Default for TempGraphConfig {
+    fn default() -> Self {
+        TempGraphConfig {
+            sample_secs: 2,
+            backfill_points: 240,
+        }
+    }
+}
+
+/// Actions that can't be undone (cancel_print, emergency_stop, firmware_restart, host_restart)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfirmConfig {
+    /// Ask Yes/No on the confirm page first
+    pub dialog: Vec<ConfirmAction>,
+    /// Run only when the button is held (DGUS key has to repeat its code while pressed)
+    pub long_press: Vec<ConfirmAction>,
+    pub long_press_ms: u64,
+}
+
+impl Default for ConfirmConfig {
+    fn default() -> Self {
+        ConfirmConfig {
+            dialog: vec![ConfirmAction::CancelPrint, ConfirmAction::EmergencyStop],
+            long_press: Vec::new(),
+            long_press_ms: 1000,
+        }
+    }
+}
+
+/// Calibration wizards on the calibrate page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CalibrateConfig {
+    /// TESTZ steps in mm, selected with step buttons while probing Z offset
+    pub z_steps: Vec<f64>,
+    pub default_z_step: f64,
+    /// PID_CALIBRATE targets
+    pub pid_nozzle_temp: i16,
+    pub pid_bed_temp: i16,
+}
+
+impl Default for CalibrateConfig {
+    fn default() -> Self {
+        CalibrateConfig {
+            z_steps: vec![0.01, 0.05, 0.1, 1.0],
+            default_z_step: 0.05,
+            pid_nozzle_temp: 210,
+            pid_bed_temp: 60,
+        }
+    }
+}
+
+/// Moonraker power devices (needs [power ...] sections in moonraker.conf)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerConfig {
+    /// Device that powers the printer, the connection page offers to switch it on,
+    /// empty - no power on button
+    pub printer_device: String,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        PowerConfig {
+            printer_device: "printer".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    Stable,
+    Prerelease,
+    Off,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpdatesConfig {
+    pub channel: UpdateChannel,
+    pub check_interval_min: u64,
+    /// Minisign public key used to verify releases, overrides the built-in one
+    pub public_key: Option<String>,
+}
+
+impl Default for UpdatesConfig {
+    fn default() -> Self {
+        UpdatesConfig {
+            channel: UpdateChannel::Stable,
+            check_interval_min: 60,
+            public_key: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuzzerConfig {
+    pub enabled: bool,
+    /// Beep length when klipper shuts down or print fails
+    pub error_ms: u16,
+    /// Beep length when print summary is shown (0 - no beep)
+    pub finished_ms: u16,
+}
+
+impl Default for BuzzerConfig {
+    fn default() -> Self {
+        BuzzerConfig {
+            enabled: true,
+            error_ms: 1000,
+            finished_ms: 500,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read config file {}: {}", path.display(), e))?;
+
+        let config: Config = toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Config file {}: {}", path.display(), e))?;
+
+        config
+            .validate()
+            .map_err(|e| anyhow::anyhow!("Config file {}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    /// Applies url given on the command line, it wins over the config file (also after reloads)
+    pub fn override_url(&mut self, url: &Option<String>) -> Result<()> {
+        if let Some(url) = url {
+            self.moonraker.url = url.clone();
+            self.validate()?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns every problem found, so user can fix them all at once
+    pub fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+
+        if self.moonraker.url.is_empty() {
+            errors.push("moonraker.url must not be empty".to_string());
+        } else if self.moonraker.url.contains("://") {
+            errors.push(format!(
+                "moonraker.url must be host:port without scheme, got \"{}\"",
+                self.moonraker.url
+            ));
+        }
+
+        if let Some(layout) = &self.layout {
+            if !layout.exists() {
+                errors.push(format!("layout file {} does not exist", layout.display()));
+            }
+        }
+
+        if !SUPPORTED_BAUD_RATES.contains(&self.serial.baud) {
+            errors.push(format!(
+                "serial.baud must be one of {:?}, got {}",
+                SUPPORTED_BAUD_RATES, self.serial.baud
+            ));
+        }
+
+        if self.timeouts.retry_ms == 0 {
+            errors.push("timeouts.retry_ms must be greater than 0".to_string());
+        }
+        if self.timeouts.boot_ms == 0 {
+            errors.push("timeouts.boot_ms must be greater than 0".to_string());
+        }
+        if self.timeouts.alive_ms <= 1000 {
+            errors.push(format!(
+                "timeouts.alive_ms must be greater than 1000 (screen is pinged every second), got {}",
+                self.timeouts.alive_ms
+            ));
+        }
+
+        if self.preheat.presets.is_empty() && !self.preheat.use_mainsail {
+            errors.push("preheat.presets must not be empty".to_string());
+        }
+        for preset in &self.preheat.presets {
+            if preset.name.is_empty() {
+                errors.push("preheat.presets name must not be empty".to_string());
+            }
+            if !(0..=500).contains(&preset.nozzle) {
+                errors.push(format!(
+                    "preheat.presets {} nozzle must be between 0 and 500, got {}",
+                    preset.name, preset.nozzle
+                ));
+            }
+            if !(0..=200).contains(&preset.bed) {
+                errors.push(format!(
+                    "preheat.presets {} bed must be between 0 and 200, got {}",
+                    preset.name, preset.bed
+                ));
+            }
+            if let Some(chamber) = preset.chamber {
+                if !(0..=100).contains(&chamber) {
+                    errors.push(format!(
+                        "preheat.presets {} chamber must be between 0 and 100, got {}",
+                        preset.name, chamber
+                    ));
+                }
+            }
+            if let Some(fan) = preset.fan {
+                if fan > 100 {
+                    errors.push(format!(
+                        "preheat.presets {} fan must be between 0 and 100, got {}",
+                        preset.name, fan
+                    ));
+                }
+            }
+        }
+
+        if self.movement.steps.is_empty() {
+            errors.push("movement.steps must not be empty".to_string());
+        }
+        if let Some(step) = self.movement.steps.iter().find(|step| **step <= 0.0) {
+            errors.push(format!(
+                "movement.steps must be greater than 0, got {}",
+                step
+            ));
+        }
+        if self.movement.default_step <= 0.0 {
+            errors.push(format!(
+                "movement.default_step must be greater than 0, got {}",
+                self.movement.default_step
+            ));
+        }
+        let feedrate = &self.movement.feedrate;
+        if feedrate.x == 0 || feedrate.y == 0 || feedrate.z == 0 {
+            errors.push("movement.feedrate x, y and z must be greater than 0".to_string());
+        }
+
+        let extruder = &self.extruder;
+        if extruder.lengths.is_empty() || extruder.lengths.iter().any(|l| *l <= 0.0) {
+            errors.push("extruder.lengths must not be empty and greater than 0".to_string());
+        }
+        if extruder.speeds.is_empty() || extruder.speeds.iter().any(|s| *s <= 0.0) {
+            errors.push("extruder.speeds must not be empty and greater than 0".to_string());
+        }
+        for (name, value) in [
+            ("default_length", extruder.default_length),
+            ("default_speed", extruder.default_speed),
+            ("load_length", extruder.load_length),
+            ("unload_length", extruder.unload_length),
+            ("filament_speed", extruder.filament_speed),
+        ] {
+            if value <= 0.0 {
+                errors.push(format!(
+                    "extruder.{} must be greater than 0, got {}",
+                    name, value
+                ));
+            }
+        }
+
+        let tune = &self.tune;
+        for (name, value) in [
+            ("speed_step", tune.speed_step),
+            ("flow_step", tune.flow_step),
+            ("fan_step", tune.fan_step),
+            ("temp_step", tune.temp_step),
+        ] {
+            if !(1..=100).contains(&value) {
+                errors.push(format!("tune.{} must be 1-100, got {}", name, value));
+            }
+        }
+        if !(0.001..=0.1).contains(&tune.z_step) {
+            errors.push(format!(
+                "tune.z_step must be 0.001-0.1 mm, got {}",
+                tune.z_step
+            ));
+        }
+
+        let eta = &self.eta;
+        let weights = [eta.file_weight, eta.filament_weight, eta.slicer_weight];
+        if weights.iter().any(|w| *w < 0.0) || weights.iter().all(|w| *w == 0.0) {
+            errors
+                .push("eta weights must not be negative and at least one must be set".to_string());
+        }
+        if !(0.0..1.0).contains(&eta.smoothing) {
+            errors.push(format!(
+                "eta.smoothing must be 0 or more and less than 1, got {}",
+                eta.smoothing
+            ));
+        }
+
+        if !(1..=10).contains(&self.status_line.marquee_step) {
+            errors.push(format!(
+                "status_line.marquee_step must be 1-10, got {}",
+                self.status_line.marquee_step
+            ));
+        }
+
+        if self.updates.check_interval_min == 0 {
+            errors.push("updates.check_interval_min must be greater than 0".to_string());
+        }
+        if let Some(public_key) = &self.updates.public_key {
+            if minisign_verify::PublicKey::from_base64(public_key).is_err() {
+                errors.push("updates.public_key is not a valid minisign public key".to_string());
+            }
+        }
+
+        if !(1..=60).contains(&self.temp_graph.sample_secs) {
+            errors.push(format!(
+                "temp_graph.sample_secs must be between 1 and 60, got {}",
+                self.temp_graph.sample_secs
+            ));
+        }
+
+        for action in &self.confirm.long_press {
+            if self.confirm.dialog.contains(action) {
+                errors.push(format!(
+                    "confirm: {:?} can't be in both dialog and long_press",
+                    action
+                ));
+            }
+        }
+        if !(100..=10000).contains(&self.confirm.long_press_ms) {
+            errors.push(format!(
+                "confirm.long_press_ms must be between 100 and 10000, got {}",
+                self.confirm.long_press_ms
+            ));
+        }
+
+        let calibrate = &self.calibrate;
+        if calibrate.z_steps.is_empty() || calibrate.z_steps.iter().any(|s| *s <= 0.0) {
+            errors.push("calibrate.z_steps must not be empty and greater than 0".to_string());
+        }
+        if calibrate.default_z_step <= 0.0 {
+            errors.push(format!(
+                "calibrate.default_z_step must be greater than 0, got {}",
+                calibrate.default_z_step
+            ));
+        }
+        for (name, value) in [
+            ("pid_nozzle_temp", calibrate.pid_nozzle_temp),
+            ("pid_bed_temp", calibrate.pid_bed_temp),
+        ] {
+            if !(40..=350).contains(&value) {
+                errors.push(format!("calibrate.{} must be 40-350, got {}", name, value));
+            }
+        }
+
+        if self.buzzer.error_ms > 2000 {
+            errors.push(format!(
+                "buzzer.error_ms must be at most 2000, got {}",
+                self.buzzer.error_ms
+            ));
+        }
+
+        if self.buzzer.finished_ms > 2000 {
+            errors.push(format!(
+                "buzzer.finished_ms must be at most 2000, got {}",
+                self.buzzer.finished_ms
+            ));
+        }
+
+        if !errors.is_empty() {
+            return Err(anyhow::anyhow!("\n  - {}", errors.join("\n  - ")));
+        }
+
+        Ok(())
+    }
+}
+
+/// Config used when none was passed on command line:
+/// `/opt/serial-screen/config.toml` first, then `~/printer_data/config/serial-screen.toml`
+/// (so it can be edited from Mainsail/Fluidd)
+pub fn find_config_file() -> Option<PathBuf> {
+    let mut candidates = vec![PathBuf::from(DEFAULT_CONFIG_PATH)];
+    if let Ok(home) = std::env::var("HOME") {
+        candidates.push(Path::new(&home).join("printer_data/config/serial-screen.toml"));
+    }
+
+    candidates.into_iter().find(|path| path.exists())
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Reloads config when the file changes, invalid config is reported and ignored
+pub fn spawn_reload_task(path: PathBuf, config: ConfigRef, url: Option<String>) {
+    tokio::spawn(async move {
+        let mut last_modified = modified_time(&path);
+
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_millis(RELOAD_CHECK_INTERVAL)).await;
+
+            let modified = modified_time(&path);
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            let new_config = Config::load(&path).and_then(|mut new_config| {
+                new_config.override_url(&url)?;
+                Ok(new_config)
+            });
+            match new_config {
+                Ok(new_config) => {
+                    let mut config = config.write().await;
+                    if new_config.moonraker != config.moonraker
+                        || new_config.layout != config.layout
+                    {
+                        println!("Moonraker and layout changes will be applied after restart.");
+                    }
+
+                    *config = new_config;
+                    println!("Config reloaded from {}", path.display());
+                }
+                Err(e) => {
+                    println!("Not reloading config: {}", e);
+                }
+            }
+        }
+    });
+}
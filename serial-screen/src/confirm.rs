@@ -0,0 +1,254 @@
+use std::{path::Path, sync::Arc};
+
+use anyhow::Result;
+use moonraker_api::{MoonrakerMethod, MoonrakerMsg};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{mpsc::UnboundedSender, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::ConfirmConfig,
+    layout::{ConfirmButtonKeys, Layout},
+    moonraker::{MoonrakerTx, PrinterState},
+    screen_state::{ScreenState, StatusPage},
+    serial_utils::{construct_change_page, construct_text},
+    utils,
+};
+
+/// Screen repeats the key code while a long press button is held,
+/// a longer gap between reports means it was released
+const HOLD_GAP: Duration = Duration::from_millis(400);
+
+/// Actions that can't be undone, see `[confirm]` in config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfirmAction {
+    CancelPrint,
+    EmergencyStop,
+    FirmwareRestart,
+    HostRestart,
+}
+
+impl ConfirmAction {
+    /// Question on the confirm page, "Cancel print 'benchy'?"
+    fn question(&self, screen_state: &ScreenState) -> String {
+        match self {
+            ConfirmAction::CancelPrint => {
+                let name = Path::new(&screen_state.print_filename)
+                    .file_stem()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+
+                match name.is_empty() {
+                    true => "Cancel print?".to_string(),
+                    false => format!("Cancel print '{}'?", name),
+                }
+            }
+            ConfirmAction::EmergencyStop => "Emergency stop the printer?".to_string(),
+            ConfirmAction::FirmwareRestart => "Restart firmware?".to_string(),
+            ConfirmAction::HostRestart => "Restart Klipper host?".to_string(),
+        }
+    }
+
+    /// Page the action was started from, dialog goes back there
+    fn return_page(&self, screen_state: &ScreenState, layout: &Layout) -> u16 {
+        match self {
+            ConfirmAction::CancelPrint => layout.pages.printing,
+            ConfirmAction::EmergencyStop => layout.pages.main,
+            ConfirmAction::FirmwareRestart | ConfirmAction::HostRestart => {
+                match screen_state.status_page() {
+                    StatusPage::Error => layout.pages.error,
+                    _ => layout.pages.connection,
+                }
+            }
+        }
+    }
+
+    fn run(
+        &self,
+        screen_state: &ScreenState,
+        moonraker_tx: &UnboundedSender<MoonrakerMsg>,
+        serial_tx: &UnboundedSender<Vec<u8>>,
+        layout: &Layout,
+    ) -> Result<()> {
+        match self {
+            ConfirmAction::CancelPrint => {
+                // print could have ended while the dialog was open
+                if screen_state.printer_state == PrinterState::Printing
+                    || screen_state.printer_state == PrinterState::Paused
+                {
+                    moonraker_tx.send(MoonrakerMsg::new_with_method_and_id(
+                        MoonrakerMethod::PrintCancel,
+                    ))?;
+                }
+            }
+            ConfirmAction::EmergencyStop => {
+                moonraker_tx.send(MoonrakerMsg::new_with_method_and_id(
+                    MoonrakerMethod::EmergencyStop,
+                ))?;
+
+                serial_tx.send(construct_change_page(layout.pages.emergency_stop))?;
+            }
+            ConfirmAction::FirmwareRestart => {
+                moonraker_tx.send(MoonrakerMsg::new_with_method_and_id(
+                    MoonrakerMethod::FirmwareRestart,
+                ))?;
+            }
+            ConfirmAction::HostRestart => {
+                moonraker_tx.send(MoonrakerMsg::new_with_method_and_id(
+                    MoonrakerMethod::PrinterRestart,
+                ))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfirmState {
+    Idle,
+    /// Confirm page is shown, waiting for Yes/No
+    Pending {
+        action: ConfirmAction,
+        return_page: u16,
+    },
+    /// Long press button is held, `fired` once the action ran (until it's released)
+    Holding {
+        action: ConfirmAction,
+        since: Instant,
+        last: Instant,
+        fired: bool,
+    },
+}
+
+/// Runs the action right away, or asks for confirmation / waits for the hold, depending on config
+pub fn request(
+    action: ConfirmAction,
+    screen_state: &mut ScreenState,
+    config: &ConfirmConfig,
+    moonraker_tx: &UnboundedSender<MoonrakerMsg>,
+    serial_tx: &UnboundedSender<Vec<u8>>,
+    layout: &Layout,
+) -> Result<()> {
+    if config.long_press.contains(&action) {
+        return hold(
+            action,
+            screen_state,
+            config,
+            moonraker_tx,
+            serial_tx,
+            layout,
+        );
+    }
+
+    if config.dialog.contains(&action) {
+        screen_state.confirm = ConfirmState::Pending {
+            action,
+            return_page: action.return_page(screen_state, layout),
+        };
+
+        serial_tx.send(construct_text(
+            layout.addresses.confirm_message,
+            &utils::fit_text(&action.question(screen_state), 40),
+        ))?;
+        serial_tx.send(construct_change_page(layout.pages.confirm))?;
+        return Ok(());
+    }
+
+    screen_state.confirm = ConfirmState::Idle;
+    action.run(screen_state, moonraker_tx, serial_tx, layout)
+}
+
+/// Called for every repeated key report, the action runs once the button is held long enough
+fn hold(
+    action: ConfirmAction,
+    screen_state: &mut ScreenState,
+    config: &ConfirmConfig,
+    moonraker_tx: &UnboundedSender<MoonrakerMsg>,
+    serial_tx: &UnboundedSender<Vec<u8>>,
+    layout: &Layout,
+) -> Result<()> {
+    let now = Instant::now();
+
+    let (since, fired) = match screen_state.confirm {
+        ConfirmState::Holding {
+            action: held,
+            since,
+            last,
+            fired,
+        } if held == action && now.duration_since(last) < HOLD_GAP => (since, fired),
+        _ => (now, false),
+    };
+
+    let run = !fired && now.duration_since(since) >= Duration::from_millis(config.long_press_ms);
+    screen_state.confirm = ConfirmState::Holding {
+        action,
+        since,
+        last: now,
+        fired: fired || run,
+    };
+
+    if run {
+        action.run(screen_state, moonraker_tx, serial_tx, layout)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmButton {
+    Undefined(u16),
+
+    Yes,
+    No,
+}
+
+impl ConfirmButton {
+    pub fn from_id(id: u16, keys: &ConfirmButtonKeys) -> Self {
+        match id {
+            id if id == keys.yes => ConfirmButton::Yes,
+            id if id == keys.no => ConfirmButton::No,
+            _ => ConfirmButton::Undefined(id),
+        }
+    }
+}
+
+pub async fn parse_confirm_button(
+    button: ConfirmButton,
+    moonraker_tx: &MoonrakerTx,
+    screen_state: &Arc<RwLock<ScreenState>>,
+    serial_tx: &Arc<Mutex<UnboundedSender<Vec<u8>>>>,
+    layout: &Layout,
+) -> Result<()> {
+    let serial_tx = serial_tx.lock().await;
+    let moonraker_tx = moonraker_tx.lock().await;
+    let mut screen_state = screen_state.write().await;
+
+    if let ConfirmButton::Undefined(id) = button {
+        println!("Undefined confirm button pressed with ID: {}", id);
+        return Ok(());
+    }
+
+    let (action, return_page) = match screen_state.confirm {
+        ConfirmState::Pending {
+            action,
+            return_page,
+        } => (action, return_page),
+        // dialog left open from before a restart
+        _ => {
+            serial_tx.send(construct_change_page(layout.pages.main))?;
+            return Ok(());
+        }
+    };
+    screen_state.confirm = ConfirmState::Idle;
+    serial_tx.send(construct_change_page(return_page))?;
+
+    if button == ConfirmButton::Yes {
+        action.run(&screen_state, &moonraker_tx, &serial_tx, layout)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,309 @@
+use crate::config::EtaConfig;
+
+/// File/filament progress below this is mostly start gcode (heating, probing), ratios are useless there
+const MIN_PROGRESS: f64 = 0.01;
+/// How far the slicer estimate can be scaled by the measured print speed
+const SLICER_SCALE_RANGE: (f64, f64) = (0.5, 2.0);
+
+/// Values of print_stats/display_status at one moment, durations in seconds, filament in mm
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PrintSample {
+    pub print_duration: f64,
+    /// File progress 0.0-1.0
+    pub progress: f64,
+    pub filament_used: f64,
+}
+
+/// Totals from file metadata, None if the slicer didn't write them
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SlicerTotals {
+    pub estimated_time: Option<f64>,
+    pub filament_total: Option<f64>,
+}
+
+/// Remaining time extrapolated from the time it took to print the file so far
+pub fn file_estimate(sample: &PrintSample) -> Option<f64> {
+    if sample.progress < MIN_PROGRESS || sample.print_duration <= 0.0 {
+        return None;
+    }
+
+    Some(sample.print_duration / sample.progress.min(1.0) - sample.print_duration)
+}
+
+/// Remaining time extrapolated from used vs total filament
+pub fn filament_estimate(sample: &PrintSample, totals: &SlicerTotals) -> Option<f64> {
+    let filament_total = totals.filament_total.filter(|total| *total > 0.0)?;
+    let progress = (sample.filament_used / filament_total).min(1.0);
+    if progress < MIN_PROGRESS || sample.print_duration <= 0.0 {
+        return None;
+    }
+
+    Some(sample.print_duration / progress - sample.print_duration)
+}
+
+/// Slicer remaining time, scaled by actual vs estimated elapsed time (how much slower/faster the printer is)
+pub fn slicer_estimate(sample: &PrintSample, totals: &SlicerTotals) -> Option<f64> {
+    let estimated_time = totals.estimated_time.filter(|time| *time > 0.0)?;
+    let progress = sample.progress.clamp(0.0, 1.0);
+
+    let scale = match progress >= MIN_PROGRESS && sample.print_duration > 0.0 {
+        true => (sample.print_duration / (estimated_time * progress))
+            .clamp(SLICER_SCALE_RANGE.0, SLICER_SCALE_RANGE.1),
+        false => 1.0,
+    };
+
+    Some(estimated_time * (1.0 - progress) * scale)
+}
+
+/// Weighted blend of the estimates that are available, smoothed over samples
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EtaEstimator {
+    /// Smoothed print_duration + remaining, smoothing the total doesn't lag behind the countdown
+    total: Option<f64>,
+    last_duration: Option<f64>,
+}
+
+impl EtaEstimator {
+    pub fn reset(&mut self) {
+        *self = EtaEstimator::default();
+    }
+
+    /// Remaining seconds after the sample, None if no estimate is available yet
+    pub fn update(
+        &mut self,
+        sample: &PrintSample,
+        totals: &SlicerTotals,
+        config: &EtaConfig,
+    ) -> Option<f64> {
+        // print_duration went back, it's another print
+        if self
+            .last_duration
+            .is_some_and(|last| sample.print_duration < last)
+        {
+            self.reset();
+        }
+
+        // print_duration doesn't grow while paused, the estimate stays where it was
+        if self.last_duration != Some(sample.print_duration) {
+            self.last_duration = Some(sample.print_duration);
+
+            if let Some(remaining) = blend(sample, totals, config) {
+                let total = sample.print_duration + remaining;
+                self.total = Some(match self.total {
+                    Some(smoothed) => {
+                        smoothed * config.smoothing + total * (1.0 - config.smoothing)
+                    }
+                    None => total,
+                });
+            }
+        }
+
+        self.total
+            .map(|total| (total - sample.print_duration).max(0.0))
+    }
+}
+
+fn blend(sample: &PrintSample, totals: &SlicerTotals, config: &EtaConfig) -> Option<f64> {
+    let estimates = [
+        (file_estimate(sample), config.file_weight),
+        (filament_estimate(sample, totals), config.filament_weight),
+        (slicer_estimate(sample, totals), config.slicer_weight),
+    ];
+
+    let (sum, weights) = estimates
+        .iter()
+        .filter_map(|(estimate, weight)| estimate.map(|estimate| (estimate, *weight)))
+        .filter(|(_, weight)| *weight > 0.0)
+        .fold((0.0, 0.0), |(sum, weights), (estimate, weight)| {
+            (sum + estimate * weight, weights + weight)
+        });
+
+    match weights > 0.0 {
+        true => Some(sum / weights),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (print_duration, progress, filament_used) recorded every 10 minutes from a print
+    /// the slicer estimated at 1h/5m of filament, but which took 2h (slow printer)
+    const SLOW_PRINT: [(f64, f64, f64); 7] = [
+        (0.0, 0.0, 0.0),
+        (1200.0, 0.16, 800.0),
+        (2400.0, 0.33, 1650.0),
+        (3600.0, 0.5, 2500.0),
+        (4800.0, 0.66, 3300.0),
+        (6000.0, 0.83, 4150.0),
+        (7200.0, 1.0, 5000.0),
+    ];
+
+    const SLOW_TOTALS: SlicerTotals = SlicerTotals {
+        estimated_time: Some(3600.0),
+        filament_total: Some(5000.0),
+    };
+
+    fn sample((print_duration, progress, filament_used): (f64, f64, f64)) -> PrintSample {
+        PrintSample {
+            print_duration,
+            progress,
+            filament_used,
+        }
+    }
+
+    fn unsmoothed() -> EtaConfig {
+        EtaConfig {
+            smoothing: 0.0,
+            ..EtaConfig::default()
+        }
+    }
+
+    #[test]
+    fn file_estimate_extrapolates_elapsed_time() {
+        let remaining = file_estimate(&sample((1800.0, 0.25, 0.0))).unwrap();
+        assert!((remaining - 5400.0).abs() < 1e-6);
+
+        assert_eq!(file_estimate(&sample((60.0, 0.0, 0.0))), None);
+    }
+
+    #[test]
+    fn filament_estimate_needs_slicer_total() {
+        let totals = SlicerTotals {
+            estimated_time: None,
+            filament_total: Some(4000.0),
+        };
+        let remaining = filament_estimate(&sample((600.0, 0.1, 1000.0)), &totals).unwrap();
+        assert!((remaining - 1800.0).abs() < 1e-6);
+
+        assert_eq!(
+            filament_estimate(&sample((600.0, 0.1, 1000.0)), &SlicerTotals::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn slicer_estimate_is_used_before_progress_is_known() {
+        let remaining = slicer_estimate(&sample((30.0, 0.0, 0.0)), &SLOW_TOTALS).unwrap();
+        assert!((remaining - 3600.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn slicer_estimate_is_scaled_by_print_speed() {
+        // twice as slow as the slicer expected, half of the file is left
+        let remaining = slicer_estimate(&sample((3600.0, 0.5, 0.0)), &SLOW_TOTALS).unwrap();
+        assert!((remaining - 3600.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn slow_print_converges_to_real_remaining_time() {
+        let mut estimator = EtaEstimator::default();
+        let config = unsmoothed();
+
+        for recorded in SLOW_PRINT.iter().skip(1) {
+            let sample = sample(*recorded);
+            let remaining = estimator.update(&sample, &SLOW_TOTALS, &config).unwrap();
+            let real_remaining = 7200.0 - sample.print_duration;
+
+            // raw slicer estimate would be off by the whole hour
+            assert!(
+                (remaining - real_remaining).abs() < 300.0,
+                "at {}s: estimated {}, real {}",
+                sample.print_duration,
+                remaining,
+                real_remaining
+            );
+        }
+    }
+
+    #[test]
+    fn missing_metadata_uses_file_progress_only() {
+        let mut estimator = EtaEstimator::default();
+        let config = unsmoothed();
+        let totals = SlicerTotals::default();
+
+        assert_eq!(
+            estimator.update(&sample(SLOW_PRINT[0]), &totals, &config),
+            None
+        );
+
+        let remaining = estimator
+            .update(&sample(SLOW_PRINT[3]), &totals, &config)
+            .unwrap();
+        assert!((remaining - 3600.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn pause_keeps_estimate() {
+        let mut estimator = EtaEstimator::default();
+        let config = EtaConfig::default();
+
+        let before = estimator
+            .update(&sample(SLOW_PRINT[2]), &SLOW_TOTALS, &config)
+            .unwrap();
+        // print_duration stops while paused, display progress may still be reported
+        for _ in 0..10 {
+            let paused = estimator
+                .update(&sample((2400.0, 0.34, 1650.0)), &SLOW_TOTALS, &config)
+                .unwrap();
+            assert_eq!(paused, before);
+        }
+    }
+
+    #[test]
+    fn smoothing_dampens_jumps() {
+        let mut estimator = EtaEstimator::default();
+        let config = EtaConfig {
+            smoothing: 0.8,
+            ..EtaConfig::default()
+        };
+        let totals = SlicerTotals::default();
+
+        let steady = estimator
+            .update(&sample((1000.0, 0.5, 0.0)), &totals, &config)
+            .unwrap();
+        // progress jumps (e.g. long travel-only section at the end of the file)
+        let jumped = estimator
+            .update(&sample((1001.0, 0.9, 0.0)), &totals, &config)
+            .unwrap();
+        let unsmoothed_jump = file_estimate(&sample((1001.0, 0.9, 0.0))).unwrap();
+
+        assert!(jumped < steady);
+        assert!(jumped > unsmoothed_jump + 500.0);
+    }
+
+    #[test]
+    fn new_print_resets_estimate() {
+        let mut estimator = EtaEstimator::default();
+        let config = EtaConfig::default();
+        let totals = SlicerTotals::default();
+
+        estimator.update(&sample((5000.0, 0.9, 0.0)), &totals, &config);
+        let remaining = estimator
+            .update(&sample((100.0, 0.1, 0.0)), &totals, &config)
+            .unwrap();
+        assert!((remaining - 900.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn weights_select_sources() {
+        let config = EtaConfig {
+            file_weight: 0.0,
+            filament_weight: 0.0,
+            slicer_weight: 1.0,
+            smoothing: 0.0,
+        };
+        let remaining = blend(&sample((3600.0, 0.5, 2500.0)), &SLOW_TOTALS, &config).unwrap();
+        assert!((remaining - 3600.0).abs() < 1e-6);
+
+        let config = EtaConfig {
+            slicer_weight: 0.0,
+            ..config
+        };
+        assert_eq!(
+            blend(&sample((3600.0, 0.5, 2500.0)), &SLOW_TOTALS, &config),
+            None
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use moonraker_api::{MoonrakerMethod, MoonrakerMsg, MoonrakerParam};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::RwLock;
+
+use crate::{
+    layout::{Canvas, ExcludeButtonKeys},
+    moonraker::{MoonrakerTx, PrinterState},
+    screen_state::ScreenState,
+};
+
+/// RGB565 colors of object outlines
+const COLOR_OBJECT: u16 = 0xFFFF;
+const COLOR_CURRENT: u16 = 0x07E0;
+const COLOR_EXCLUDED: u16 = 0xF800;
+/// Bigger plates are drawn partially, DGUS draws everything in one go
+const MAX_SEGMENTS: usize = 200;
+
+/// Entry of `exclude_object.objects`, polygon is only there when the slicer labels objects with it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PrintObject {
+    pub name: String,
+    #[serde(default)]
+    pub polygon: Vec<[f64; 2]>,
+}
+
+/// `exclude_object` status, only with [exclude_object] in klipper config
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ExcludeObjects {
+    pub objects: Vec<PrintObject>,
+    pub excluded: Vec<String>,
+    pub current: Option<String>,
+}
+
+impl ExcludeObjects {
+    /// Reads object names and polygons, excluded names and the object being printed
+    pub fn update(&mut self, status: &HashMap<String, Value>) {
+        if let Some(objects) = status.get("objects") {
+            self.objects = serde_json::from_value(objects.clone()).unwrap_or_default();
+        }
+        if let Some(excluded) = status.get("excluded_objects") {
+            self.excluded = serde_json::from_value(excluded.clone()).unwrap_or_default();
+        }
+        if let Some(current) = status.get("current_object") {
+            self.current = current.as_str().map(|c| c.to_string());
+        }
+    }
+
+    pub fn is_excluded(&self, name: &str) -> bool {
+        self.excluded.iter().any(|e| e == name)
+    }
+
+    pub fn line(&self, object: &PrintObject) -> String {
+        if self.is_excluded(&object.name) {
+            format!("[excluded] {}", object.name)
+        } else if self.current.as_deref() == Some(object.name.as_str()) {
+            format!("> {}", object.name)
+        } else {
+            object.name.clone()
+        }
+    }
+
+    /// Object outlines scaled from bed (axis min/max) to canvas, Y goes up on the bed and down on the screen
+    pub fn segments(
+        &self,
+        canvas: &Canvas,
+        axis_minimum: &[f64; 3],
+        axis_maximum: &[f64; 3],
+    ) -> Vec<(u16, [u16; 4])> {
+        let bed_width = (axis_maximum[0] - axis_minimum[0]).max(1.0);
+        let bed_depth = (axis_maximum[1] - axis_minimum[1]).max(1.0);
+        let point = |p: &[f64; 2]| {
+            let x = (p[0] - axis_minimum[0]) / bed_width * canvas.width as f64;
+            let y = (p[1] - axis_minimum[1]) / bed_depth * canvas.height as f64;
+            (
+                canvas.x + x.clamp(0.0, canvas.width as f64) as u16,
+                canvas.y + canvas.height - y.clamp(0.0, canvas.height as f64) as u16,
+            )
+        };
+
+        let mut segments = Vec::new();
+        for object in &self.objects {
+            let color = if self.is_excluded(&object.name) {
+                COLOR_EXCLUDED
+            } else if self.current.as_deref() == Some(object.name.as_str()) {
+                COLOR_CURRENT
+            } else {
+                COLOR_OBJECT
+            };
+
+            if object.polygon.len() < 2 {
+                continue;
+            }
+
+            // closed outline, last point connects back to the first one
+            for (idx, start) in object.polygon.iter().enumerate() {
+                let end = &object.polygon[(idx + 1) % object.polygon.len()];
+                let (xs, ys) = point(start);
+                let (xe, ye) = point(end);
+                segments.push((color, [xs, ys, xe, ye]));
+            }
+        }
+
+        segments.truncate(MAX_SEGMENTS);
+        segments
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExcludeButton {
+    Undefined(u16),
+
+    Up,
+    Down,
+    Line(usize),
+    Confirm,
+    Cancel,
+}
+
+impl ExcludeButton {
+    pub fn from_id(id: u16, keys: &ExcludeButtonKeys) -> Self {
+        if let Some(idx) = keys.lines.iter().position(|&code| code == id) {
+            return ExcludeButton::Line(idx);
+        }
+
+        match id {
+            id if id == keys.up => ExcludeButton::Up,
+            id if id == keys.down => ExcludeButton::Down,
+            id if id == keys.confirm => ExcludeButton::Confirm,
+            id if id == keys.cancel => ExcludeButton::Cancel,
+            _ => ExcludeButton::Undefined(id),
+        }
+    }
+}
+
+pub async fn parse_exclude_button(
+    button: ExcludeButton,
+    moonraker_tx: &MoonrakerTx,
+    screen_state: &Arc<RwLock<ScreenState>>,
+) -> Result<()> {
+    let moonraker_tx = moonraker_tx.lock().await;
+    let mut screen_state = screen_state.write().await;
+
+    match button {
+        ExcludeButton::Up => {
+            if screen_state.objects_scroll > 0 {
+                screen_state.objects_scroll -= 1;
+            }
+        }
+        ExcludeButton::Down => {
+            if screen_state.objects_scroll + 1 < screen_state.exclude_objects.objects.len() {
+                screen_state.objects_scroll += 1;
+            }
+        }
+        ExcludeButton::Line(idx) => {
+            let object = screen_state
+                .exclude_objects
+                .objects
+                .get(screen_state.objects_scroll + idx)
+                .map(|object| object.name.clone());
+
+            if let Some(name) = object {
+                if screen_state.exclude_objects.is_excluded(&name) {
+                    screen_state.exclude_message = format!("'{}' is already excluded", name);
+                    screen_state.exclude_pending = None;
+                } else {
+                    screen_state.exclude_message = format!("Exclude '{}'?", name);
+                    screen_state.exclude_pending = Some(name);
+                }
+            }
+        }
+        ExcludeButton::Confirm => {
+            let name = match screen_state.exclude_pending.take() {
+                Some(name) => name,
+                None => return Ok(()),
+            };
+
+            if screen_state.printer_state != PrinterState::Printing
+                && screen_state.printer_state != PrinterState::Paused
+            {
+                screen_state.exclude_message = "Objects can only be excluded while printing".into();
+                return Ok(());
+            }
+
+            moonraker_tx.send(MoonrakerMsg::new_param_id(
+                MoonrakerMethod::GcodeScript,
+                MoonrakerParam::GcodeScript {
+                    script: format!("EXCLUDE_OBJECT NAME={}", name),
+                },
+            ))?;
+            screen_state.exclude_message = format!("'{}' excluded", name);
+        }
+        ExcludeButton::Cancel => {
+            screen_state.exclude_pending = None;
+            screen_state.exclude_message = String::new();
+        }
+        ExcludeButton::Undefined(id) => {
+            println!("Undefined exclude button pressed with ID: {}", id);
+        }
+    }
+
+    Ok(())
+}
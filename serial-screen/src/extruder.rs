@@ -0,0 +1,286 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use tokio::{sync::RwLock, time::Instant};
+
+use crate::{
+    config::{ConfigRef, ExtruderConfig},
+    layout::ExtruderButtonKeys,
+    moonraker::MoonrakerTx,
+    preheat,
+    screen_state::ScreenState,
+    utils::send_gcode_script,
+};
+
+/// Nozzle is considered at target when it's this close
+const TEMP_TOLERANCE: i16 = 3;
+/// Load/unload is considered done if klipper didn't start it in this time
+const MOVE_START_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilamentAction {
+    Load,
+    Unload,
+}
+
+impl FilamentAction {
+    fn macro_name(self) -> &'static str {
+        match self {
+            FilamentAction::Load => "LOAD_FILAMENT",
+            FilamentAction::Unload => "UNLOAD_FILAMENT",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            FilamentAction::Load => "Loading",
+            FilamentAction::Unload => "Unloading",
+        }
+    }
+}
+
+/// Load/unload flow: heat to material temperature, wait, purge or retract, done
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilamentWizard {
+    Idle,
+    Heating {
+        action: FilamentAction,
+        target: i16,
+    },
+    Moving {
+        action: FilamentAction,
+        started: bool,
+        since: Instant,
+    },
+    Done(FilamentAction),
+}
+
+impl FilamentWizard {
+    /// Step number shown on the page (0 - idle, 1 - heating, 2 - moving, 3 - done)
+    pub fn step(&self) -> i16 {
+        match self {
+            FilamentWizard::Idle => 0,
+            FilamentWizard::Heating { .. } => 1,
+            FilamentWizard::Moving { .. } => 2,
+            FilamentWizard::Done(_) => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtruderButton {
+    Undefined(u16),
+
+    Extrude,
+    Retract,
+    Length(usize),
+    Speed(usize),
+    Load,
+    Unload,
+    Cancel,
+}
+
+impl ExtruderButton {
+    pub fn from_id(id: u16, keys: &ExtruderButtonKeys) -> Self {
+        if let Some(idx) = keys.lengths.iter().position(|&code| code == id) {
+            return ExtruderButton::Length(idx);
+        }
+        if let Some(idx) = keys.speeds.iter().position(|&code| code == id) {
+            return ExtruderButton::Speed(idx);
+        }
+
+        match id {
+            id if id == keys.extrude => ExtruderButton::Extrude,
+            id if id == keys.retract => ExtruderButton::Retract,
+            id if id == keys.load => ExtruderButton::Load,
+            id if id == keys.unload => ExtruderButton::Unload,
+            id if id == keys.cancel => ExtruderButton::Cancel,
+            _ => ExtruderButton::Undefined(id),
+        }
+    }
+}
+
+/// Relative extrusion without changing the mode used by the print (it can be paused)
+fn extrude_script(length: f64, speed: f64) -> String {
+    format!(
+        "SAVE_GCODE_STATE NAME=serial_screen_extrude\nM83\nG1 E{:+.2} F{}\nRESTORE_GCODE_STATE NAME=serial_screen_extrude",
+        length,
+        (speed * 60.0).round()
+    )
+}
+
+/// User macro if it's defined, otherwise purge/retract with lengths from config
+fn filament_script(action: FilamentAction, macros: &[String], config: &ExtruderConfig) -> String {
+    let macro_name = action.macro_name();
+    if macros.iter().any(|m| m.eq_ignore_ascii_case(macro_name)) {
+        return macro_name.to_string();
+    }
+
+    match action {
+        FilamentAction::Load => extrude_script(config.load_length, config.filament_speed),
+        FilamentAction::Unload => format!(
+            "{}\n{}",
+            // form the tip, so it doesn't get stuck in the hotend
+            extrude_script(5.0, config.filament_speed),
+            extrude_script(-config.unload_length, config.filament_speed)
+        ),
+    }
+}
+
+/// Moves the wizard forward, called every screen update
+pub async fn advance_wizard(
+    screen_state: &mut ScreenState,
+    moonraker_tx: &MoonrakerTx,
+    config: &ExtruderConfig,
+) -> Result<()> {
+    match screen_state.filament_wizard {
+        FilamentWizard::Heating { action, target } => {
+            screen_state.extruder_message =
+                format!("Heating {}/{}", screen_state.nozzle_temp, target);
+
+            if screen_state.nozzle_temp >= target - TEMP_TOLERANCE && screen_state.can_extrude {
+                let script = filament_script(action, &screen_state.macros, config);
+                send_gcode_script(&*moonraker_tx.lock().await, script)?;
+
+                screen_state.filament_wizard = FilamentWizard::Moving {
+                    action,
+                    started: false,
+                    since: Instant::now(),
+                };
+                screen_state.extruder_message = format!("{} filament...", action.label());
+            }
+        }
+        FilamentWizard::Moving {
+            action,
+            started,
+            since,
+        } => {
+            if screen_state.idle_printing && !started {
+                screen_state.filament_wizard = FilamentWizard::Moving {
+                    action,
+                    started: true,
+                    since,
+                };
+            } else if (started && !screen_state.idle_printing)
+                || (!started && since.elapsed() > MOVE_START_TIMEOUT)
+            {
+                screen_state.filament_wizard = FilamentWizard::Done(action);
+                screen_state.extruder_message = match action {
+                    FilamentAction::Load => "Filament loaded".to_string(),
+                    FilamentAction::Unload => "Filament unloaded".to_string(),
+                };
+            }
+        }
+        FilamentWizard::Idle | FilamentWizard::Done(_) => {}
+    }
+
+    Ok(())
+}
+
+/// Nozzle target for load/unload: current one if it's set, otherwise the first preset
+fn material_temp(screen_state: &ScreenState) -> Option<i16> {
+    if screen_state.target_nozzle_temp > 0 {
+        return Some(screen_state.target_nozzle_temp);
+    }
+
+    screen_state.presets.first().and_then(|preset| {
+        preset
+            .targets
+            .iter()
+            .find(|(object, _)| object == "extruder")
+            .map(|(_, target)| *target)
+    })
+}
+
+pub async fn parse_extruder_button(
+    button: ExtruderButton,
+    moonraker_tx: &MoonrakerTx,
+    screen_state: &Arc<RwLock<ScreenState>>,
+    config: &ConfigRef,
+) -> Result<()> {
+    let moonraker_tx = moonraker_tx.lock().await;
+    let mut screen_state = screen_state.write().await;
+    let extruder = config.read().await.extruder.clone();
+
+    let wizard_running = matches!(
+        screen_state.filament_wizard,
+        FilamentWizard::Heating { .. } | FilamentWizard::Moving { .. }
+    );
+
+    match button {
+        ExtruderButton::Extrude | ExtruderButton::Retract => {
+            if wizard_running {
+                return Ok(());
+            }
+            if !screen_state.can_extrude {
+                screen_state.extruder_message = "Nozzle is too cold".to_string();
+                return Ok(());
+            }
+
+            let length = screen_state
+                .extrude_length
+                .unwrap_or(extruder.default_length);
+            let speed = screen_state.extrude_speed.unwrap_or(extruder.default_speed);
+            let length = match button {
+                ExtruderButton::Retract => -length,
+                _ => length,
+            };
+
+            send_gcode_script(&moonraker_tx, extrude_script(length, speed))?;
+            screen_state.extruder_message = String::new();
+        }
+        ExtruderButton::Length(idx) => {
+            if let Some(length) = extruder.lengths.get(idx) {
+                screen_state.extrude_length = Some(*length);
+            }
+        }
+        ExtruderButton::Speed(idx) => {
+            if let Some(speed) = extruder.speeds.get(idx) {
+                screen_state.extrude_speed = Some(*speed);
+            }
+        }
+        ExtruderButton::Load | ExtruderButton::Unload => {
+            if wizard_running {
+                return Ok(());
+            }
+
+            let action = match button {
+                ExtruderButton::Load => FilamentAction::Load,
+                _ => FilamentAction::Unload,
+            };
+
+            let target = match material_temp(&screen_state) {
+                Some(target) => target,
+                None => {
+                    screen_state.extruder_message = "Set nozzle temperature first".to_string();
+                    return Ok(());
+                }
+            };
+            if let Err(e) = preheat::validate_target(&screen_state, "extruder", target) {
+                screen_state.extruder_message = e.to_string();
+                return Ok(());
+            }
+
+            if screen_state.target_nozzle_temp != target {
+                send_gcode_script(
+                    &moonraker_tx,
+                    preheat::set_heater_script("extruder", target),
+                )?;
+            }
+            screen_state.filament_wizard = FilamentWizard::Heating { action, target };
+            screen_state.extruder_message =
+                format!("Heating {}/{}", screen_state.nozzle_temp, target);
+        }
+        ExtruderButton::Cancel => {
+            if wizard_running {
+                screen_state.extruder_message = "Cancelled".to_string();
+            }
+            screen_state.filament_wizard = FilamentWizard::Idle;
+        }
+        ExtruderButton::Undefined(id) => {
+            println!("Undefined extruder button pressed with ID: {}", id);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,1093 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, path::Path};
+
+pub const DEFAULT_LAYOUT_PATH: &str = "/opt/serial-screen/layout.toml";
+
+/// Describes the DGUS project that serial-screen is driving.
+/// Defaults match the bundled `DGUS-Project`, so a layout file only
+/// needs to contain the values that differ (TOML accepts hex like `0x2000`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Layout {
+    pub pages: Pages,
+    pub addresses: Addresses,
+    pub buttons: ButtonKeys,
+    pub movement_buttons: MovementButtonKeys,
+    pub update_buttons: UpdateButtonKeys,
+    pub preheat_buttons: PreheatButtonKeys,
+    pub extruder_buttons: ExtruderButtonKeys,
+    pub tune_buttons: TuneButtonKeys,
+    pub exclude_buttons: ExcludeButtonKeys,
+    pub summary_buttons: SummaryButtonKeys,
+    pub confirm_buttons: ConfirmButtonKeys,
+    pub bed_mesh_buttons: BedMeshButtonKeys,
+    pub calibrate_buttons: CalibrateButtonKeys,
+    pub sensor_buttons: SensorButtonKeys,
+    pub output_buttons: OutputButtonKeys,
+    pub power_buttons: PowerButtonKeys,
+    pub inputs: InputAddresses,
+    pub exclude_canvas: Canvas,
+    pub mesh_canvas: Canvas,
+    pub temp_curves: CurveChannels,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Pages {
+    pub boot: u16,
+    pub main: u16,
+    pub printing: u16,
+    pub emergency_stop: u16,
+    pub preheat: u16,
+    pub movement: u16,
+    pub macros: u16,
+    pub updates: u16,
+    pub connection: u16,
+    pub error: u16,
+    pub custom_preheat: u16,
+    pub extruder: u16,
+    pub tune: u16,
+    pub exclude_objects: u16,
+    pub print_summary: u16,
+    pub confirm: u16,
+    pub temp_graph: u16,
+    pub bed_mesh: u16,
+    pub calibrate: u16,
+    pub sensors: u16,
+    pub outputs: u16,
+    pub power: u16,
+    pub system: u16,
+    pub settings: u16,
+}
+
+impl Default for Pages {
+    fn default() -> Self {
+        Pages {
+            boot: 0,
+            main: 1,
+            printing: 2,
+            emergency_stop: 3,
+            preheat: 4,
+            movement: 5,
+            macros: 6,
+            updates: 7,
+            connection: 8,
+            error: 9,
+            custom_preheat: 10,
+            extruder: 11,
+            tune: 12,
+            exclude_objects: 13,
+            print_summary: 14,
+            confirm: 15,
+            temp_graph: 16,
+            bed_mesh: 17,
+            calibrate: 18,
+            sensors: 19,
+            outputs: 20,
+            power: 21,
+            system: 22,
+            settings: 23,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Addresses {
+    pub time: u16,                          // 5 chars "HH:MM"
+    pub eta: u16,                           // 10 chars "ETA: HH:MM"
+    pub model_name: u16,                    // 20 chars
+    pub nozzle_temp: u16,                   // i16
+    pub target_nozzle_temp: u16,            // i16
+    pub bed_temp: u16,                      // i16
+    pub target_bed_temp: u16,               // i16
+    pub progress: u16,                      // i16 (0-100)
+    pub paused: u16,                        // i16 (0/1)
+    pub macro_lines: Vec<u16>,              // 50 chars each
+    pub update_lines: Vec<u16>,             // 50 chars each
+    pub update_message: u16,                // 50 chars
+    pub connection_title: u16,              // 30 chars
+    pub connection_message_lines: Vec<u16>, // 50 chars each
+    pub error_title: u16,                   // 30 chars
+    pub error_kind: u16,                    // i16 (0 - print error, 1 - shutdown, 2 - klippy error)
+    pub error_message_lines: Vec<u16>,      // 50 chars each
+    pub preheat_message: u16,               // 30 chars
+    pub preheat_target: u16,                // 20 chars
+    pub preset_lines: Vec<u16>,             // 50 chars each
+    pub position: u16,                      // 40 chars "X000.00 Y000.00 Z000.00"
+    pub jog_step: u16,                      // 10 chars
+    pub movement_message: u16,              // 30 chars
+    pub movement_available: u16,            // i16 bits of MovementCommand
+    pub movement_busy: u16,                 // i16 bits of MovementCommand
+    pub extrude_length: u16,                // 10 chars
+    pub extrude_speed: u16,                 // 10 chars
+    pub extruder_message: u16,              // 30 chars
+    pub filament_step: u16,                 // i16 (0 - idle, 1 - heating, 2 - moving, 3 - done)
+    pub tune_speed: u16,                    // i16 percent
+    pub tune_flow: u16,                     // i16 percent
+    pub tune_fan: u16,                      // i16 percent
+    pub z_offset: u16,                      // 10 chars
+    pub tune_message: u16,                  // 30 chars
+    pub object_lines: Vec<u16>,             // 50 chars each
+    pub exclude_message: u16,               // 30 chars
+    pub exclude_confirm: u16,               // i16 (0/1 - confirm buttons shown)
+    pub exclude_graphics: u16,              // basic graphic, 1000+ words
+    pub print_layer: u16,                   // 20 chars "Layer 12/150"
+    pub print_elapsed: u16,                 // 20 chars "Elapsed 1:23:45"
+    pub print_filament: u16,                // 30 chars "Filament 1.25/4.80 m"
+    pub print_height: u16,                  // 30 chars "Z 2.40/15.00 mm"
+    pub print_finish: u16,                  // 20 chars "Done at 14:32"
+    pub status_line: u16,                   // 30 chars
+    pub summary_title: u16,                 // 30 chars
+    pub summary_lines: Vec<u16>,            // 50 chars each
+    pub confirm_message: u16,               // 40 chars
+    pub mesh_range: u16,                    // 30 chars "Min -0.050  Max +0.075 mm"
+    pub mesh_deviation: u16,                // 30 chars "Range 0.125  Deviation 0.031 mm"
+    pub mesh_message: u16,                  // 30 chars
+    pub mesh_profile_lines: Vec<u16>,       // 50 chars each
+    pub mesh_graphics: u16,                 // basic graphic, 2000+ words
+    pub calibration_step: u16,              // i16 (0 idle, 1 Z offset, 2 screws, 3 PID, 4 save)
+    pub calibration_title: u16,             // 30 chars
+    pub calibration_message: u16,           // 30 chars
+    pub calibration_z_step: u16,            // 10 chars
+    pub calibration_lines: Vec<u16>,        // 50 chars each
+    pub sensor_lines: Vec<u16>,             // 50 chars each
+    pub sensor_message: u16,                // 30 chars
+    pub active_tool: u16,                   // 20 chars
+    pub output_lines: Vec<u16>,             // 50 chars each
+    pub output_message: u16,                // 30 chars
+    pub power_lines: Vec<u16>,              // 50 chars each
+    pub power_message: u16,                 // 30 chars
+    pub printer_power: u16,                 // i16 (0 - unknown, 1 - off, 2 - on)
+    pub system_cpu: u16,                    // 30 chars
+    pub system_memory: u16,                 // 30 chars
+    pub system_uptime: u16,                 // 20 chars
+    pub system_cores: u16,                  // 50 chars
+    pub klipper_version: u16,               // 50 chars
+    pub moonraker_version: u16,             // 50 chars
+    pub network_lines: Vec<u16>,            // 50 chars each
+}
+
+impl Default for Addresses {
+    fn default() -> Self {
+        Addresses {
+            time: 0x2000,
+            eta: 0x2005,
+            model_name: 0x2015,
+            nozzle_temp: 0x2025,
+            target_nozzle_temp: 0x2026,
+            bed_temp: 0x2027,
+            target_bed_temp: 0x2028,
+            progress: 0x2029,
+            paused: 0x2030,
+            macro_lines: vec![0x3000, 0x3051, 0x3102, 0x3153],
+            update_lines: vec![0x3204, 0x3255, 0x3306, 0x3357],
+            update_message: 0x3408,
+            connection_title: 0x2100,
+            connection_message_lines: vec![0x3459, 0x3510, 0x3561, 0x3612],
+            error_title: 0x2130,
+            error_kind: 0x2160,
+            error_message_lines: vec![0x3663, 0x3714, 0x3765, 0x3816, 0x3867, 0x3918],
+            preheat_message: 0x2161,
+            preheat_target: 0x2191,
+            preset_lines: vec![0x3969, 0x4020, 0x4071, 0x4122],
+            position: 0x2211,
+            jog_step: 0x2251,
+            movement_message: 0x2261,
+            movement_available: 0x2291,
+            movement_busy: 0x2292,
+            extrude_length: 0x2293,
+            extrude_speed: 0x2303,
+            extruder_message: 0x2313,
+            filament_step: 0x2343,
+            tune_speed: 0x2344,
+            tune_flow: 0x2345,
+            tune_fan: 0x2346,
+            z_offset: 0x2347,
+            tune_message: 0x2357,
+            object_lines: vec![0x4173, 0x4224, 0x4275, 0x4326],
+            exclude_message: 0x2387,
+            exclude_confirm: 0x2417,
+            exclude_graphics: 0x5000,
+            print_layer: 0x2418,
+            print_elapsed: 0x2438,
+            print_filament: 0x2458,
+            print_height: 0x2488,
+            print_finish: 0x2518,
+            status_line: 0x2538,
+            summary_title: 0x2568,
+            summary_lines: vec![0x4377, 0x4428, 0x4479, 0x4530],
+            confirm_message: 0x2598,
+            mesh_range: 0x2638,
+            mesh_deviation: 0x2668,
+            mesh_message: 0x2698,
+            mesh_profile_lines: vec![0x4581, 0x4632, 0x4683, 0x4734],
+            mesh_graphics: 0x5800,
+            calibration_step: 0x2728,
+            calibration_title: 0x2729,
+            calibration_message: 0x2759,
+            calibration_z_step: 0x2789,
+            calibration_lines: vec![0x4785, 0x4836, 0x4887, 0x4938],
+            sensor_lines: vec![0x6000, 0x6051, 0x6102, 0x6153],
+            sensor_message: 0x2799,
+            active_tool: 0x2829,
+            output_lines: vec![0x6204, 0x6255, 0x6306, 0x6357],
+            output_message: 0x2849,
+            power_lines: vec![0x6408, 0x6459, 0x6510, 0x6561],
+            power_message: 0x2879,
+            printer_power: 0x2909,
+            system_cpu: 0x2910,
+            system_memory: 0x2940,
+            system_uptime: 0x2970,
+            system_cores: 0x6612,
+            klipper_version: 0x6663,
+            moonraker_version: 0x6714,
+            network_lines: vec![0x6765, 0x6816, 0x6867, 0x6918],
+        }
+    }
+}
+
+/// Key codes written to `address` by the return-key-code touch controls
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ButtonKeys {
+    pub address: u16,
+
+    pub printing_progress: u16,
+    pub emergency_stop: u16,
+    pub preheat: u16,
+    pub printer_movement: u16,
+    pub settings: u16,
+    pub back_to_main: u16,
+    pub pause: u16,
+    pub stop: u16,
+    pub emergency_stop_release: u16,
+    pub preheat_pla: u16,
+    pub preheat_cooldown: u16,
+    pub preheat_custom: u16,
+    pub macros_up: u16,
+    pub macros_down: u16,
+    pub macros_buttons: Vec<u16>,
+    pub macros: u16,
+    pub updates: u16,
+    pub firmware_restart: u16,
+    pub host_restart: u16,
+    pub error_dismiss: u16,
+    pub extruder: u16,
+    pub tune: u16,
+    pub exclude_objects: u16,
+    pub temp_graph: u16,
+    pub bed_mesh: u16,
+    pub calibrate: u16,
+    pub sensors: u16,
+    pub outputs: u16,
+    pub power: u16,
+    pub system: u16,
+}
+
+impl Default for ButtonKeys {
+    fn default() -> Self {
+        ButtonKeys {
+            address: 0x1000,
+
+            printing_progress: 1,
+            emergency_stop: 2,
+            preheat: 3,
+            printer_movement: 4,
+            settings: 5,
+            back_to_main: 6,
+            pause: 7,
+            stop: 8,
+            emergency_stop_release: 9,
+            preheat_pla: 10,
+            preheat_cooldown: 11,
+            preheat_custom: 12,
+            macros_up: 13,
+            macros_down: 14,
+            macros_buttons: vec![15, 16, 17, 18],
+            macros: 19,
+            updates: 20,
+            firmware_restart: 21,
+            host_restart: 22,
+            error_dismiss: 23,
+            extruder: 24,
+            tune: 25,
+            exclude_objects: 26,
+            temp_graph: 27,
+            bed_mesh: 28,
+            calibrate: 29,
+            sensors: 30,
+            outputs: 31,
+            power: 32,
+            system: 33,
+        }
+    }
+}
+
+impl ButtonKeys {
+    fn codes(&self) -> Vec<u16> {
+        let mut codes = vec![
+            self.printing_progress,
+            self.emergency_stop,
+            self.preheat,
+            self.printer_movement,
+            self.settings,
+            self.back_to_main,
+            self.pause,
+            self.stop,
+            self.emergency_stop_release,
+            self.preheat_pla,
+            self.preheat_cooldown,
+            self.preheat_custom,
+            self.macros_up,
+            self.macros_down,
+            self.macros,
+            self.updates,
+            self.firmware_restart,
+            self.host_restart,
+            self.error_dismiss,
+            self.extruder,
+            self.tune,
+            self.exclude_objects,
+            self.temp_graph,
+            self.bed_mesh,
+            self.calibrate,
+            self.sensors,
+            self.outputs,
+            self.power,
+            self.system,
+        ];
+        codes.extend_from_slice(&self.macros_buttons);
+
+        codes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MovementButtonKeys {
+    pub address: u16,
+
+    pub y_plus: u16,
+    pub x_plus: u16,
+    pub x_minus: u16,
+    pub y_minus: u16,
+    pub z_plus: u16,
+    pub z_minus: u16,
+    pub home: u16,
+    pub steps: Vec<u16>,
+    pub home_x: u16,
+    pub home_y: u16,
+    pub home_z: u16,
+    pub motors_off: u16,
+    pub z_tilt_adjust: u16,
+    pub quad_gantry_level: u16,
+    pub bed_mesh_calibrate: u16,
+}
+
+impl Default for MovementButtonKeys {
+    fn default() -> Self {
+        MovementButtonKeys {
+            address: 0x1001,
+
+            y_plus: 1,
+            x_plus: 2,
+            x_minus: 3,
+            y_minus: 4,
+            z_plus: 5,
+            z_minus: 6,
+            home: 7,
+            steps: vec![8, 9, 10, 11],
+            home_x: 12,
+            home_y: 13,
+            home_z: 14,
+            motors_off: 15,
+            z_tilt_adjust: 16,
+            quad_gantry_level: 17,
+            bed_mesh_calibrate: 18,
+        }
+    }
+}
+
+impl MovementButtonKeys {
+    fn codes(&self) -> Vec<u16> {
+        let mut codes = vec![
+            self.y_plus,
+            self.x_plus,
+            self.x_minus,
+            self.y_minus,
+            self.z_plus,
+            self.z_minus,
+            self.home,
+            self.home_x,
+            self.home_y,
+            self.home_z,
+            self.motors_off,
+            self.z_tilt_adjust,
+            self.quad_gantry_level,
+            self.bed_mesh_calibrate,
+        ];
+        codes.extend_from_slice(&self.steps);
+
+        codes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpdateButtonKeys {
+    pub address: u16,
+
+    pub up: u16,
+    pub down: u16,
+    pub lines: Vec<u16>,
+    pub refresh: u16,
+    pub upgrade_all: u16,
+}
+
+impl Default for UpdateButtonKeys {
+    fn default() -> Self {
+        UpdateButtonKeys {
+            address: 0x1002,
+
+            up: 1,
+            down: 2,
+            lines: vec![3, 4, 5, 6],
+            refresh: 7,
+            upgrade_all: 8,
+        }
+    }
+}
+
+impl UpdateButtonKeys {
+    fn codes(&self) -> Vec<u16> {
+        let mut codes = vec![self.up, self.down, self.refresh, self.upgrade_all];
+        codes.extend_from_slice(&self.lines);
+
+        codes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PreheatButtonKeys {
+    pub address: u16,
+
+    pub up: u16,
+    pub down: u16,
+    pub lines: Vec<u16>,
+    pub target: u16,
+}
+
+impl Default for PreheatButtonKeys {
+    fn default() -> Self {
+        PreheatButtonKeys {
+            address: 0x1003,
+
+            up: 1,
+            down: 2,
+            lines: vec![3, 4, 5, 6],
+            target: 7,
+        }
+    }
+}
+
+impl PreheatButtonKeys {
+    fn codes(&self) -> Vec<u16> {
+        let mut codes = vec![self.up, self.down, self.target];
+        codes.extend_from_slice(&self.lines);
+
+        codes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExtruderButtonKeys {
+    pub address: u16,
+
+    pub extrude: u16,
+    pub retract: u16,
+    pub lengths: Vec<u16>,
+    pub speeds: Vec<u16>,
+    pub load: u16,
+    pub unload: u16,
+    pub cancel: u16,
+}
+
+impl Default for ExtruderButtonKeys {
+    fn default() -> Self {
+        ExtruderButtonKeys {
+            address: 0x1004,
+
+            extrude: 1,
+            retract: 2,
+            lengths: vec![3, 4, 5, 6],
+            speeds: vec![7, 8, 9],
+            load: 10,
+            unload: 11,
+            cancel: 12,
+        }
+    }
+}
+
+impl ExtruderButtonKeys {
+    fn codes(&self) -> Vec<u16> {
+        let mut codes = vec![
+            self.extrude,
+            self.retract,
+            self.load,
+            self.unload,
+            self.cancel,
+        ];
+        codes.extend_from_slice(&self.lengths);
+        codes.extend_from_slice(&self.speeds);
+
+        codes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TuneButtonKeys {
+    pub address: u16,
+
+    pub speed_up: u16,
+    pub speed_down: u16,
+    pub flow_up: u16,
+    pub flow_down: u16,
+    pub fan_up: u16,
+    pub fan_down: u16,
+    pub nozzle_up: u16,
+    pub nozzle_down: u16,
+    pub bed_up: u16,
+    pub bed_down: u16,
+    pub z_up: u16,
+    pub z_down: u16,
+    pub save_z_offset: u16,
+}
+
+impl Default for TuneButtonKeys {
+    fn default() -> Self {
+        TuneButtonKeys {
+            address: 0x1005,
+
+            speed_up: 1,
+            speed_down: 2,
+            flow_up: 3,
+            flow_down: 4,
+            fan_up: 5,
+            fan_down: 6,
+            nozzle_up: 7,
+            nozzle_down: 8,
+            bed_up: 9,
+            bed_down: 10,
+            z_up: 11,
+            z_down: 12,
+            save_z_offset: 13,
+        }
+    }
+}
+
+impl TuneButtonKeys {
+    fn codes(&self) -> Vec<u16> {
+        vec![
+            self.speed_up,
+            self.speed_down,
+            self.flow_up,
+            self.flow_down,
+            self.fan_up,
+            self.fan_down,
+            self.nozzle_up,
+            self.nozzle_down,
+            self.bed_up,
+            self.bed_down,
+            self.z_up,
+            self.z_down,
+            self.save_z_offset,
+        ]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExcludeButtonKeys {
+    pub address: u16,
+
+    pub up: u16,
+    pub down: u16,
+    pub lines: Vec<u16>,
+    pub confirm: u16,
+    pub cancel: u16,
+}
+
+impl Default for ExcludeButtonKeys {
+    fn default() -> Self {
+        ExcludeButtonKeys {
+            address: 0x1006,
+
+            up: 1,
+            down: 2,
+            lines: vec![3, 4, 5, 6],
+            confirm: 7,
+            cancel: 8,
+        }
+    }
+}
+
+impl ExcludeButtonKeys {
+    fn codes(&self) -> Vec<u16> {
+        let mut codes = vec![self.up, self.down, self.confirm, self.cancel];
+        codes.extend_from_slice(&self.lines);
+
+        codes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SummaryButtonKeys {
+    pub address: u16,
+
+    pub print_again: u16,
+    pub cool_down: u16,
+    pub ok: u16,
+}
+
+impl Default for SummaryButtonKeys {
+    fn default() -> Self {
+        SummaryButtonKeys {
+            address: 0x1007,
+
+            print_again: 1,
+            cool_down: 2,
+            ok: 3,
+        }
+    }
+}
+
+impl SummaryButtonKeys {
+    fn codes(&self) -> Vec<u16> {
+        vec![self.print_again, self.cool_down, self.ok]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfirmButtonKeys {
+    pub address: u16,
+
+    pub yes: u16,
+    pub no: u16,
+}
+
+impl Default for ConfirmButtonKeys {
+    fn default() -> Self {
+        ConfirmButtonKeys {
+            address: 0x1008,
+
+            yes: 1,
+            no: 2,
+        }
+    }
+}
+
+impl ConfirmButtonKeys {
+    fn codes(&self) -> Vec<u16> {
+        vec![self.yes, self.no]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BedMeshButtonKeys {
+    pub address: u16,
+
+    pub up: u16,
+    pub down: u16,
+    pub lines: Vec<u16>,
+    pub clear: u16,
+}
+
+impl Default for BedMeshButtonKeys {
+    fn default() -> Self {
+        BedMeshButtonKeys {
+            address: 0x1009,
+
+            up: 1,
+            down: 2,
+            lines: vec![3, 4, 5, 6],
+            clear: 7,
+        }
+    }
+}
+
+impl BedMeshButtonKeys {
+    fn codes(&self) -> Vec<u16> {
+        let mut codes = vec![self.up, self.down, self.clear];
+        codes.extend_from_slice(&self.lines);
+
+        codes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CalibrateButtonKeys {
+    pub address: u16,
+
+    pub z_offset: u16,
+    pub screws_tilt: u16,
+    pub pid_nozzle: u16,
+    pub pid_bed: u16,
+    pub z_up: u16,
+    pub z_down: u16,
+    pub z_steps: Vec<u16>,
+    pub accept: u16,
+    pub abort: u16,
+    pub save_config: u16,
+    pub close: u16,
+}
+
+impl Default for CalibrateButtonKeys {
+    fn default() -> Self {
+        CalibrateButtonKeys {
+            address: 0x100A,
+
+            z_offset: 1,
+            screws_tilt: 2,
+            pid_nozzle: 3,
+            pid_bed: 4,
+            z_up: 5,
+            z_down: 6,
+            z_steps: vec![7, 8, 9, 10],
+            accept: 11,
+            abort: 12,
+            save_config: 13,
+            close: 14,
+        }
+    }
+}
+
+impl CalibrateButtonKeys {
+    fn codes(&self) -> Vec<u16> {
+        let mut codes = vec![
+            self.z_offset,
+            self.screws_tilt,
+            self.pid_nozzle,
+            self.pid_bed,
+            self.z_up,
+            self.z_down,
+            self.accept,
+            self.abort,
+            self.save_config,
+            self.close,
+        ];
+        codes.extend_from_slice(&self.z_steps);
+
+        codes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SensorButtonKeys {
+    pub address: u16,
+
+    pub up: u16,
+    pub down: u16,
+    pub lines: Vec<u16>,
+    pub next_tool: u16,
+    pub off: u16,
+}
+
+impl Default for SensorButtonKeys {
+    fn default() -> Self {
+        SensorButtonKeys {
+            address: 0x100B,
+
+            up: 1,
+            down: 2,
+            lines: vec![3, 4, 5, 6],
+            next_tool: 7,
+            off: 8,
+        }
+    }
+}
+
+impl SensorButtonKeys {
+    fn codes(&self) -> Vec<u16> {
+        let mut codes = vec![self.up, self.down, self.next_tool, self.off];
+        codes.extend_from_slice(&self.lines);
+
+        codes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputButtonKeys {
+    pub address: u16,
+
+    pub up: u16,
+    pub down: u16,
+    pub lines: Vec<u16>,
+    pub toggle: u16,
+}
+
+impl Default for OutputButtonKeys {
+    fn default() -> Self {
+        OutputButtonKeys {
+            address: 0x100C,
+
+            up: 1,
+            down: 2,
+            lines: vec![3, 4, 5, 6],
+            toggle: 7,
+        }
+    }
+}
+
+impl OutputButtonKeys {
+    fn codes(&self) -> Vec<u16> {
+        let mut codes = vec![self.up, self.down, self.toggle];
+        codes.extend_from_slice(&self.lines);
+
+        codes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerButtonKeys {
+    pub address: u16,
+
+    pub up: u16,
+    pub down: u16,
+    pub lines: Vec<u16>,
+    /// On the connection page
+    pub printer_on: u16,
+}
+
+impl Default for PowerButtonKeys {
+    fn default() -> Self {
+        PowerButtonKeys {
+            address: 0x100D,
+
+            up: 1,
+            down: 2,
+            lines: vec![3, 4, 5, 6],
+            printer_on: 7,
+        }
+    }
+}
+
+impl PowerButtonKeys {
+    fn codes(&self) -> Vec<u16> {
+        let mut codes = vec![self.up, self.down, self.printer_on];
+        codes.extend_from_slice(&self.lines);
+
+        codes
+    }
+}
+
+/// Screen area (in pixels) the bed is drawn into by basic graphic controls
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Canvas {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Default for Canvas {
+    fn default() -> Self {
+        Canvas {
+            x: 20,
+            y: 60,
+            width: 200,
+            height: 200,
+        }
+    }
+}
+
+/// DGUS curve channels (0-7) of the temperature graph, the trend curve controls select them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CurveChannels {
+    pub nozzle: u8,
+    pub nozzle_target: u8,
+    pub bed: u8,
+    pub bed_target: u8,
+}
+
+impl Default for CurveChannels {
+    fn default() -> Self {
+        CurveChannels {
+            nozzle: 0,
+            nozzle_target: 1,
+            bed: 2,
+            bed_target: 3,
+        }
+    }
+}
+
+impl CurveChannels {
+    /// In `TempPoint` order
+    pub fn channels(&self) -> [u8; 4] {
+        [self.nozzle, self.nozzle_target, self.bed, self.bed_target]
+    }
+}
+
+/// VPs written by the screen's data entry (numeric keypad) controls
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputAddresses {
+    pub nozzle_target: u16,
+    pub bed_target: u16,
+    pub sensor_target: u16,
+    /// Sliders (0-100), serial-screen writes the selected output's values back to them
+    pub output_value: u16,
+    pub led_red: u16,
+    pub led_green: u16,
+    pub led_blue: u16,
+}
+
+impl Default for InputAddresses {
+    fn default() -> Self {
+        InputAddresses {
+            nozzle_target: 0x1100,
+            bed_target: 0x1101,
+            sensor_target: 0x1102,
+            output_value: 0x1103,
+            led_red: 0x1104,
+            led_green: 0x1105,
+            led_blue: 0x1106,
+        }
+    }
+}
+
+impl InputAddresses {
+    pub fn contains(&self, address: u16) -> bool {
+        self.addresses().iter().any(|(_, a)| *a == address)
+    }
+
+    fn addresses(&self) -> Vec<(&'static str, u16)> {
+        vec![
+            ("inputs.nozzle_target", self.nozzle_target),
+            ("inputs.bed_target", self.bed_target),
+            ("inputs.sensor_target", self.sensor_target),
+            ("inputs.output_value", self.output_value),
+            ("inputs.led_red", self.led_red),
+            ("inputs.led_green", self.led_green),
+            ("inputs.led_blue", self.led_blue),
+        ]
+    }
+}
+
+impl Layout {
+    /// Loads layout from file, `.json` files are parsed as JSON, everything else as TOML
+    pub fn load(path: &Path) -> Result<Layout> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read layout file {}: {}", path.display(), e))?;
+
+        let layout: Layout = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&content)
+                .map_err(|e| anyhow::anyhow!("Layout file {}: {}", path.display(), e))?
+        } else {
+            toml::from_str(&content)
+                .map_err(|e| anyhow::anyhow!("Layout file {}: {}", path.display(), e))?
+        };
+
+        layout.validate()?;
+        Ok(layout)
+    }
+
+    /// Loads layout from `path` if it exists, otherwise returns the built-in default
+    pub fn load_or_default(path: &Path) -> Result<Layout> {
+        if !path.exists() {
+            return Ok(Layout::default());
+        }
+
+        Layout::load(path)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        check_unique_codes("buttons", &self.buttons.codes())?;
+        check_unique_codes("movement_buttons", &self.movement_buttons.codes())?;
+        check_unique_codes("update_buttons", &self.update_buttons.codes())?;
+        check_unique_codes("preheat_buttons", &self.preheat_buttons.codes())?;
+        check_unique_codes("extruder_buttons", &self.extruder_buttons.codes())?;
+        check_unique_codes("tune_buttons", &self.tune_buttons.codes())?;
+        check_unique_codes("exclude_buttons", &self.exclude_buttons.codes())?;
+        check_unique_codes("summary_buttons", &self.summary_buttons.codes())?;
+        check_unique_codes("confirm_buttons", &self.confirm_buttons.codes())?;
+        check_unique_codes("bed_mesh_buttons", &self.bed_mesh_buttons.codes())?;
+        check_unique_codes("calibrate_buttons", &self.calibrate_buttons.codes())?;
+        check_unique_codes("sensor_buttons", &self.sensor_buttons.codes())?;
+        check_unique_codes("output_buttons", &self.output_buttons.codes())?;
+        check_unique_codes("power_buttons", &self.power_buttons.codes())?;
+
+        let channels = self.temp_curves.channels();
+        check_unique_codes("temp_curves", &channels.map(|channel| channel as u16))?;
+        if let Some(channel) = channels.iter().find(|channel| **channel > 7) {
+            return Err(anyhow::anyhow!(
+                "Layout section [temp_curves] uses channel {}, DGUS has channels 0-7",
+                channel
+            ));
+        }
+
+        let mut addresses = vec![
+            ("buttons", self.buttons.address),
+            ("movement_buttons", self.movement_buttons.address),
+            ("update_buttons", self.update_buttons.address),
+            ("preheat_buttons", self.preheat_buttons.address),
+            ("extruder_buttons", self.extruder_buttons.address),
+            ("tune_buttons", self.tune_buttons.address),
+            ("exclude_buttons", self.exclude_buttons.address),
+            ("summary_buttons", self.summary_buttons.address),
+            ("confirm_buttons", self.confirm_buttons.address),
+            ("bed_mesh_buttons", self.bed_mesh_buttons.address),
+            ("calibrate_buttons", self.calibrate_buttons.address),
+            ("sensor_buttons", self.sensor_buttons.address),
+            ("output_buttons", self.output_buttons.address),
+            ("power_buttons", self.power_buttons.address),
+        ];
+        addresses.extend(self.inputs.addresses());
+        check_unique_addresses(&addresses)?;
+
+        Ok(())
+    }
+}
+
+fn check_unique_addresses(sections: &[(&str, u16)]) -> Result<()> {
+    for (idx, (section, address)) in sections.iter().enumerate() {
+        if let Some((other, _)) = sections[idx + 1..].iter().find(|(_, a)| a == address) {
+            return Err(anyhow::anyhow!(
+                "Layout sections [{}] and [{}] use the same address {:#X}",
+                section,
+                other,
+                address
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn check_unique_codes(section: &str, codes: &[u16]) -> Result<()> {
+    let mut seen = HashSet::new();
+    for code in codes {
+        if !seen.insert(code) {
+            return Err(anyhow::anyhow!(
+                "Layout section [{}] uses key code {} more than once",
+                section,
+                code
+            ));
+        }
+    }
+
+    Ok(())
+}
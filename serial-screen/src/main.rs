@@ -1,62 +1,162 @@
 use anyhow::Result;
-use buttons::{parse_button_click, parse_movement_button, Button, MovementButton};
+use bed_mesh::{parse_bed_mesh_button, BedMeshButton};
+use buttons::{parse_button_click, Button};
+use calibrate::{parse_calibrate_button, CalibrateButton};
+use clap::Parser;
+use cli::{Cli, Command};
+use config::{Config, ConfigRef};
+use confirm::{parse_confirm_button, ConfirmButton};
+use exclude::{parse_exclude_button, ExcludeButton};
+use extruder::{parse_extruder_button, ExtruderButton};
+use layout::{Layout, DEFAULT_LAYOUT_PATH};
 use moonraker::{MoonrakerRx, MoonrakerTx};
+use moonraker_api::ConnectionState;
+use movement::{parse_movement_button, MovementButton};
+use outputs::{parse_output_button, OutputButton};
+use power::{parse_power_button, PowerButton};
+use preheat::{parse_input, parse_preheat_button, Input, PreheatButton};
 use rppal::uart::Uart;
-use screen_state::ScreenState;
+use screen_state::{ScreenState, UpdateTaskContext};
+use sensors::{parse_sensor_button, SensorButton};
 use serial_utils::construct_change_page;
-use std::sync::Arc;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use summary::{parse_summary_button, SummaryButton};
 use tokio::{
-    sync::{Mutex, RwLock},
+    sync::{watch, Mutex, RwLock},
     time::Instant,
 };
+use tune::{parse_tune_button, TuneButton};
+use update_manager::{parse_update_button, UpdateButton};
 use updater::check_for_updates;
-use utils::subscribe_websocket_events;
 
+mod bed_mesh;
 mod buttons;
+mod calibrate;
+mod cli;
+mod config;
+mod confirm;
+mod eta;
+mod exclude;
+mod extruder;
+mod layout;
 mod moonraker;
+mod movement;
+mod outputs;
+mod power;
+mod preheat;
+mod progress;
 mod screen_state;
+mod sensors;
 mod serial_utils;
+mod status_line;
 mod structs;
+mod summary;
+mod system;
+mod temp_graph;
+mod tune;
+mod update_manager;
 mod updater;
 mod utils;
 mod version;
 
-const RETRY_TIMEOUT: u64 = 5000;
-const BOOT_TIMEOUT: u128 = 1000;
-const TIMEOUT_THRESHOLD: u128 = 2000;
-
 #[tokio::main]
 async fn main() -> Result<()> {
-    let moonraker_api_url = std::env::args()
-        .nth(1)
-        .unwrap_or_else(|| "localhost:7125".to_string());
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            // args a new release rejects are a failed start too (--help and --version aren't)
+            if e.use_stderr() {
+                updater::count_start_attempt()?;
+            }
+            e.exit();
+        }
+    };
+    let command = cli.command.unwrap_or(Command::Run);
+    if matches!(command, Command::Run) {
+        updater::check_pending_update()?;
+    }
 
-    check_for_updates().await;
+    let config_path = cli.config.or_else(config::find_config_file);
+    let mut config = match &config_path {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    config.override_url(&cli.url)?;
 
-    let screen_state = Arc::new(RwLock::new(ScreenState::new()));
+    match command {
+        Command::Run => run(config, config_path, cli.url).await,
+        Command::CheckConfig => {
+            load_layout(&config)?;
 
-    let moonraker = moonraker_api::connect(&moonraker_api_url).await?;
-    let moonraker_tx = Arc::new(Mutex::new(moonraker.0));
-    let moonraker_rx = Arc::new(Mutex::new(moonraker.1));
-    subscribe_websocket_events(moonraker_tx.clone()).await?;
+            match &config_path {
+                Some(path) => println!("# {} is valid", path.display()),
+                None => println!("# No config file found, using defaults"),
+            }
+            println!("{}", toml::to_string_pretty(&config)?);
+            Ok(())
+        }
+        Command::DefaultConfig => {
+            println!("{}", toml::to_string_pretty(&Config::default())?);
+            Ok(())
+        }
+        Command::DefaultLayout => {
+            println!("{}", toml::to_string_pretty(&Layout::default())?);
+            Ok(())
+        }
+        Command::Rollback => updater::rollback(),
+    }
+}
 
-    _ = moonraker_tx
-        .lock()
-        .await
-        .send(moonraker_api::MoonrakerMsg::new_with_method_and_id(
-            moonraker_api::MoonrakerMethod::PrinterObjectsList,
-        ));
+fn load_layout(config: &Config) -> Result<Layout> {
+    match &config.layout {
+        Some(path) => Layout::load(path),
+        None => Layout::load_or_default(Path::new(DEFAULT_LAYOUT_PATH)),
+    }
+}
+
+async fn run(config: Config, config_path: Option<PathBuf>, url: Option<String>) -> Result<()> {
+    let layout = Arc::new(load_layout(&config)?);
+    let moonraker_api_url = config.moonraker.url.clone();
+
+    let moonraker = moonraker_api::connect_with_options(
+        &moonraker_api_url,
+        moonraker_api::ConnectOptions {
+            api_key: config.moonraker.api_key.clone(),
+            proc_stats: config.moonraker.proc_stats,
+        },
+    )
+    .await?;
+
+    let config = Arc::new(RwLock::new(config));
+    if let Some(config_path) = config_path {
+        config::spawn_reload_task(config_path, config.clone(), url);
+    }
+
+    check_for_updates(config.clone()).await;
+
+    let screen_state = Arc::new(RwLock::new(ScreenState::new()));
+
+    // printer objects are subscribed once klippy reports ready
+    let moonraker_tx = Arc::new(Mutex::new(moonraker.tx));
+    let moonraker_rx = Arc::new(Mutex::new(moonraker.rx));
 
     loop {
         let res = connect_to_serial(
             screen_state.clone(),
             moonraker_tx.clone(),
             moonraker_rx.clone(),
+            moonraker.state.clone(),
             moonraker_api_url.clone(),
+            layout.clone(),
+            config.clone(),
         )
         .await;
         if res.is_err() {
-            tokio::time::sleep(tokio::time::Duration::from_millis(RETRY_TIMEOUT)).await;
+            let retry_timeout = config.read().await.timeouts.retry_ms;
+            tokio::time::sleep(tokio::time::Duration::from_millis(retry_timeout)).await;
         }
     }
 }
@@ -65,32 +165,48 @@ async fn connect_to_serial(
     screen_state: Arc<RwLock<ScreenState>>,
     moonraker_tx: MoonrakerTx,
     moonraker_rx: MoonrakerRx,
+    connection_state: watch::Receiver<ConnectionState>,
     moonraker_api_url: String,
+    layout: Arc<Layout>,
+    config: ConfigRef,
 ) -> Result<()> {
-    let serial = rppal::uart::Uart::new(115200, rppal::uart::Parity::None, 8, 1);
+    let (serial_config, timeouts) = {
+        let config = config.read().await;
+        (config.serial.clone(), config.timeouts.clone())
+    };
+
+    let serial = match &serial_config.device {
+        Some(device) => {
+            Uart::with_path(device, serial_config.baud, rppal::uart::Parity::None, 8, 1)
+        }
+        None => Uart::new(serial_config.baud, rppal::uart::Parity::None, 8, 1),
+    };
     if let Err(e) = serial {
         return Err(anyhow::anyhow!("Serial connection error: {}", e));
     }
 
     let mut last_alive = Instant::now();
     let mut serial = serial?;
-    check_boot_state(&mut serial).await?;
+    check_boot_state(&mut serial, &layout, timeouts.boot_ms).await?;
 
     let (serial_tx, mut serial_rx) = tokio::sync::mpsc::unbounded_channel::<Vec<u8>>();
     let serial_tx = Arc::new(Mutex::new(serial_tx));
 
-    let screen_update_task = screen_state::spawn_update_task(
-        moonraker_tx.clone(),
-        moonraker_rx.clone(),
-        screen_state.clone(),
-        serial_tx.clone(),
+    let screen_update_task = screen_state::spawn_update_task(UpdateTaskContext {
+        moonraker_tx: moonraker_tx.clone(),
+        moonraker_rx: moonraker_rx.clone(),
+        screen_state: screen_state.clone(),
+        serial_tx: serial_tx.clone(),
         moonraker_api_url,
-    )
+        connection_state,
+        layout: layout.clone(),
+        config: config.clone(),
+    })
     .await?;
 
     let mut buffer = vec![0; 1024];
     loop {
-        if last_alive.elapsed().as_millis() > TIMEOUT_THRESHOLD {
+        if last_alive.elapsed().as_millis() > timeouts.alive_ms as u128 {
             println!("Connection to screen lost.");
             screen_update_task.abort();
 
@@ -111,31 +227,180 @@ async fn connect_to_serial(
                 match address {
                     0x14 => {
                         let page_number = u16::from_be_bytes([buffer[7], buffer[8]]);
-                        if page_number == 0 {
-                            serial.write(&construct_change_page(1))?;
+                        if page_number == layout.pages.boot {
+                            serial.write(&construct_change_page(layout.pages.main))?;
+                        }
+                    }
+                    address if address == layout.buttons.address => {
+                        let btn = u16::from_be_bytes([buffer[7], buffer[8]]);
+                        let btn = Button::from_id(btn, &layout.buttons);
+
+                        let res = parse_button_click(
+                            btn,
+                            &moonraker_tx,
+                            &screen_state,
+                            &serial_tx,
+                            &layout,
+                            &config,
+                        )
+                        .await;
+                        if let Err(e) = res {
+                            println!("Error while parsing button click: {}", e);
+                        }
+                    }
+                    address if address == layout.movement_buttons.address => {
+                        let btn = u16::from_be_bytes([buffer[7], buffer[8]]);
+                        let btn = MovementButton::from_id(btn, &layout.movement_buttons);
+
+                        let res =
+                            parse_movement_button(btn, &moonraker_tx, &screen_state, &config).await;
+                        if let Err(e) = res {
+                            println!("Error while parsing button click: {}", e);
+                        }
+                    }
+                    address if address == layout.update_buttons.address => {
+                        let btn = u16::from_be_bytes([buffer[7], buffer[8]]);
+                        let btn = UpdateButton::from_id(btn, &layout.update_buttons);
+
+                        let res = parse_update_button(
+                            btn,
+                            &moonraker_tx,
+                            &screen_state,
+                            &serial_tx,
+                            &layout,
+                        )
+                        .await;
+                        if let Err(e) = res {
+                            println!("Error while parsing button click: {}", e);
+                        }
+                    }
+                    address if address == layout.preheat_buttons.address => {
+                        let btn = u16::from_be_bytes([buffer[7], buffer[8]]);
+                        let btn = PreheatButton::from_id(btn, &layout.preheat_buttons);
+
+                        let res = parse_preheat_button(
+                            btn,
+                            &moonraker_tx,
+                            &screen_state,
+                            &serial_tx,
+                            &layout,
+                        )
+                        .await;
+                        if let Err(e) = res {
+                            println!("Error while parsing button click: {}", e);
+                        }
+                    }
+                    address if address == layout.extruder_buttons.address => {
+                        let btn = u16::from_be_bytes([buffer[7], buffer[8]]);
+                        let btn = ExtruderButton::from_id(btn, &layout.extruder_buttons);
+
+                        let res =
+                            parse_extruder_button(btn, &moonraker_tx, &screen_state, &config).await;
+                        if let Err(e) = res {
+                            println!("Error while parsing button click: {}", e);
                         }
                     }
-                    0x1000 => {
+                    address if address == layout.tune_buttons.address => {
                         let btn = u16::from_be_bytes([buffer[7], buffer[8]]);
-                        let btn = Button::from_id(btn);
+                        let btn = TuneButton::from_id(btn, &layout.tune_buttons);
 
                         let res =
-                            parse_button_click(btn, &moonraker_tx, &screen_state, &serial_tx).await;
+                            parse_tune_button(btn, &moonraker_tx, &screen_state, &config).await;
+                        if let Err(e) = res {
+                            println!("Error while parsing button click: {}", e);
+                        }
+                    }
+                    address if address == layout.exclude_buttons.address => {
+                        let btn = u16::from_be_bytes([buffer[7], buffer[8]]);
+                        let btn = ExcludeButton::from_id(btn, &layout.exclude_buttons);
+
+                        let res = parse_exclude_button(btn, &moonraker_tx, &screen_state).await;
                         if let Err(e) = res {
                             println!("Error while parsing button click: {}", e);
                         }
                     }
-                    0x1001 => {
+                    address if address == layout.summary_buttons.address => {
                         let btn = u16::from_be_bytes([buffer[7], buffer[8]]);
-                        let btn = MovementButton::from_id(btn);
+                        let btn = SummaryButton::from_id(btn, &layout.summary_buttons);
+
+                        let res = parse_summary_button(btn, &moonraker_tx, &screen_state).await;
+                        if let Err(e) = res {
+                            println!("Error while parsing button click: {}", e);
+                        }
+                    }
+                    address if address == layout.confirm_buttons.address => {
+                        let btn = u16::from_be_bytes([buffer[7], buffer[8]]);
+                        let btn = ConfirmButton::from_id(btn, &layout.confirm_buttons);
+
+                        let res = parse_confirm_button(
+                            btn,
+                            &moonraker_tx,
+                            &screen_state,
+                            &serial_tx,
+                            &layout,
+                        )
+                        .await;
+                        if let Err(e) = res {
+                            println!("Error while parsing button click: {}", e);
+                        }
+                    }
+                    address if address == layout.bed_mesh_buttons.address => {
+                        let btn = u16::from_be_bytes([buffer[7], buffer[8]]);
+                        let btn = BedMeshButton::from_id(btn, &layout.bed_mesh_buttons);
+
+                        let res = parse_bed_mesh_button(btn, &moonraker_tx, &screen_state).await;
+                        if let Err(e) = res {
+                            println!("Error while parsing button click: {}", e);
+                        }
+                    }
+                    address if address == layout.calibrate_buttons.address => {
+                        let btn = u16::from_be_bytes([buffer[7], buffer[8]]);
+                        let btn = CalibrateButton::from_id(btn, &layout.calibrate_buttons);
 
                         let res =
-                            parse_movement_button(btn, &moonraker_tx, &screen_state, &serial_tx)
+                            parse_calibrate_button(btn, &moonraker_tx, &screen_state, &config)
                                 .await;
                         if let Err(e) = res {
                             println!("Error while parsing button click: {}", e);
                         }
                     }
+                    address if address == layout.sensor_buttons.address => {
+                        let btn = u16::from_be_bytes([buffer[7], buffer[8]]);
+                        let btn = SensorButton::from_id(btn, &layout.sensor_buttons);
+
+                        let res = parse_sensor_button(btn, &moonraker_tx, &screen_state).await;
+                        if let Err(e) = res {
+                            println!("Error while parsing button click: {}", e);
+                        }
+                    }
+                    address if address == layout.output_buttons.address => {
+                        let btn = u16::from_be_bytes([buffer[7], buffer[8]]);
+                        let btn = OutputButton::from_id(btn, &layout.output_buttons);
+
+                        let res = parse_output_button(btn, &moonraker_tx, &screen_state).await;
+                        if let Err(e) = res {
+                            println!("Error while parsing button click: {}", e);
+                        }
+                    }
+                    address if address == layout.power_buttons.address => {
+                        let btn = u16::from_be_bytes([buffer[7], buffer[8]]);
+                        let btn = PowerButton::from_id(btn, &layout.power_buttons);
+
+                        let res =
+                            parse_power_button(btn, &moonraker_tx, &screen_state, &config).await;
+                        if let Err(e) = res {
+                            println!("Error while parsing button click: {}", e);
+                        }
+                    }
+                    address if layout.inputs.contains(address) => {
+                        let value = u16::from_be_bytes([buffer[7], buffer[8]]);
+                        let input = Input::from_address(address, &layout.inputs);
+
+                        let res = parse_input(input, value, &moonraker_tx, &screen_state).await;
+                        if let Err(e) = res {
+                            println!("Error while parsing input: {}", e);
+                        }
+                    }
                     _ => {
                         if data_length > 2 {
                             let value =
@@ -160,14 +425,14 @@ async fn connect_to_serial(
     //Ok(())
 }
 
-async fn check_boot_state(serial: &mut Uart) -> Result<()> {
-    serial.write(&construct_change_page(1))?;
+async fn check_boot_state(serial: &mut Uart, layout: &Layout, boot_timeout: u64) -> Result<()> {
+    serial.write(&construct_change_page(layout.pages.main))?;
     let now = Instant::now();
 
     let mut buffer = vec![0; 1024];
     loop {
-        if now.elapsed().as_millis() > BOOT_TIMEOUT {
-            _ = serial.write(&construct_change_page(0));
+        if now.elapsed().as_millis() > boot_timeout as u128 {
+            _ = serial.write(&construct_change_page(layout.pages.boot));
             return Err(anyhow::anyhow!("Connection Timeout"));
         }
 
@@ -1,13 +1,26 @@
 use crate::{
+    bed_mesh::BedMesh,
+    calibrate::{self, ManualProbe},
+    exclude::ExcludeObjects,
+    movement, outputs, power, preheat,
     screen_state::ScreenState,
-    serial_utils::construct_change_page,
-    structs::{FileMetadataRoot, PrinterObjectsRoot, PrinterStateRoot},
-    utils::{self, subscribe_websocket_events},
+    sensors, status_line,
+    structs::{
+        FileMetadataResult, FileMetadataRoot, HistoryJobRoot, PrinterInfo, PrinterObjectsRoot,
+        PrinterQueryRoot, PrinterStateRoot, PrinterStatusMapRoot, ServerInfo, TemperatureStore,
+    },
+    summary,
+    system::SystemStats,
+    tune, update_manager,
+    utils::{self, query_configfile_settings, subscribe_websocket_events},
 };
 use anyhow::Result;
-use moonraker_api::{MoonrakerMethod, MoonrakerMsg};
+use moonraker_api::{
+    params::{DatabaseItem, PowerDevices, ProcStats, SystemInfoRoot, UpdateStatus},
+    ConnectionState, MoonrakerMethod, MoonrakerMsg, MoonrakerParam,
+};
 use serde::Deserialize;
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 use tokio::sync::{
     mpsc::{UnboundedReceiver, UnboundedSender},
     Mutex,
@@ -23,15 +36,67 @@ pub enum PrinterState {
     Paused,
     Error,
     Complete,
+    Cancelled,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
-pub enum HomedAxis {
-    None,
+pub enum KlippyState {
+    Ready,
+    Startup,
+    Shutdown,
+    Error,
+    Disconnected,
+}
+
+/// Parsed `toolhead.homed_axes` ("", "x", "xy", "xyz", "z" ...)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HomedAxes {
+    pub x: bool,
+    pub y: bool,
+    pub z: bool,
+}
+
+impl HomedAxes {
+    pub fn all() -> Self {
+        HomedAxes {
+            x: true,
+            y: true,
+            z: true,
+        }
+    }
+
+    pub fn is_homed(&self, axis: Axis) -> bool {
+        match axis {
+            Axis::X => self.x,
+            Axis::Y => self.y,
+            Axis::Z => self.z,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
     X,
     Y,
-    XY,
-    XYZ,
+    Z,
+}
+
+impl Axis {
+    pub fn index(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Axis::X => "X",
+            Axis::Y => "Y",
+            Axis::Z => "Z",
+        }
+    }
 }
 
 impl From<&str> for PrinterState {
@@ -42,20 +107,30 @@ impl From<&str> for PrinterState {
             "paused" => PrinterState::Paused,
             "error" => PrinterState::Error,
             "complete" => PrinterState::Complete,
+            "cancelled" => PrinterState::Cancelled,
             _ => PrinterState::Standby,
         }
     }
 }
 
-impl From<&str> for HomedAxis {
+impl From<&str> for KlippyState {
     fn from(s: &str) -> Self {
         match s {
-            "" => HomedAxis::None,
-            "x" => HomedAxis::X,
-            "y" => HomedAxis::Y,
-            "xy" => HomedAxis::XY,
-            "xyz" => HomedAxis::XYZ,
-            _ => HomedAxis::None,
+            "ready" => KlippyState::Ready,
+            "startup" => KlippyState::Startup,
+            "shutdown" => KlippyState::Shutdown,
+            "error" => KlippyState::Error,
+            _ => KlippyState::Disconnected,
+        }
+    }
+}
+
+impl From<&str> for HomedAxes {
+    fn from(s: &str) -> Self {
+        HomedAxes {
+            x: s.contains('x'),
+            y: s.contains('y'),
+            z: s.contains('z'),
         }
     }
 }
@@ -64,7 +139,6 @@ pub async fn recieve_moonraker_updates(
     screen_state: &mut ScreenState,
     moonraker_tx: &MoonrakerTx,
     moonraker_rx: &MoonrakerRx,
-    serial_tx: &Arc<Mutex<UnboundedSender<Vec<u8>>>>,
     client: &reqwest::Client,
     moonraker_api_url: String,
 ) -> Result<()> {
@@ -75,11 +149,30 @@ pub async fn recieve_moonraker_updates(
             params,
         } = msg.clone()
         {
+            if let MoonrakerParam::NotifyGcodeResponse(lines) = &params {
+                for line in lines {
+                    calibrate::gcode_response(screen_state, line);
+                }
+            }
+
+            // notifications only have changed fields, every update() below keeps the rest
             if let moonraker_api::MoonrakerParam::NotifyStatusUpdate(data, _) = params {
+                sensors::update(&mut screen_state.sensors, &data);
+                outputs::update(&mut screen_state.outputs, &data);
+
                 if let Some(display_status) = data.get("display_status") {
+                    if let Some(message) = display_status.get("message") {
+                        // null when cleared with M117 without text
+                        status_line::set_display_message(
+                            screen_state,
+                            message.as_str().unwrap_or(""),
+                        );
+                    }
+
                     if let Some(progress) = display_status.get("progress") {
+                        screen_state.file_progress = progress.as_f64().unwrap_or(0.0);
                         screen_state.printing_progress =
-                            (progress.as_f64().unwrap_or(0.0) * 100.0).round() as i16;
+                            (screen_state.file_progress * 100.0).round() as i16;
                     }
                 }
 
@@ -93,6 +186,10 @@ pub async fn recieve_moonraker_updates(
                         screen_state.target_nozzle_temp =
                             target.as_f64().unwrap_or(0.0).round() as i16;
                     }
+
+                    if let Some(can_extrude) = extruder.get("can_extrude") {
+                        screen_state.can_extrude = can_extrude.as_bool().unwrap_or(false);
+                    }
                 }
 
                 if let Some(heater_bed) = data.get("heater_bed") {
@@ -116,19 +213,78 @@ pub async fn recieve_moonraker_updates(
                             .unwrap_or("");
 
                         screen_state.model_name = utils::center_pad(model_name, " ", 20);
+                        screen_state.print_filename = filename.as_str().unwrap_or("").to_string();
 
-                        screen_state.file_estimated_time = get_file_estimated_time(
+                        let file_metadata = get_file_metadata(
                             client,
                             filename.as_str().unwrap_or(""),
                             &moonraker_api_url,
                         )
-                        .await
-                        .unwrap_or(Some(-1))
-                        .unwrap_or(-1);
+                        .await;
+                        set_file_metadata(screen_state, file_metadata);
+                    }
+
+                    if let Some(print_duration) = print_stats.get("print_duration") {
+                        screen_state.print_duration = print_duration.as_f64().unwrap_or(0.0);
+                    }
+
+                    if let Some(total_duration) = print_stats.get("total_duration") {
+                        screen_state.total_duration = total_duration.as_f64().unwrap_or(0.0);
+                    }
+
+                    if let Some(filament_used) = print_stats.get("filament_used") {
+                        screen_state.filament_used = filament_used.as_f64().unwrap_or(0.0);
+                    }
+
+                    if let Some(info) = print_stats.get("info") {
+                        if let Some(current_layer) = info.get("current_layer") {
+                            screen_state.current_layer = current_layer.as_i64();
+                        }
+                        if let Some(total_layer) = info.get("total_layer") {
+                            screen_state.total_layer = total_layer.as_i64();
+                        }
+                    }
+
+                    if let Some(message) = print_stats.get("message") {
+                        screen_state.print_message = message.as_str().unwrap_or("").to_string();
                     }
 
+                    // last, the summary is made from the other print_stats fields
                     if let Some(state) = print_stats.get("state") {
+                        let previous_state = screen_state.printer_state;
                         screen_state.printer_state = state.as_str().unwrap_or("").into();
+
+                        if summary::is_finished(previous_state, screen_state.printer_state) {
+                            // history writes job_id into the metadata when the job starts
+                            let job_id = get_file_metadata(
+                                client,
+                                &screen_state.print_filename,
+                                &moonraker_api_url,
+                            )
+                            .await
+                            .map(|metadata| metadata.job_id)
+                            .filter(|job_id| !job_id.is_empty());
+
+                            summary::job_finished(
+                                screen_state,
+                                &*moonraker_tx.lock().await,
+                                job_id,
+                            );
+                        }
+                    }
+                }
+
+                if let Some(webhooks) = data.get("webhooks") {
+                    if let Some(state) = webhooks.get("state") {
+                        let klippy_state = state.as_str().unwrap_or("").into();
+                        set_klippy_state(screen_state, moonraker_tx, klippy_state).await;
+                    }
+
+                    if let Some(state_message) = webhooks.get("state_message") {
+                        if screen_state.klippy_state != KlippyState::Ready {
+                            screen_state.klippy_message =
+                                state_message.as_str().unwrap_or("").trim().to_string();
+                        }
                     }
                 }
 
@@ -136,6 +292,62 @@ pub async fn recieve_moonraker_updates(
                     if let Some(homed_axes) = toolhead.get("homed_axes") {
                         screen_state.homed_axes = homed_axes.as_str().unwrap_or("").into();
                     }
+
+                    if let Some(position) = toolhead.get("position").and_then(xyz) {
+                        screen_state.toolhead_position = position;
+                    }
+                    if let Some(axis_minimum) = toolhead.get("axis_minimum").and_then(xyz) {
+                        screen_state.axis_minimum = axis_minimum;
+                    }
+                    if let Some(axis_maximum) = toolhead.get("axis_maximum").and_then(xyz) {
+                        screen_state.axis_maximum = axis_maximum;
+                    }
+                    if let Some(extruder) = toolhead.get("extruder") {
+                        screen_state.active_extruder = extruder.as_str().unwrap_or("").to_string();
+                    }
+                }
+
+                if let Some(idle_timeout) = data.get("idle_timeout") {
+                    if let Some(state) = idle_timeout.get("state") {
+                        movement::update_busy(screen_state, state.as_str().unwrap_or(""));
+                    }
+                }
+
+                if let Some(gcode_move) = data.get("gcode_move") {
+                    if let Some(gcode_position) = gcode_move.get("gcode_position").and_then(xyz) {
+                        screen_state.gcode_position = gcode_position;
+                    }
+
+                    if let Some(speed_factor) = gcode_move.get("speed_factor") {
+                        screen_state.speed_factor =
+                            tune::factor_percent(speed_factor.as_f64().unwrap_or(1.0));
+                    }
+                    if let Some(extrude_factor) = gcode_move.get("extrude_factor") {
+                        screen_state.extrude_factor =
+                            tune::factor_percent(extrude_factor.as_f64().unwrap_or(1.0));
+                    }
+                    if let Some(homing_origin) = gcode_move.get("homing_origin").and_then(xyz) {
+                        screen_state.z_offset = homing_origin[2];
+                    }
+                }
+
+                if let Some(exclude_object) = data.get("exclude_object") {
+                    screen_state.exclude_objects.update(exclude_object);
+                }
+
+                if let Some(bed_mesh) = data.get("bed_mesh") {
+                    screen_state.bed_mesh.update(bed_mesh);
+                }
+
+                if let Some(manual_probe) = data.get("manual_probe") {
+                    screen_state.manual_probe.update(manual_probe);
+                }
+
+                if let Some(fan) = data.get("fan") {
+                    if let Some(speed) = fan.get("speed") {
+                        screen_state.fan_speed =
+                            (speed.as_f64().unwrap_or(0.0) * 100.0).round() as i16;
+                    }
                 }
             }
         }
@@ -153,16 +365,63 @@ pub async fn recieve_moonraker_updates(
 
             match method? {
                 MoonrakerMethod::PrinterObjectsSubscribe => {
+                    let status: PrinterStatusMapRoot = serde_json::from_value(result.clone())
+                        .map_err(|e| anyhow::anyhow!("SERDE Error: {}", e))?;
+                    sensors::update(&mut screen_state.sensors, &status.status);
+                    outputs::update(&mut screen_state.outputs, &status.status);
+
                     let result: PrinterStateRoot = serde_json::from_value(result)
                         .map_err(|e| anyhow::anyhow!("SERDE Error: {}", e))?;
 
+                    screen_state.file_progress = result.status.display_status.progress;
+                    status_line::set_display_message(
+                        screen_state,
+                        result.status.display_status.message.as_str().unwrap_or(""),
+                    );
                     screen_state.printing_progress =
-                        (result.status.display_status.progress * 100.0).round() as i16;
+                        (screen_state.file_progress * 100.0).round() as i16;
                     screen_state.printer_state = result.status.print_stats.state.as_str().into();
+                    screen_state.print_message = result.status.print_stats.message;
                     screen_state.homed_axes = result.status.toolhead.homed_axes.as_str().into();
+                    screen_state.active_extruder = result.status.toolhead.extruder.clone();
+                    if let Some(position) = xyz_from_slice(&result.status.toolhead.position) {
+                        screen_state.toolhead_position = position;
+                    }
+                    if let Some(axis_minimum) = xyz_from_slice(&result.status.toolhead.axis_minimum)
+                    {
+                        screen_state.axis_minimum = axis_minimum;
+                    }
+                    if let Some(axis_maximum) = xyz_from_slice(&result.status.toolhead.axis_maximum)
+                    {
+                        screen_state.axis_maximum = axis_maximum;
+                    }
+                    movement::update_busy(screen_state, &result.status.idle_timeout.state);
+                    if let Some(gcode_position) =
+                        xyz_from_slice(&result.status.gcode_move.gcode_position)
+                    {
+                        screen_state.gcode_position = gcode_position;
+                    }
+                    let gcode_move = &result.status.gcode_move;
+                    screen_state.speed_factor = tune::factor_percent(gcode_move.speed_factor);
+                    screen_state.extrude_factor = tune::factor_percent(gcode_move.extrude_factor);
+                    if let Some(homing_origin) = xyz_from_slice(&gcode_move.homing_origin) {
+                        screen_state.z_offset = homing_origin[2];
+                    }
+                    screen_state.fan_speed = (result.status.fan.speed * 100.0).round() as i16;
+                    screen_state.exclude_objects = ExcludeObjects::default();
+                    screen_state
+                        .exclude_objects
+                        .update(&result.status.exclude_object);
+                    screen_state.bed_mesh = BedMesh::default();
+                    screen_state.bed_mesh.update(&result.status.bed_mesh);
+                    screen_state.manual_probe = ManualProbe::default();
+                    screen_state
+                        .manual_probe
+                        .update(&result.status.manual_probe);
 
                     screen_state.nozzle_temp = result.status.extruder.temperature.round() as i16;
                     screen_state.target_nozzle_temp = result.status.extruder.target.round() as i16;
+                    screen_state.can_extrude = result.status.extruder.can_extrude;
 
                     screen_state.bed_temp = result.status.heater_bed.temperature.round() as i16;
                     screen_state.target_bed_temp = result.status.heater_bed.target.round() as i16;
@@ -175,20 +434,159 @@ pub async fn recieve_moonraker_updates(
                         .next()
                         .unwrap_or("");
                     screen_state.model_name = utils::center_pad(model_name, " ", 20);
+                    screen_state.print_filename = result.status.print_stats.filename.clone();
 
-                    screen_state.file_estimated_time = get_file_estimated_time(
+                    let file_metadata = get_file_metadata(
                         client,
                         &result.status.print_stats.filename,
                         &moonraker_api_url,
                     )
-                    .await
-                    .unwrap_or(Some(-1))
-                    .unwrap_or(-1);
+                    .await;
+                    set_file_metadata(screen_state, file_metadata);
+
+                    screen_state.print_duration = result.status.print_stats.print_duration;
+                    screen_state.total_duration = result.status.print_stats.total_duration;
+                    screen_state.filament_used = result.status.print_stats.filament_used;
+                    screen_state.current_layer = result.status.print_stats.info.current_layer;
+                    screen_state.total_layer = result.status.print_stats.info.total_layer;
+                }
+                MoonrakerMethod::PrinterObjectsQuery => {
+                    let result: PrinterQueryRoot = serde_json::from_value(result)
+                        .map_err(|e| anyhow::anyhow!("SERDE Error: {}", e))?;
+
+                    if let Some(settings) = result
+                        .status
+                        .get("configfile")
+                        .and_then(|configfile| configfile.get("settings"))
+                    {
+                        screen_state.heater_max_temps = preheat::heater_max_temps(settings);
+                        screen_state.pwm_pins = outputs::pwm_pins(settings);
+
+                        // objects list may have been first, pins were discovered as digital then
+                        let objects = screen_state
+                            .outputs
+                            .iter()
+                            .map(|output| output.object.clone())
+                            .collect::<Vec<_>>();
+                        screen_state.outputs = outputs::discover(
+                            &objects,
+                            &screen_state.outputs,
+                            &screen_state.pwm_pins,
+                        );
+                    }
+                }
+                MoonrakerMethod::DatabaseGetItem => {
+                    let result: DatabaseItem = serde_json::from_value(result)
+                        .map_err(|e| anyhow::anyhow!("SERDE Error: {}", e))?;
+
+                    if result.namespace == "mainsail" && result.key == "presets" {
+                        screen_state.mainsail_presets = preheat::mainsail_presets(&result.value);
+                    }
+                }
+                MoonrakerMethod::HistoryGetJob => {
+                    let result: HistoryJobRoot = serde_json::from_value(result)
+                        .map_err(|e| anyhow::anyhow!("SERDE Error: {}", e))?;
+
+                    if let Some(summary) = &mut screen_state.job_summary {
+                        summary.update_from_job(&result.job);
+                    }
+                }
+                MoonrakerMethod::TemperatureStore => {
+                    let result: TemperatureStore = serde_json::from_value(result)
+                        .map_err(|e| anyhow::anyhow!("SERDE Error: {}", e))?;
+
+                    screen_state.temp_graph.set_history(&result);
+                }
+                MoonrakerMethod::PowerDevices => {
+                    let result: PowerDevices = serde_json::from_value(result)
+                        .map_err(|e| anyhow::anyhow!("SERDE Error: {}", e))?;
+
+                    screen_state.power_devices = result.devices;
+                }
+                MoonrakerMethod::PowerGetDevice | MoonrakerMethod::PowerPostDevice => {
+                    let result: HashMap<String, String> = serde_json::from_value(result)
+                        .map_err(|e| anyhow::anyhow!("SERDE Error: {}", e))?;
+
+                    power::update_statuses(&mut screen_state.power_devices, &result);
+                }
+                MoonrakerMethod::MachineSystemInfo => {
+                    let result: SystemInfoRoot = serde_json::from_value(result)
+                        .map_err(|e| anyhow::anyhow!("SERDE Error: {}", e))?;
+
+                    screen_state.system.set_system_info(result.system_info);
+                }
+                MoonrakerMethod::MachineProcStats => {
+                    let result: ProcStats = serde_json::from_value(result)
+                        .map_err(|e| anyhow::anyhow!("SERDE Error: {}", e))?;
+
+                    screen_state.system.set_proc_stats(result);
+                }
+                MoonrakerMethod::ServerInfo => {
+                    let result: ServerInfo = serde_json::from_value(result)
+                        .map_err(|e| anyhow::anyhow!("SERDE Error: {}", e))?;
+
+                    screen_state.system.moonraker_version = result.moonraker_version.clone();
+
+                    let klippy_state = result.klippy_state.as_str().into();
+                    set_klippy_state(screen_state, moonraker_tx, klippy_state).await;
+
+                    if result.klippy_connected && klippy_state != KlippyState::Ready {
+                        // state_message is only in printer.info
+                        _ = moonraker_tx
+                            .lock()
+                            .await
+                            .send(MoonrakerMsg::new_with_method_and_id(
+                                MoonrakerMethod::PrinterInfo,
+                            ));
+                    } else {
+                        screen_state.klippy_message = result.warnings.join(" ");
+                    }
+                }
+                MoonrakerMethod::PrinterInfo => {
+                    let result: PrinterInfo = serde_json::from_value(result)
+                        .map_err(|e| anyhow::anyhow!("SERDE Error: {}", e))?;
+
+                    screen_state.system.klipper_version = result.software_version.clone();
+
+                    let klippy_state = result.state.as_str().into();
+                    set_klippy_state(screen_state, moonraker_tx, klippy_state).await;
+                    // also requested when ready (for the version), keep warnings of server.info
+                    if klippy_state != KlippyState::Ready {
+                        screen_state.klippy_message = result.state_message.trim().to_string();
+                    }
+                }
+                MoonrakerMethod::MachineUpdateStatus | MoonrakerMethod::MachineUpdateRefresh => {
+                    let result: UpdateStatus = serde_json::from_value(result)
+                        .map_err(|e| anyhow::anyhow!("SERDE Error: {}", e))?;
+
+                    screen_state.updates = update_manager::entries_from_status(result);
+                    screen_state.updates_scroll = 0;
+                    if screen_state.update_message == "Checking for updates..." {
+                        screen_state.update_message = String::new();
+                    }
                 }
                 MoonrakerMethod::PrinterObjectsList => {
                     let result: PrinterObjectsRoot = serde_json::from_value(result)
                         .map_err(|e| anyhow::anyhow!("SERDE Error: {}", e))?;
 
+                    screen_state.available_commands = movement::available_commands(&result.objects);
+                    screen_state.has_probe = result.objects.iter().any(|o| o == "probe");
+                    screen_state.has_screws_tilt =
+                        result.objects.iter().any(|o| o == "screws_tilt_adjust");
+
+                    screen_state.sensors =
+                        sensors::discover(&result.objects, &screen_state.sensors);
+                    screen_state.outputs = outputs::discover(
+                        &result.objects,
+                        &screen_state.outputs,
+                        &screen_state.pwm_pins,
+                    );
+
+                    let mut discovered = sensors::subscription(&screen_state.sensors);
+                    discovered.extend(outputs::subscription(&screen_state.outputs));
+                    // a new subscription replaces the one made before the objects were known
+                    _ = subscribe_websocket_events(moonraker_tx.clone(), &discovered).await;
+
                     let macros = result
                         .objects
                         .into_iter()
@@ -202,23 +600,61 @@ pub async fn recieve_moonraker_updates(
             }
         }
 
+        if let MoonrakerMsg::MsgMethodParamVec {
+            jsonrpc: _,
+            method: _,
+            params,
+        } = msg.clone()
+        {
+            for param in params {
+                match param {
+                    MoonrakerParam::NotifyUpdateResponse(response) => {
+                        if let Some(line) = response.message.lines().last() {
+                            screen_state.update_message = line.to_string();
+                        }
+
+                        if response.complete {
+                            _ = moonraker_tx.lock().await.send(
+                                MoonrakerMsg::new_with_method_and_id(
+                                    MoonrakerMethod::MachineUpdateStatus,
+                                ),
+                            );
+                        }
+                    }
+                    MoonrakerParam::NotifyUpdateRefreshed(status) => {
+                        screen_state.updates = update_manager::entries_from_status(status);
+                        screen_state.updates_scroll = 0;
+                    }
+                    MoonrakerParam::NotifyPowerChanged(device) => {
+                        power::device_changed(&mut screen_state.power_devices, device);
+                    }
+                    MoonrakerParam::NotifyProcStatUpdate(stats) => {
+                        screen_state.system.proc_stat_update(stats);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
         if let MoonrakerMsg::MsgMethod { jsonrpc: _, method } = msg {
-            if method == MoonrakerMethod::NotifyKlippyReady {
-                println!("Klippy is ready, subscribing to printer objects.");
-                serial_tx
-                    .lock()
-                    .await
-                    .send(construct_change_page(1))
-                    .unwrap();
-
-                _ = subscribe_websocket_events(moonraker_tx.clone()).await;
-
-                _ = moonraker_tx
-                    .lock()
-                    .await
-                    .send(MoonrakerMsg::new_with_method_and_id(
-                        MoonrakerMethod::PrinterObjectsList,
-                    ));
+            match method {
+                MoonrakerMethod::NotifyKlippyReady => {
+                    set_klippy_state(screen_state, moonraker_tx, KlippyState::Ready).await;
+                }
+                MoonrakerMethod::NotifyKlippyShutdown => {
+                    set_klippy_state(screen_state, moonraker_tx, KlippyState::Shutdown).await;
+                    _ = moonraker_tx
+                        .lock()
+                        .await
+                        .send(MoonrakerMsg::new_with_method_and_id(
+                            MoonrakerMethod::PrinterInfo,
+                        ));
+                }
+                MoonrakerMethod::NotifyKlippyDisconnected => {
+                    set_klippy_state(screen_state, moonraker_tx, KlippyState::Disconnected).await;
+                    screen_state.klippy_message = String::new();
+                }
+                _ => {}
             }
         }
     }
@@ -226,11 +662,119 @@ pub async fn recieve_moonraker_updates(
     Ok(())
 }
 
-async fn get_file_estimated_time(
+/// X, Y, Z of klipper coordinate list (`[x, y, z, e]`)
+fn xyz(value: &serde_json::Value) -> Option<[f64; 3]> {
+    let list = value
+        .as_array()?
+        .iter()
+        .map(|v| v.as_f64())
+        .collect::<Option<Vec<_>>>()?;
+
+    xyz_from_slice(&list)
+}
+
+fn xyz_from_slice(list: &[f64]) -> Option<[f64; 3]> {
+    match list {
+        [x, y, z, ..] => Some([*x, *y, *z]),
+        _ => None,
+    }
+}
+
+/// Tracks websocket connection, klippy state is queried again after every reconnect
+pub async fn update_connection_state(
+    screen_state: &mut ScreenState,
+    moonraker_tx: &MoonrakerTx,
+    connection_state: &ConnectionState,
+) {
+    let connected = *connection_state == ConnectionState::Connected;
+    if let ConnectionState::Disconnected(e) = connection_state {
+        screen_state.connection_error = e.clone();
+    }
+
+    if connected == screen_state.moonraker_connected {
+        return;
+    }
+
+    screen_state.moonraker_connected = connected;
+    if connected {
+        screen_state.connection_error = String::new();
+
+        let moonraker_tx = moonraker_tx.lock().await;
+        _ = moonraker_tx.send(MoonrakerMsg::new_with_method_and_id(
+            MoonrakerMethod::ServerInfo,
+        ));
+        _ = preheat::request_mainsail_presets(&moonraker_tx);
+        // power devices work without klippy, the printer may be the one that's off
+        _ = power::request_devices(&moonraker_tx);
+    } else {
+        screen_state.system = SystemStats::default();
+        set_klippy_state(screen_state, moonraker_tx, KlippyState::Disconnected).await;
+    }
+}
+
+/// Subscribes to printer objects when klippy becomes ready,
+/// clears printer values when it goes away (so they are not stale after reconnect)
+async fn set_klippy_state(
+    screen_state: &mut ScreenState,
+    moonraker_tx: &MoonrakerTx,
+    klippy_state: KlippyState,
+) {
+    if screen_state.klippy_state == klippy_state {
+        return;
+    }
+    screen_state.klippy_state = klippy_state;
+
+    if klippy_state == KlippyState::Ready {
+        println!("Klippy is ready, subscribing to printer objects.");
+        screen_state.klippy_message = String::new();
+
+        _ = subscribe_websocket_events(moonraker_tx.clone(), &[]).await;
+        _ = query_configfile_settings(moonraker_tx.clone()).await;
+        _ = moonraker_tx
+            .lock()
+            .await
+            .send(MoonrakerMsg::new_with_method_and_id(
+                MoonrakerMethod::PrinterObjectsList,
+            ));
+        _ = moonraker_tx
+            .lock()
+            .await
+            .send(MoonrakerMsg::new_with_method_and_id(
+                MoonrakerMethod::TemperatureStore,
+            ));
+        // software_version for the system page
+        _ = moonraker_tx
+            .lock()
+            .await
+            .send(MoonrakerMsg::new_with_method_and_id(
+                MoonrakerMethod::PrinterInfo,
+            ));
+    } else {
+        screen_state.nozzle_temp = 0;
+        screen_state.target_nozzle_temp = 0;
+        screen_state.bed_temp = 0;
+        screen_state.target_bed_temp = 0;
+        // rediscovered when klippy is ready again
+        screen_state.sensors.clear();
+        screen_state.outputs.clear();
+    }
+}
+
+/// Estimated time is -1 when the file has no metadata (or slicer didn't write it)
+fn set_file_metadata(screen_state: &mut ScreenState, file_metadata: Option<FileMetadataResult>) {
+    screen_state.file_estimated_time = file_metadata
+        .as_ref()
+        .map(|metadata| metadata.estimated_time as i32)
+        .filter(|estimated_time| *estimated_time > 0)
+        .unwrap_or(-1);
+    screen_state.file_metadata = file_metadata;
+}
+
+async fn get_file_metadata(
     client: &reqwest::Client,
     filename: &str,
     moonraker_api_url: &str,
-) -> Result<Option<i32>> {
+) -> Option<FileMetadataResult> {
     let file_metadata = client
         .get(format!(
             "http://{}/server/files/metadata?filename={}",
@@ -241,9 +785,9 @@ async fn get_file_estimated_time(
 
     if let Ok(file_metadata) = file_metadata {
         if let Ok(file_metadata) = file_metadata.json::<FileMetadataRoot>().await {
-            return Ok(Some(file_metadata.result.estimated_time as i32));
+            return Some(file_metadata.result);
         }
     }
 
-    Ok(None)
+    None
 }
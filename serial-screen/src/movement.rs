@@ -0,0 +1,274 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use tokio::{sync::RwLock, time::Instant};
+
+use crate::{
+    config::ConfigRef,
+    layout::MovementButtonKeys,
+    moonraker::{Axis, HomedAxes, MoonrakerTx},
+    screen_state::ScreenState,
+};
+
+/// Command is not busy anymore if klipper didn't start executing it in this time
+const BUSY_START_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Long running commands, their bits in `movement_available`/`movement_busy` VPs
+/// are the enum discriminants
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovementCommand {
+    HomeAll = 0,
+    HomeX = 1,
+    HomeY = 2,
+    HomeZ = 3,
+    ZTiltAdjust = 4,
+    QuadGantryLevel = 5,
+    BedMeshCalibrate = 6,
+}
+
+impl MovementCommand {
+    pub fn bit(self) -> i16 {
+        1 << self as i16
+    }
+
+    /// Klipper object that must exist for the command to work
+    fn required_object(self) -> Option<&'static str> {
+        match self {
+            MovementCommand::ZTiltAdjust => Some("z_tilt"),
+            MovementCommand::QuadGantryLevel => Some("quad_gantry_level"),
+            MovementCommand::BedMeshCalibrate => Some("bed_mesh"),
+            _ => None,
+        }
+    }
+
+    /// Leveling needs homed printer, so it's homed first if needed
+    fn script(self, homed_axes: HomedAxes) -> String {
+        let home_first = if homed_axes == HomedAxes::all() {
+            ""
+        } else {
+            "G28\n"
+        };
+
+        match self {
+            MovementCommand::HomeAll => "G28".to_string(),
+            MovementCommand::HomeX => "G28 X".to_string(),
+            MovementCommand::HomeY => "G28 Y".to_string(),
+            MovementCommand::HomeZ => "G28 Z".to_string(),
+            MovementCommand::ZTiltAdjust => format!("{}Z_TILT_ADJUST", home_first),
+            MovementCommand::QuadGantryLevel => format!("{}QUAD_GANTRY_LEVEL", home_first),
+            MovementCommand::BedMeshCalibrate => format!("{}BED_MESH_CALIBRATE", home_first),
+        }
+    }
+
+    fn all() -> [MovementCommand; 7] {
+        [
+            MovementCommand::HomeAll,
+            MovementCommand::HomeX,
+            MovementCommand::HomeY,
+            MovementCommand::HomeZ,
+            MovementCommand::ZTiltAdjust,
+            MovementCommand::QuadGantryLevel,
+            MovementCommand::BedMeshCalibrate,
+        ]
+    }
+}
+
+/// Command sent to klipper, it's done when `idle_timeout.state` leaves "Printing"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovementBusy {
+    pub command: MovementCommand,
+    pub started: bool,
+    pub since: Instant,
+}
+
+/// Bits of commands printer supports, from `printer.objects.list`
+pub fn available_commands(objects: &[String]) -> i16 {
+    MovementCommand::all()
+        .into_iter()
+        .filter(|command| match command.required_object() {
+            Some(object) => objects.iter().any(|o| o == object),
+            None => true,
+        })
+        .fold(0, |bits, command| bits | command.bit())
+}
+
+pub fn update_busy(screen_state: &mut ScreenState, idle_state: &str) {
+    let printing = idle_state == "Printing";
+    screen_state.idle_printing = printing;
+
+    if let Some(busy) = &mut screen_state.movement_busy {
+        if printing {
+            busy.started = true;
+        } else if busy.started {
+            screen_state.movement_busy = None;
+        }
+    }
+}
+
+/// Clears busy state of commands that never started (e.g. klipper refused them)
+pub fn expire_busy(screen_state: &mut ScreenState) {
+    if let Some(busy) = screen_state.movement_busy {
+        if !busy.started && busy.since.elapsed() > BUSY_START_TIMEOUT {
+            screen_state.movement_busy = None;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovementButton {
+    Undefined(u16),
+
+    ZPlus,
+    ZMinus,
+    YPlus,
+    YMinus,
+    XPlus,
+    XMinus,
+    Step(usize),
+    Command(MovementCommand),
+    MotorsOff,
+}
+
+impl MovementButton {
+    pub fn from_id(id: u16, keys: &MovementButtonKeys) -> Self {
+        if let Some(idx) = keys.steps.iter().position(|&code| code == id) {
+            return MovementButton::Step(idx);
+        }
+
+        match id {
+            id if id == keys.y_plus => MovementButton::YPlus,
+            id if id == keys.x_plus => MovementButton::XPlus,
+            id if id == keys.x_minus => MovementButton::XMinus,
+            id if id == keys.y_minus => MovementButton::YMinus,
+            id if id == keys.z_plus => MovementButton::ZPlus,
+            id if id == keys.z_minus => MovementButton::ZMinus,
+            id if id == keys.home => MovementButton::Command(MovementCommand::HomeAll),
+            id if id == keys.home_x => MovementButton::Command(MovementCommand::HomeX),
+            id if id == keys.home_y => MovementButton::Command(MovementCommand::HomeY),
+            id if id == keys.home_z => MovementButton::Command(MovementCommand::HomeZ),
+            id if id == keys.z_tilt_adjust => MovementButton::Command(MovementCommand::ZTiltAdjust),
+            id if id == keys.quad_gantry_level => {
+                MovementButton::Command(MovementCommand::QuadGantryLevel)
+            }
+            id if id == keys.bed_mesh_calibrate => {
+                MovementButton::Command(MovementCommand::BedMeshCalibrate)
+            }
+            id if id == keys.motors_off => MovementButton::MotorsOff,
+            _ => MovementButton::Undefined(id),
+        }
+    }
+}
+
+pub async fn parse_movement_button(
+    button: MovementButton,
+    moonraker_tx: &MoonrakerTx,
+    screen_state: &Arc<RwLock<ScreenState>>,
+    config: &ConfigRef,
+) -> Result<()> {
+    let moonraker_tx = moonraker_tx.lock().await;
+    let mut screen_state = screen_state.write().await;
+    let movement = config.read().await.movement.clone();
+
+    let (axis, direction) = match button {
+        MovementButton::Command(command) => {
+            if screen_state.available_commands & command.bit() == 0 {
+                return Ok(());
+            }
+            if screen_state.movement_busy.is_some() {
+                screen_state.movement_message = "Wait for the current move".to_string();
+                return Ok(());
+            }
+
+            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
+                moonraker_api::MoonrakerMethod::GcodeScript,
+                moonraker_api::MoonrakerParam::GcodeScript {
+                    script: command.script(screen_state.homed_axes),
+                },
+            ))?;
+            screen_state.movement_busy = Some(MovementBusy {
+                command,
+                started: false,
+                since: Instant::now(),
+            });
+            screen_state.movement_message = String::new();
+
+            return Ok(());
+        }
+        MovementButton::MotorsOff => {
+            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
+                moonraker_api::MoonrakerMethod::GcodeScript,
+                moonraker_api::MoonrakerParam::GcodeScript {
+                    script: "M84".to_string(),
+                },
+            ))?;
+            screen_state.movement_message = String::new();
+
+            return Ok(());
+        }
+        MovementButton::Step(idx) => {
+            if let Some(step) = movement.steps.get(idx) {
+                screen_state.jog_step = Some(*step);
+            }
+
+            return Ok(());
+        }
+        MovementButton::XPlus => (Axis::X, 1.0),
+        MovementButton::XMinus => (Axis::X, -1.0),
+        MovementButton::YPlus => (Axis::Y, 1.0),
+        MovementButton::YMinus => (Axis::Y, -1.0),
+        MovementButton::ZPlus => (Axis::Z, 1.0),
+        MovementButton::ZMinus => (Axis::Z, -1.0),
+        MovementButton::Undefined(id) => {
+            println!("Undefined movement button pressed with ID: {}", id);
+            return Ok(());
+        }
+    };
+
+    let step = screen_state.jog_step.unwrap_or(movement.default_step);
+    match jog_distance(&screen_state, axis, direction * step) {
+        Ok(distance) => {
+            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
+                moonraker_api::MoonrakerMethod::GcodeScript,
+                moonraker_api::MoonrakerParam::GcodeScript {
+                    script: jog_script(axis, distance, movement.feedrate.get(axis)),
+                },
+            ))?;
+            screen_state.movement_message = String::new();
+        }
+        Err(e) => {
+            screen_state.movement_message = e.to_string();
+        }
+    }
+
+    Ok(())
+}
+
+/// Refuses to move unhomed axis, clamps move to `axis_minimum`/`axis_maximum`
+fn jog_distance(screen_state: &ScreenState, axis: Axis, distance: f64) -> Result<f64> {
+    if !screen_state.homed_axes.is_homed(axis) {
+        return Err(anyhow::anyhow!("Home {} first", axis.name()));
+    }
+
+    let idx = axis.index();
+    let position = screen_state.toolhead_position[idx];
+    let (min, max) = (
+        screen_state.axis_minimum[idx],
+        screen_state.axis_maximum[idx],
+    );
+
+    let mut target = position + distance;
+    if max > min {
+        target = target.clamp(min, max);
+    }
+
+    let distance = target - position;
+    if distance.abs() < 0.001 {
+        return Err(anyhow::anyhow!("{} is at its limit", axis.name()));
+    }
+
+    Ok(distance)
+}
+
+fn jog_script(axis: Axis, distance: f64, feedrate: u32) -> String {
+    format!("G91\nG1 {}{:+.3} F{}\nG90", axis.name(), distance, feedrate)
+}
@@ -0,0 +1,394 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use moonraker_api::MoonrakerMsg;
+use serde_json::Value;
+use tokio::sync::{mpsc::UnboundedSender, RwLock};
+
+use crate::{
+    layout::OutputButtonKeys, moonraker::MoonrakerTx, screen_state::ScreenState,
+    utils::send_gcode_script,
+};
+
+const LED_PREFIXES: [&str; 4] = ["led ", "neopixel ", "dotstar ", "pca9632 "];
+/// Fans klipper controls by itself (temperature_fan target is set on sensors page)
+const AUTO_FAN_PREFIXES: [&str; 3] = ["heater_fan ", "controller_fan ", "temperature_fan "];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputKind {
+    /// Part cooling fan, M106/M107
+    PartFan,
+    /// SET_FAN_SPEED
+    GenericFan,
+    /// Speed is only shown
+    AutoFan,
+    /// SET_LED
+    Led,
+    /// SET_PIN, `scale` of pwm pins (None - digital pin, only on/off)
+    Pin { pwm_scale: Option<f64> },
+}
+
+/// Fan, LED or output pin, discovered from `printer.objects.list`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Output {
+    /// Klipper object name ("fan_generic exhaust", "neopixel chamber")
+    pub object: String,
+    pub kind: OutputKind,
+    /// 0-1, fan speed or pin value (klipper reports pin values without scale)
+    pub value: f64,
+    /// 0-1 red, green, blue of the first LED in the chain
+    pub color: [f64; 3],
+}
+
+impl Output {
+    fn new(object: &str, kind: OutputKind) -> Self {
+        Output {
+            object: object.to_string(),
+            kind,
+            value: 0.0,
+            color: [0.0; 3],
+        }
+    }
+
+    /// Section name without the type ("exhaust" for "fan_generic exhaust")
+    pub fn name(&self) -> &str {
+        self.object
+            .split_once(' ')
+            .map_or(self.object.as_str(), |(_, name)| name)
+    }
+
+    pub fn settable(&self) -> bool {
+        self.kind != OutputKind::AutoFan
+    }
+
+    pub fn is_on(&self) -> bool {
+        match self.kind {
+            OutputKind::Led => self.color.iter().any(|channel| *channel > 0.0),
+            _ => self.value > 0.0,
+        }
+    }
+
+    /// Reads fan `speed`, pin `value` or the first LED chip's `color_data`, all 0.0-1.0
+    pub fn update(&mut self, status: &HashMap<String, Value>) {
+        if let Some(speed) = status.get("speed") {
+            self.value = speed.as_f64().unwrap_or(0.0);
+        }
+        if let Some(value) = status.get("value") {
+            self.value = value.as_f64().unwrap_or(0.0);
+        }
+        if let Some(color_data) = status.get("color_data") {
+            let color_data: Vec<Vec<f64>> =
+                serde_json::from_value(color_data.clone()).unwrap_or_default();
+            if let Some(color) = color_data.first() {
+                for (idx, channel) in self.color.iter_mut().enumerate() {
+                    *channel = color.get(idx).copied().unwrap_or(0.0);
+                }
+            }
+        }
+    }
+
+    /// Slider position in percent, LEDs show their brightest channel
+    pub fn percent(&self) -> i16 {
+        let value = match self.kind {
+            OutputKind::Led => self.color.iter().copied().fold(0.0, f64::max),
+            _ => self.value,
+        };
+
+        percent(value)
+    }
+
+    /// LED slider positions in percent
+    pub fn color_percent(&self) -> [i16; 3] {
+        self.color.map(percent)
+    }
+
+    /// "exhaust              60%", "lights               ON", "chamber   R100 G80 B0"
+    pub fn line(&self, selected: bool) -> String {
+        let marker = match selected {
+            true => "> ",
+            false => "",
+        };
+        let state = match self.kind {
+            OutputKind::Led => {
+                let [red, green, blue] = self.color_percent();
+                format!("R{} G{} B{}", red, green, blue)
+            }
+            OutputKind::Pin { pwm_scale: None } => match self.is_on() {
+                true => "ON".to_string(),
+                false => "OFF".to_string(),
+            },
+            OutputKind::AutoFan => format!("{}% auto", self.percent()),
+            _ => format!("{}%", self.percent()),
+        };
+
+        format!("{}{: <20} {}", marker, self.name(), state)
+    }
+
+    /// Script that sets the output to `value` (0-1), LEDs get the same value on all channels
+    fn script(&self, value: f64) -> Option<String> {
+        let value = value.clamp(0.0, 1.0);
+
+        match self.kind {
+            OutputKind::PartFan => Some(format!("M106 S{}", (value * 255.0).round())),
+            OutputKind::GenericFan => Some(format!(
+                "SET_FAN_SPEED FAN={} SPEED={:.2}",
+                self.name(),
+                value
+            )),
+            OutputKind::AutoFan => None,
+            OutputKind::Led => Some(led_script(self.name(), [value; 3])),
+            OutputKind::Pin {
+                pwm_scale: Some(scale),
+            } => Some(format!(
+                "SET_PIN PIN={} VALUE={:.2}",
+                self.name(),
+                value * scale
+            )),
+            OutputKind::Pin { pwm_scale: None } => Some(format!(
+                "SET_PIN PIN={} VALUE={}",
+                self.name(),
+                (value > 0.0) as u8
+            )),
+        }
+    }
+}
+
+fn percent(value: f64) -> i16 {
+    (value * 100.0).round().clamp(0.0, 100.0) as i16
+}
+
+fn led_script(led: &str, color: [f64; 3]) -> String {
+    format!(
+        "SET_LED LED={} RED={:.2} GREEN={:.2} BLUE={:.2}",
+        led, color[0], color[1], color[2]
+    )
+}
+
+/// `scale` of every `output_pin` with `pwm: True`, from `configfile.settings` (section names are lowercase)
+pub fn pwm_pins(settings: &Value) -> HashMap<String, f64> {
+    let mut pins = HashMap::new();
+
+    if let Some(settings) = settings.as_object() {
+        for (section, options) in settings {
+            if !section.starts_with("output_pin ") {
+                continue;
+            }
+            if options.get("pwm").and_then(|pwm| pwm.as_bool()) == Some(true) {
+                let scale = options.get("scale").and_then(|s| s.as_f64()).unwrap_or(1.0);
+                pins.insert(section.clone(), scale);
+            }
+        }
+    }
+
+    pins
+}
+
+fn output_kind(object: &str, pwm_pins: &HashMap<String, f64>) -> Option<OutputKind> {
+    match object {
+        "fan" => Some(OutputKind::PartFan),
+        object if object.starts_with("fan_generic ") => Some(OutputKind::GenericFan),
+        object if AUTO_FAN_PREFIXES.iter().any(|p| object.starts_with(p)) => {
+            Some(OutputKind::AutoFan)
+        }
+        object if LED_PREFIXES.iter().any(|p| object.starts_with(p)) => Some(OutputKind::Led),
+        object if object.starts_with("output_pin ") => Some(OutputKind::Pin {
+            pwm_scale: pwm_pins.get(&object.to_lowercase()).copied(),
+        }),
+        _ => None,
+    }
+}
+
+/// Outputs from the object list, values of already known ones are kept.
+/// Settable ones go first, pins are digital until configfile settings say otherwise
+pub fn discover(
+    objects: &[String],
+    known: &[Output],
+    pwm_pins: &HashMap<String, f64>,
+) -> Vec<Output> {
+    let mut outputs = objects
+        .iter()
+        .filter_map(|object| {
+            let kind = output_kind(object, pwm_pins)?;
+            let mut output = known
+                .iter()
+                .find(|output| output.object == *object)
+                .cloned()
+                .unwrap_or_else(|| Output::new(object, kind));
+            output.kind = kind;
+
+            Some(output)
+        })
+        .collect::<Vec<_>>();
+    outputs.sort_by_key(|output| !output.settable());
+
+    outputs
+}
+
+/// Fields to subscribe for every output
+pub fn subscription(outputs: &[Output]) -> Vec<(String, Vec<String>)> {
+    outputs
+        .iter()
+        .map(|output| {
+            let field = match output.kind {
+                OutputKind::Led => "color_data",
+                OutputKind::Pin { .. } => "value",
+                _ => "speed",
+            };
+            (output.object.clone(), vec![field.to_string()])
+        })
+        .collect()
+}
+
+/// Applies subscribe results and status notifications to every discovered output
+pub fn update(outputs: &mut [Output], status: &HashMap<String, HashMap<String, Value>>) {
+    for output in outputs {
+        if let Some(status) = status.get(&output.object) {
+            output.update(status);
+        }
+    }
+}
+
+fn selected(screen_state: &mut ScreenState) -> Option<Output> {
+    let output = screen_state
+        .outputs
+        .iter()
+        .find(|output| Some(&output.object) == screen_state.selected_output.as_ref())
+        .cloned();
+
+    match output {
+        Some(output) if output.settable() => Some(output),
+        Some(output) => {
+            screen_state.output_message = format!("{} is controlled by klipper", output.name());
+            None
+        }
+        None => {
+            screen_state.output_message = "Select an output first".to_string();
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputSlider {
+    /// Fan speed, pin value or LED brightness (all channels)
+    Value,
+    Red,
+    Green,
+    Blue,
+}
+
+/// Slider (or keypad) value in percent for the selected output
+pub fn set_selected_value(
+    moonraker_tx: &UnboundedSender<MoonrakerMsg>,
+    screen_state: &mut ScreenState,
+    slider: OutputSlider,
+    value: u16,
+) -> Result<()> {
+    let output = match selected(screen_state) {
+        Some(output) => output,
+        None => return Ok(()),
+    };
+    let value = value.min(100) as f64 / 100.0;
+
+    let script = match (slider, output.kind) {
+        (OutputSlider::Value, _) => output.script(value),
+        (_, OutputKind::Led) => {
+            let mut color = output.color;
+            let channel = match slider {
+                OutputSlider::Red => 0,
+                OutputSlider::Green => 1,
+                _ => 2,
+            };
+            color[channel] = value;
+            Some(led_script(output.name(), color))
+        }
+        _ => {
+            screen_state.output_message = format!("{} is not a LED", output.name());
+            return Ok(());
+        }
+    };
+
+    if let Some(script) = script {
+        send_gcode_script(moonraker_tx, script)?;
+        screen_state.output_message = String::new();
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputButton {
+    Undefined(u16),
+
+    Up,
+    Down,
+    Line(usize),
+    Toggle,
+}
+
+impl OutputButton {
+    pub fn from_id(id: u16, keys: &OutputButtonKeys) -> Self {
+        if let Some(idx) = keys.lines.iter().position(|&code| code == id) {
+            return OutputButton::Line(idx);
+        }
+
+        match id {
+            id if id == keys.up => OutputButton::Up,
+            id if id == keys.down => OutputButton::Down,
+            id if id == keys.toggle => OutputButton::Toggle,
+            _ => OutputButton::Undefined(id),
+        }
+    }
+}
+
+pub async fn parse_output_button(
+    button: OutputButton,
+    moonraker_tx: &MoonrakerTx,
+    screen_state: &Arc<RwLock<ScreenState>>,
+) -> Result<()> {
+    let moonraker_tx = moonraker_tx.lock().await;
+    let mut screen_state = screen_state.write().await;
+
+    match button {
+        OutputButton::Up => {
+            if screen_state.outputs_scroll > 0 {
+                screen_state.outputs_scroll -= 1;
+            }
+        }
+        OutputButton::Down => {
+            if screen_state.outputs_scroll + 1 < screen_state.outputs.len() {
+                screen_state.outputs_scroll += 1;
+            }
+        }
+        OutputButton::Line(idx) => {
+            let output = screen_state
+                .outputs
+                .get(screen_state.outputs_scroll + idx)
+                .cloned();
+
+            if let Some(output) = output {
+                screen_state.output_message = match output.settable() {
+                    true => String::new(),
+                    false => format!("{} is controlled by klipper", output.name()),
+                };
+                screen_state.selected_output = Some(output.object);
+            }
+        }
+        OutputButton::Toggle => {
+            if let Some(output) = selected(&mut screen_state) {
+                let value = match output.is_on() {
+                    true => 0.0,
+                    false => 1.0,
+                };
+                if let Some(script) = output.script(value) {
+                    send_gcode_script(&moonraker_tx, script)?;
+                }
+            }
+        }
+        OutputButton::Undefined(id) => {
+            println!("Undefined output button pressed with ID: {}", id);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use moonraker_api::{
+    params::{PowerAction, PowerDevice},
+    MoonrakerMethod, MoonrakerMsg, MoonrakerParam,
+};
+use tokio::sync::{mpsc::UnboundedSender, RwLock};
+
+use crate::{
+    config::{ConfigRef, PowerConfig},
+    layout::PowerButtonKeys,
+    moonraker::{MoonrakerTx, PrinterState},
+    screen_state::ScreenState,
+};
+
+/// `printer_power` values for the power on button on the connection page
+pub const PRINTER_POWER_UNKNOWN: i16 = 0;
+pub const PRINTER_POWER_OFF: i16 = 1;
+pub const PRINTER_POWER_ON: i16 = 2;
+
+/// "printer              ON", locked devices are marked as they can't be switched while printing
+pub fn line(device: &PowerDevice) -> String {
+    let lock = match device.locked_while_printing {
+        true => " (locked)",
+        false => "",
+    };
+
+    format!(
+        "{: <20} {}{}",
+        device.device,
+        device.status.to_uppercase(),
+        lock
+    )
+}
+
+/// Applies `{"<device>": "<status>"}` from `get_device`/`post_device`
+pub fn update_statuses(devices: &mut [PowerDevice], statuses: &HashMap<String, String>) {
+    for device in devices {
+        if let Some(status) = statuses.get(&device.device) {
+            device.status = status.clone();
+        }
+    }
+}
+
+/// Applies `notify_power_changed`
+pub fn device_changed(devices: &mut Vec<PowerDevice>, changed: PowerDevice) {
+    match devices.iter_mut().find(|d| d.device == changed.device) {
+        Some(device) => *device = changed,
+        None => devices.push(changed),
+    }
+}
+
+/// State of the configured printer device, called every screen update
+pub fn update_printer_power(screen_state: &mut ScreenState, config: &PowerConfig) {
+    let device = screen_state
+        .power_devices
+        .iter()
+        .find(|device| device.device == config.printer_device);
+
+    screen_state.printer_power = match device.map(|device| device.status.as_str()) {
+        Some("on") => PRINTER_POWER_ON,
+        Some("off") => PRINTER_POWER_OFF,
+        _ => PRINTER_POWER_UNKNOWN,
+    };
+}
+
+pub fn request_devices(moonraker_tx: &UnboundedSender<MoonrakerMsg>) -> Result<()> {
+    moonraker_tx.send(MoonrakerMsg::new_with_method_and_id(
+        MoonrakerMethod::PowerDevices,
+    ))?;
+
+    Ok(())
+}
+
+fn post_device(
+    moonraker_tx: &UnboundedSender<MoonrakerMsg>,
+    device: &str,
+    action: PowerAction,
+) -> Result<()> {
+    moonraker_tx.send(MoonrakerMsg::new_param_id(
+        MoonrakerMethod::PowerPostDevice,
+        MoonrakerParam::PowerDevice {
+            device: device.to_string(),
+            action: Some(action),
+        },
+    ))?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerButton {
+    Undefined(u16),
+
+    Up,
+    Down,
+    Line(usize),
+    PrinterOn,
+}
+
+impl PowerButton {
+    pub fn from_id(id: u16, keys: &PowerButtonKeys) -> Self {
+        if let Some(idx) = keys.lines.iter().position(|&code| code == id) {
+            return PowerButton::Line(idx);
+        }
+
+        match id {
+            id if id == keys.up => PowerButton::Up,
+            id if id == keys.down => PowerButton::Down,
+            id if id == keys.printer_on => PowerButton::PrinterOn,
+            _ => PowerButton::Undefined(id),
+        }
+    }
+}
+
+pub async fn parse_power_button(
+    button: PowerButton,
+    moonraker_tx: &MoonrakerTx,
+    screen_state: &Arc<RwLock<ScreenState>>,
+    config: &ConfigRef,
+) -> Result<()> {
+    let moonraker_tx = moonraker_tx.lock().await;
+    let mut screen_state = screen_state.write().await;
+
+    match button {
+        PowerButton::Up => {
+            if screen_state.power_scroll > 0 {
+                screen_state.power_scroll -= 1;
+            }
+        }
+        PowerButton::Down => {
+            if screen_state.power_scroll + 1 < screen_state.power_devices.len() {
+                screen_state.power_scroll += 1;
+            }
+        }
+        PowerButton::Line(idx) => {
+            let device = screen_state
+                .power_devices
+                .get(screen_state.power_scroll + idx)
+                .cloned();
+
+            if let Some(device) = device {
+                let printing = screen_state.printer_state == PrinterState::Printing
+                    || screen_state.printer_state == PrinterState::Paused;
+                // moonraker refuses it too, this only explains why nothing happens
+                if device.locked_while_printing && printing {
+                    screen_state.power_message =
+                        format!("{} is locked while printing", device.device);
+                    return Ok(());
+                }
+
+                let action = match device.status.as_str() {
+                    "on" => PowerAction::Off,
+                    _ => PowerAction::On,
+                };
+                post_device(&moonraker_tx, &device.device, action)?;
+                screen_state.power_message = String::new();
+            }
+        }
+        PowerButton::PrinterOn => {
+            let printer_device = config.read().await.power.printer_device.clone();
+            if printer_device.is_empty() {
+                return Ok(());
+            }
+
+            post_device(&moonraker_tx, &printer_device, PowerAction::On)?;
+            screen_state.klippy_message = format!("Powering on {}...", printer_device);
+        }
+        PowerButton::Undefined(id) => {
+            println!("Undefined power button pressed with ID: {}", id);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,412 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use moonraker_api::{MoonrakerMethod, MoonrakerMsg, MoonrakerParam};
+use serde_json::Value;
+use tokio::sync::{mpsc::UnboundedSender, Mutex, RwLock};
+
+use crate::{
+    config::{PreheatConfig, PreheatPresetConfig},
+    layout::{InputAddresses, Layout, PreheatButtonKeys},
+    moonraker::{MoonrakerTx, PrinterState},
+    outputs::{self, OutputSlider},
+    screen_state::ScreenState,
+    sensors,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Undefined(u16),
+
+    NozzleTarget,
+    BedTarget,
+    /// Heater selected on sensors page
+    SensorTarget,
+    /// Sliders of the output selected on outputs page
+    Output(OutputSlider),
+}
+
+impl Input {
+    pub fn from_address(address: u16, inputs: &InputAddresses) -> Self {
+        match address {
+            address if address == inputs.nozzle_target => Input::NozzleTarget,
+            address if address == inputs.bed_target => Input::BedTarget,
+            address if address == inputs.sensor_target => Input::SensorTarget,
+            address if address == inputs.output_value => Input::Output(OutputSlider::Value),
+            address if address == inputs.led_red => Input::Output(OutputSlider::Red),
+            address if address == inputs.led_green => Input::Output(OutputSlider::Green),
+            address if address == inputs.led_blue => Input::Output(OutputSlider::Blue),
+            _ => Input::Undefined(address),
+        }
+    }
+}
+
+/// `max_temp` of every section that has one (extruder, heater_bed, heater_generic ...),
+/// from `configfile.settings`
+pub fn heater_max_temps(settings: &Value) -> HashMap<String, i16> {
+    let mut max_temps = HashMap::new();
+
+    if let Some(settings) = settings.as_object() {
+        for (section, options) in settings {
+            if let Some(max_temp) = options.get("max_temp").and_then(|t| t.as_f64()) {
+                max_temps.insert(section.clone(), max_temp.floor() as i16);
+            }
+        }
+    }
+
+    max_temps
+}
+
+pub fn set_heater_script(heater: &str, target: i16) -> String {
+    format!("SET_HEATER_TEMPERATURE HEATER={} TARGET={}", heater, target)
+}
+
+/// Checks target against heater's `max_temp` (if config was already loaded)
+pub fn validate_target(screen_state: &ScreenState, heater: &str, target: i16) -> Result<()> {
+    let max_temp = screen_state.heater_max_temps.get(heater).copied();
+
+    match max_temp {
+        Some(max_temp) if !(0..=max_temp).contains(&target) => {
+            Err(anyhow::anyhow!("{} must be 0-{}", heater, max_temp))
+        }
+        None if target < 0 => Err(anyhow::anyhow!("{} target is invalid", heater)),
+        _ => Ok(()),
+    }
+}
+
+pub async fn parse_input(
+    input: Input,
+    value: u16,
+    moonraker_tx: &MoonrakerTx,
+    screen_state: &Arc<RwLock<ScreenState>>,
+) -> Result<()> {
+    let moonraker_tx = moonraker_tx.lock().await;
+    let mut screen_state = screen_state.write().await;
+
+    // keypad values are unsigned, anything above i16 is out of range anyway
+    let target = i16::try_from(value).unwrap_or(-1);
+
+    let heater = match input {
+        Input::NozzleTarget => "extruder",
+        Input::BedTarget => "heater_bed",
+        Input::SensorTarget => {
+            return sensors::set_selected_target(&moonraker_tx, &mut screen_state, target);
+        }
+        Input::Output(slider) => {
+            return outputs::set_selected_value(&moonraker_tx, &mut screen_state, slider, value);
+        }
+        Input::Undefined(address) => {
+            println!("Undefined input at address: {:#X}", address);
+            return Ok(());
+        }
+    };
+
+    if let Err(e) = validate_target(&screen_state, heater, target) {
+        screen_state.preheat_message = e.to_string();
+        return Ok(());
+    }
+
+    moonraker_tx.send(MoonrakerMsg::new_param_id(
+        MoonrakerMethod::GcodeScript,
+        MoonrakerParam::GcodeScript {
+            script: set_heater_script(heater, target),
+        },
+    ))?;
+    screen_state.preheat_message = format!("{} set to {}", heater, target);
+
+    Ok(())
+}
+
+/// Preset from config or Mainsail, targets are klipper object names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preset {
+    pub name: String,
+    pub targets: Vec<(String, i16)>,
+    /// Part cooling fan speed in percent
+    pub fan: Option<u8>,
+    pub gcode: Option<String>,
+}
+
+impl Preset {
+    fn from_config(preset: &PreheatPresetConfig, chamber_heater: &str) -> Self {
+        let mut targets = vec![
+            ("extruder".to_string(), preset.nozzle),
+            ("heater_bed".to_string(), preset.bed),
+        ];
+        if let Some(chamber) = preset.chamber {
+            targets.push((chamber_heater.to_string(), chamber));
+        }
+
+        Preset {
+            name: preset.name.clone(),
+            targets,
+            fan: preset.fan,
+            gcode: None,
+        }
+    }
+
+    pub fn line(&self) -> String {
+        let temps = self
+            .targets
+            .iter()
+            .map(|(_, target)| target.to_string())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        match self.fan {
+            Some(fan) => format!("{} {} fan {}%", self.name, temps, fan),
+            None => format!("{} {}", self.name, temps),
+        }
+    }
+}
+
+/// Heaters that get preset temperatures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreheatTarget {
+    All,
+    Nozzle,
+    Bed,
+}
+
+impl PreheatTarget {
+    pub fn next(self) -> Self {
+        match self {
+            PreheatTarget::All => PreheatTarget::Nozzle,
+            PreheatTarget::Nozzle => PreheatTarget::Bed,
+            PreheatTarget::Bed => PreheatTarget::All,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PreheatTarget::All => "All heaters",
+            PreheatTarget::Nozzle => "Nozzle only",
+            PreheatTarget::Bed => "Bed only",
+        }
+    }
+
+    fn matches(self, object: &str) -> bool {
+        match self {
+            PreheatTarget::All => true,
+            PreheatTarget::Nozzle => object.starts_with("extruder"),
+            PreheatTarget::Bed => object == "heater_bed",
+        }
+    }
+}
+
+/// Mainsail presets replace the configured ones only if there are any
+pub fn presets(config: &PreheatConfig, mainsail_presets: &[Preset]) -> Vec<Preset> {
+    if config.use_mainsail && !mainsail_presets.is_empty() {
+        return mainsail_presets.to_vec();
+    }
+
+    config
+        .presets
+        .iter()
+        .map(|preset| Preset::from_config(preset, &config.chamber_heater))
+        .collect()
+}
+
+pub fn request_mainsail_presets(moonraker_tx: &UnboundedSender<MoonrakerMsg>) -> Result<()> {
+    moonraker_tx.send(MoonrakerMsg::new_param_id(
+        MoonrakerMethod::DatabaseGetItem,
+        MoonrakerParam::DatabaseItem {
+            namespace: "mainsail".to_string(),
+            key: "presets".to_string(),
+        },
+    ))?;
+
+    Ok(())
+}
+
+/// Parses `presets` item of `mainsail` namespace:
+/// `{"presets": {"<id>": {"name", "gcode", "values": {"<object>": {"bool", "type", "value"}}}}}`
+pub fn mainsail_presets(value: &Value) -> Vec<Preset> {
+    let mut presets = value
+        .get("presets")
+        .and_then(|presets| presets.as_object())
+        .map(|presets| {
+            presets
+                .values()
+                .filter_map(|preset| {
+                    let name = preset.get("name")?.as_str()?.to_string();
+                    let gcode = preset
+                        .get("gcode")
+                        .and_then(|gcode| gcode.as_str())
+                        .filter(|gcode| !gcode.trim().is_empty())
+                        .map(|gcode| gcode.to_string());
+
+                    let mut targets = preset
+                        .get("values")
+                        .and_then(|values| values.as_object())
+                        .map(|values| {
+                            values
+                                .iter()
+                                .filter(|(_, v)| {
+                                    v.get("bool").and_then(|b| b.as_bool()) == Some(true)
+                                })
+                                .filter_map(|(object, v)| {
+                                    let target = v.get("value")?.as_f64()?;
+                                    Some((object.clone(), target.round() as i16))
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                        .unwrap_or_default();
+                    targets.sort_by_key(|(object, _)| target_order(object));
+
+                    Some(Preset {
+                        name,
+                        targets,
+                        fan: None,
+                        gcode,
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    presets.sort_by(|a, b| a.name.cmp(&b.name));
+    presets
+}
+
+/// Nozzle first, then bed, then everything else (same as config presets)
+fn target_order(object: &str) -> (u8, String) {
+    match object {
+        "extruder" => (0, String::new()),
+        "heater_bed" => (1, String::new()),
+        _ => (2, object.to_string()),
+    }
+}
+
+pub fn target_script(object: &str, target: i16) -> String {
+    match object.split_once(' ') {
+        Some(("temperature_fan", name)) => format!(
+            "SET_TEMPERATURE_FAN_TARGET TEMPERATURE_FAN={} TARGET={}",
+            name, target
+        ),
+        Some((_, name)) => set_heater_script(name, target),
+        None => set_heater_script(object, target),
+    }
+}
+
+/// Fan and preset gcode are skipped while printing, only temperatures are retuned then
+pub fn apply_preset(
+    preset: &Preset,
+    target: PreheatTarget,
+    moonraker_tx: &UnboundedSender<MoonrakerMsg>,
+    screen_state: &mut ScreenState,
+) -> Result<()> {
+    let printing = screen_state.printer_state == PrinterState::Printing
+        || screen_state.printer_state == PrinterState::Paused;
+
+    let targets = preset
+        .targets
+        .iter()
+        .filter(|(object, _)| target.matches(object))
+        .collect::<Vec<_>>();
+
+    for (object, temp) in &targets {
+        if let Err(e) = validate_target(screen_state, object, *temp) {
+            screen_state.preheat_message = e.to_string();
+            return Ok(());
+        }
+    }
+
+    let mut script = targets
+        .iter()
+        .map(|(object, temp)| target_script(object, *temp))
+        .collect::<Vec<_>>();
+    if target == PreheatTarget::All && !printing {
+        if let Some(fan) = preset.fan {
+            script.push(format!("M106 S{}", (fan as u16 * 255) / 100));
+        }
+        if let Some(gcode) = &preset.gcode {
+            script.push(gcode.clone());
+        }
+    }
+
+    if script.is_empty() {
+        return Ok(());
+    }
+
+    moonraker_tx.send(MoonrakerMsg::new_param_id(
+        MoonrakerMethod::GcodeScript,
+        MoonrakerParam::GcodeScript {
+            script: script.join("\n"),
+        },
+    ))?;
+    screen_state.preheat_message = format!("{} ({})", preset.name, target.label());
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreheatButton {
+    Undefined(u16),
+
+    Up,
+    Down,
+    Line(usize),
+    Target,
+}
+
+impl PreheatButton {
+    pub fn from_id(id: u16, keys: &PreheatButtonKeys) -> Self {
+        if let Some(idx) = keys.lines.iter().position(|&code| code == id) {
+            return PreheatButton::Line(idx);
+        }
+
+        match id {
+            id if id == keys.up => PreheatButton::Up,
+            id if id == keys.down => PreheatButton::Down,
+            id if id == keys.target => PreheatButton::Target,
+            _ => PreheatButton::Undefined(id),
+        }
+    }
+}
+
+pub async fn parse_preheat_button(
+    button: PreheatButton,
+    moonraker_tx: &MoonrakerTx,
+    screen_state: &Arc<RwLock<ScreenState>>,
+    serial_tx: &Arc<Mutex<UnboundedSender<Vec<u8>>>>,
+    layout: &Layout,
+) -> Result<()> {
+    let serial_tx = serial_tx.lock().await;
+    let moonraker_tx = moonraker_tx.lock().await;
+    let mut screen_state = screen_state.write().await;
+
+    match button {
+        PreheatButton::Up => {
+            if screen_state.presets_scroll > 0 {
+                screen_state.presets_scroll -= 1;
+                screen_state.update_presets_list(&serial_tx, layout).await?;
+            }
+        }
+        PreheatButton::Down => {
+            if screen_state.presets_scroll + 1 < screen_state.presets.len() {
+                screen_state.presets_scroll += 1;
+                screen_state.update_presets_list(&serial_tx, layout).await?;
+            }
+        }
+        PreheatButton::Line(idx) => {
+            let preset = screen_state
+                .presets
+                .get(screen_state.presets_scroll + idx)
+                .cloned();
+
+            if let Some(preset) = preset {
+                let target = screen_state.preheat_target;
+                apply_preset(&preset, target, &moonraker_tx, &mut screen_state)?;
+            }
+        }
+        PreheatButton::Target => {
+            screen_state.preheat_target = screen_state.preheat_target.next();
+        }
+        PreheatButton::Undefined(id) => {
+            println!("Undefined preheat button pressed with ID: {}", id);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use chrono::{Duration, Local};
+
+use crate::{moonraker::PrinterState, screen_state::ScreenState};
+
+/// "1:05:09" or "05:09" for prints shorter than an hour
+pub fn format_duration(secs: f64) -> String {
+    let secs = secs.max(0.0).round() as i64;
+    let (hours, minutes, seconds) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+
+    match hours {
+        0 => format!("{:0>2}:{:0>2}", minutes, seconds),
+        _ => format!("{}:{:0>2}:{:0>2}", hours, minutes, seconds),
+    }
+}
+
+/// Nothing is shown before the first print (print_stats keep last values after it ends)
+fn has_print(screen_state: &ScreenState) -> bool {
+    screen_state.printer_state != PrinterState::Standby
+}
+
+/// Layer from print_stats (SET_PRINT_STATS_INFO), otherwise estimated from Z and slicer layer heights
+fn current_layer(screen_state: &ScreenState) -> Option<i64> {
+    if let Some(layer) = screen_state.current_layer {
+        return Some(layer);
+    }
+
+    let metadata = screen_state.file_metadata.as_ref()?;
+    if metadata.layer_height <= 0.0 || metadata.first_layer_height <= 0.0 {
+        return None;
+    }
+
+    let z = screen_state.gcode_position[2];
+    let layer = ((z - metadata.first_layer_height) / metadata.layer_height).ceil() as i64 + 1;
+    Some(layer.max(1))
+}
+
+fn total_layers(screen_state: &ScreenState) -> Option<i64> {
+    if let Some(layers) = screen_state.total_layer {
+        return Some(layers);
+    }
+
+    let metadata = screen_state.file_metadata.as_ref()?;
+    if metadata.layer_count > 0 {
+        return Some(metadata.layer_count);
+    }
+    if metadata.layer_height <= 0.0 || metadata.first_layer_height <= 0.0 {
+        return None;
+    }
+
+    let layers = (metadata.object_height - metadata.first_layer_height) / metadata.layer_height;
+    Some(layers.ceil() as i64 + 1)
+}
+
+/// "Layer 12/150", or only the current layer if total is unknown
+pub fn layer_text(screen_state: &ScreenState) -> String {
+    if !has_print(screen_state) {
+        return String::new();
+    }
+
+    match (current_layer(screen_state), total_layers(screen_state)) {
+        (Some(layer), Some(total)) => format!("Layer {}/{}", layer.min(total), total),
+        (Some(layer), None) => format!("Layer {}", layer),
+        _ => String::new(),
+    }
+}
+
+pub fn elapsed_text(screen_state: &ScreenState) -> String {
+    if !has_print(screen_state) {
+        return String::new();
+    }
+
+    format!("Elapsed {}", format_duration(screen_state.print_duration))
+}
+
+/// "Filament 1.25/4.80 m", slicer total is optional
+pub fn filament_text(screen_state: &ScreenState) -> String {
+    if !has_print(screen_state) {
+        return String::new();
+    }
+
+    let used = screen_state.filament_used / 1000.0;
+    let total = screen_state
+        .file_metadata
+        .as_ref()
+        .map(|metadata| metadata.filament_total / 1000.0)
+        .filter(|total| *total > 0.0);
+
+    match total {
+        Some(total) => format!("Filament {:.2}/{:.2} m", used, total),
+        None => format!("Filament {:.2} m", used),
+    }
+}
+
+/// "Z 2.40/15.00 mm", object height is optional
+pub fn height_text(screen_state: &ScreenState) -> String {
+    if !has_print(screen_state) {
+        return String::new();
+    }
+
+    let z = screen_state.gcode_position[2];
+    let height = screen_state
+        .file_metadata
+        .as_ref()
+        .map(|metadata| metadata.object_height)
+        .filter(|height| *height > 0.0);
+
+    match height {
+        Some(height) => format!("Z {:.2}/{:.2} mm", z, height),
+        None => format!("Z {:.2} mm", z),
+    }
+}
+
+/// Wall clock time the print ends at, "Done at 14:32"
+pub fn finish_text(screen_state: &ScreenState) -> String {
+    if screen_state.printer_state != PrinterState::Printing
+        && screen_state.printer_state != PrinterState::Paused
+    {
+        return String::new();
+    }
+
+    match screen_state.remaining_secs() {
+        Some(remaining) => {
+            let finish = Local::now() + Duration::seconds(remaining.round() as i64);
+            format!("Done at {}", finish.format("%H:%M"))
+        }
+        None => String::new(),
+    }
+}
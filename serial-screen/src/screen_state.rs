@@ -1,16 +1,54 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use crate::{
-    moonraker::{self, HomedAxis, MoonrakerRx, MoonrakerTx, PrinterState},
-    serial_utils::{construct_change_page, construct_i16, construct_text},
+    bed_mesh::BedMesh,
+    calibrate::{self, CalibrationWizard, ManualProbe},
+    config::{Config, ConfigRef, EtaConfig},
+    confirm::ConfirmState,
+    eta::{EtaEstimator, PrintSample, SlicerTotals},
+    exclude::ExcludeObjects,
+    extruder::{self, FilamentAction, FilamentWizard},
+    layout::Layout,
+    moonraker::{self, HomedAxes, KlippyState, MoonrakerRx, MoonrakerTx, PrinterState},
+    movement::{self, MovementBusy, MovementCommand},
+    outputs::Output,
+    power,
+    preheat::{self, PreheatTarget, Preset},
+    progress,
+    sensors::Sensor,
+    serial_utils::{
+        construct_beep, construct_change_page, construct_curve_data, construct_curve_reset,
+        construct_filled_rectangles, construct_i16, construct_line_segments, construct_text,
+    },
+    status_line,
+    structs::FileMetadataResult,
+    summary::JobSummary,
+    system::{self, SystemStats},
+    temp_graph::{self, TempGraph},
+    tune,
+    update_manager::UpdateEntry,
+    utils,
 };
 use anyhow::Result;
 use chrono::Local;
+use moonraker_api::{params::PowerDevice, ConnectionState, MoonrakerMethod, MoonrakerMsg};
 use tokio::{
-    sync::{mpsc::UnboundedSender, Mutex, MutexGuard, RwLock},
+    sync::{mpsc::UnboundedSender, watch, Mutex, MutexGuard, RwLock},
     task::JoinHandle,
+    time::Instant,
 };
 
+/// How often klippy state is polled while it's not ready
+const KLIPPY_POLL_SECS: u64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusPage {
+    Online,
+    Connection,
+    Error,
+    Summary,
+}
+
 // TODO: maybe use a macro for this?
 //       macro should be like serde renaming etc
 //       so for each field we can specify the address
@@ -20,21 +58,156 @@ use tokio::{
 pub struct ScreenState {
     pub current_page: u8,
     pub printer_state: PrinterState,
-    pub homed_axes: HomedAxis,
+    pub homed_axes: HomedAxes,
+    /// Machine coordinates, used to clamp jog moves
+    pub toolhead_position: [f64; 3],
+    pub axis_minimum: [f64; 3],
+    pub axis_maximum: [f64; 3],
+    pub gcode_position: [f64; 3],
+    /// Jog step selected on movement page, config `default_step` if not selected yet
+    pub jog_step: Option<f64>,
+    pub movement_message: String,
+    /// Bits of `MovementCommand`s printer supports
+    pub available_commands: i16,
+    pub movement_busy: Option<MovementBusy>,
+    /// `idle_timeout.state` is "Printing" (klipper is executing commands)
+    pub idle_printing: bool,
+
+    pub can_extrude: bool,
+    /// Selected on extruder page, config defaults if not selected yet
+    pub extrude_length: Option<f64>,
+    pub extrude_speed: Option<f64>,
+    pub filament_wizard: FilamentWizard,
+    pub extruder_message: String,
+
+    /// `gcode_move` factors and `fan.speed` in percent
+    pub speed_factor: i16,
+    pub extrude_factor: i16,
+    pub fan_speed: i16,
+    /// Z of `gcode_move.homing_origin` (babystepping)
+    pub z_offset: f64,
+    pub has_probe: bool,
+    pub tune_message: String,
+
+    pub exclude_objects: ExcludeObjects,
+    pub objects_scroll: usize,
+    /// Object waiting for confirmation on exclude page
+    pub exclude_pending: Option<String>,
+    pub exclude_message: String,
+
+    pub bed_mesh: BedMesh,
+    pub mesh_scroll: usize,
+    pub mesh_message: String,
+
+    pub manual_probe: ManualProbe,
+    pub calibration_wizard: CalibrationWizard,
+    pub calibration_message: String,
+    pub calibration_lines: Vec<String>,
+    /// TESTZ step in mm
+    pub calibration_z_step: Option<f64>,
+    pub has_screws_tilt: bool,
+
+    /// Discovered heaters and temperature sensors
+    pub sensors: Vec<Sensor>,
+    pub sensors_scroll: usize,
+    /// Sensor object that gets the keypad target on sensors page
+    pub selected_sensor: Option<String>,
+    pub sensor_message: String,
+    /// `toolhead.extruder`
+    pub active_extruder: String,
+
+    /// Discovered fans, LEDs and output pins
+    pub outputs: Vec<Output>,
+    pub outputs_scroll: usize,
+    /// Output object the sliders on outputs page control
+    pub selected_output: Option<String>,
+    pub output_message: String,
+    /// `scale` of pwm output pins from klipper config
+    pub pwm_pins: HashMap<String, f64>,
+
+    /// Moonraker power devices
+    pub power_devices: Vec<PowerDevice>,
+    pub power_scroll: usize,
+    pub power_message: String,
+    /// State of `[power] printer_device`, see `power::PRINTER_POWER_*`
+    pub printer_power: i16,
+
+    /// Host stats for the system page
+    pub system: SystemStats,
+    pub system_cpu: String,
+    pub system_cores: String,
+    pub system_memory: String,
+    pub system_uptime: String,
+    pub network_lines: Vec<String>,
+
+    /// Action waiting for Yes/No on confirm page, or long press button being held
+    pub confirm: ConfirmState,
 
     pub macros: Vec<String>,
     pub macros_scroll: usize,
 
+    pub updates: Vec<UpdateEntry>,
+    pub updates_scroll: usize,
+    pub update_message: String,
+
+    pub moonraker_connected: bool,
+    pub klippy_state: KlippyState,
+    pub klippy_message: String,
+    pub connection_error: String,
+
+    pub print_message: String,
+    pub error_dismissed: bool,
+    /// Last print outcome, shown until acknowledged
+    pub job_summary: Option<JobSummary>,
+
+    /// Section name -> max_temp from klipper config
+    pub heater_max_temps: HashMap<String, i16>,
+    pub preheat_message: String,
+    pub preheat_target: PreheatTarget,
+    pub presets: Vec<Preset>,
+    pub presets_scroll: usize,
+    pub mainsail_presets: Vec<Preset>,
+
     pub time: String,           // 0x2000/5 HH:MM
     pub estimated_time: String, // 0x2005/10 ETA: HH:MM
     pub file_estimated_time: i32,
 
+    /// display_status.message (M117/SET_DISPLAY_TEXT)
+    pub display_message: String,
+    pub display_message_since: Instant,
+    /// Full message of the status line and the visible (scrolled) part of it
+    pub status_message: String,
+    pub status_offset: usize,
+    pub status_line: String,
+    /// Metadata of the printed file, None if it has none (or nothing was printed yet)
+    pub file_metadata: Option<FileMetadataResult>,
+    /// display_status.progress (0.0-1.0), `printing_progress` is rounded for the bar
+    pub file_progress: f64,
+    pub eta: EtaEstimator,
+    /// Blended estimate, None if there is nothing to estimate from
+    pub remaining: Option<f64>,
+
+    /// print_stats, filament in mm
+    pub print_filename: String,
+    pub print_duration: f64,
+    pub total_duration: f64,
+    pub filament_used: f64,
+    pub current_layer: Option<i64>,
+    pub total_layer: Option<i64>,
+    /// Texts computed from print_stats and metadata, only compared with old state
+    pub print_layer: String,
+    pub print_elapsed: String,
+    pub print_filament: String,
+    pub print_height: String,
+    pub print_finish: String,
+
     pub model_name: String, // 0x2015/20 Model Name (centered)
 
     pub nozzle_temp: i16,        // 0x2025/1
     pub target_nozzle_temp: i16, // 0x2026/1
     pub bed_temp: i16,           // 0x2027/1
     pub target_bed_temp: i16,    // 0x2028/1
+    pub temp_graph: TempGraph,
 
     pub printing_progress: i16, // 0x2029/1 (0-100)
 }
@@ -44,19 +217,119 @@ impl ScreenState {
         ScreenState {
             current_page: 0,
             printer_state: PrinterState::Standby,
-            homed_axes: HomedAxis::None,
+            homed_axes: HomedAxes::default(),
+            toolhead_position: [0.0; 3],
+            axis_minimum: [0.0; 3],
+            axis_maximum: [0.0; 3],
+            gcode_position: [0.0; 3],
+            jog_step: None,
+            movement_message: String::new(),
+            available_commands: 0,
+            movement_busy: None,
+            idle_printing: false,
+
+            can_extrude: false,
+            extrude_length: None,
+            extrude_speed: None,
+            filament_wizard: FilamentWizard::Idle,
+            extruder_message: String::new(),
+
+            speed_factor: 100,
+            extrude_factor: 100,
+            fan_speed: 0,
+            z_offset: 0.0,
+            has_probe: false,
+            tune_message: String::new(),
+
+            exclude_objects: ExcludeObjects::default(),
+            objects_scroll: 0,
+            exclude_pending: None,
+            exclude_message: String::new(),
+            bed_mesh: BedMesh::default(),
+            mesh_scroll: 0,
+            mesh_message: String::new(),
+            manual_probe: ManualProbe::default(),
+            calibration_wizard: CalibrationWizard::Idle,
+            calibration_message: String::new(),
+            calibration_lines: Vec::new(),
+            calibration_z_step: None,
+            has_screws_tilt: false,
+            sensors: Vec::new(),
+            sensors_scroll: 0,
+            selected_sensor: None,
+            sensor_message: String::new(),
+            active_extruder: String::new(),
+            outputs: Vec::new(),
+            outputs_scroll: 0,
+            selected_output: None,
+            output_message: String::new(),
+            pwm_pins: HashMap::new(),
+            power_devices: Vec::new(),
+            power_scroll: 0,
+            power_message: String::new(),
+            printer_power: power::PRINTER_POWER_UNKNOWN,
+            system: SystemStats::default(),
+            system_cpu: String::new(),
+            system_cores: String::new(),
+            system_memory: String::new(),
+            system_uptime: String::new(),
+            network_lines: Vec::new(),
+            confirm: ConfirmState::Idle,
 
             macros: Vec::new(),
             macros_scroll: 0,
 
+            updates: Vec::new(),
+            updates_scroll: 0,
+            update_message: String::new(),
+
+            moonraker_connected: false,
+            klippy_state: KlippyState::Disconnected,
+            klippy_message: String::new(),
+            connection_error: String::new(),
+
+            print_message: String::new(),
+            error_dismissed: false,
+            job_summary: None,
+
+            heater_max_temps: HashMap::new(),
+            preheat_message: String::new(),
+            preheat_target: PreheatTarget::All,
+            presets: Vec::new(),
+            presets_scroll: 0,
+            mainsail_presets: Vec::new(),
+
             time: "00:00".to_string(),
             estimated_time: " ".repeat(10),
             file_estimated_time: -1,
+
+            display_message: String::new(),
+            display_message_since: Instant::now(),
+            status_message: String::new(),
+            status_offset: 0,
+            status_line: String::new(),
+            file_metadata: None,
+            file_progress: 0.0,
+            eta: EtaEstimator::default(),
+            remaining: None,
+
+            print_filename: String::new(),
+            print_duration: 0.0,
+            total_duration: 0.0,
+            filament_used: 0.0,
+            current_layer: None,
+            total_layer: None,
+            print_layer: String::new(),
+            print_elapsed: String::new(),
+            print_filament: String::new(),
+            print_height: String::new(),
+            print_finish: String::new(),
             model_name: " ".repeat(20),
             nozzle_temp: 0,
             target_nozzle_temp: 0,
             bed_temp: 0,
             target_bed_temp: 0,
+            temp_graph: TempGraph::default(),
             printing_progress: 0,
         }
     }
@@ -68,19 +341,133 @@ impl ScreenState {
         ScreenState {
             current_page: 0,
             printer_state: PrinterState::Paused,
-            homed_axes: HomedAxis::XYZ,
+            homed_axes: HomedAxes::all(),
+            toolhead_position: [0.0; 3],
+            axis_minimum: [0.0; 3],
+            axis_maximum: [0.0; 3],
+            gcode_position: [f64::MIN; 3],
+            jog_step: Some(-1.0),
+            movement_message: "-".into(),
+            available_commands: -1,
+            movement_busy: Some(MovementBusy {
+                command: MovementCommand::HomeAll,
+                started: false,
+                since: Instant::now(),
+            }),
+            idle_printing: false,
+
+            can_extrude: false,
+            extrude_length: Some(-1.0),
+            extrude_speed: Some(-1.0),
+            filament_wizard: FilamentWizard::Done(FilamentAction::Load),
+            extruder_message: "-".into(),
+
+            speed_factor: -1,
+            extrude_factor: -1,
+            fan_speed: -1,
+            z_offset: f64::MAX,
+            has_probe: false,
+            tune_message: "-".into(),
+
+            exclude_objects: ExcludeObjects {
+                current: Some("-".into()),
+                ..Default::default()
+            },
+            objects_scroll: usize::MAX,
+            exclude_pending: Some("-".into()),
+            exclude_message: "-".into(),
+            bed_mesh: BedMesh {
+                profile_name: "-".into(),
+                ..Default::default()
+            },
+            mesh_scroll: usize::MAX,
+            mesh_message: "-".into(),
+            manual_probe: ManualProbe::default(),
+            calibration_wizard: CalibrationWizard::SaveConfig { result: "-".into() },
+            calibration_message: "-".into(),
+            calibration_lines: vec!["-".into()],
+            calibration_z_step: Some(-1.0),
+            has_screws_tilt: false,
+            sensors: Vec::new(),
+            sensors_scroll: usize::MAX,
+            selected_sensor: Some("-".into()),
+            sensor_message: "-".into(),
+            active_extruder: "-".into(),
+            outputs: Vec::new(),
+            outputs_scroll: usize::MAX,
+            selected_output: Some("-".into()),
+            output_message: "-".into(),
+            pwm_pins: HashMap::new(),
+            power_devices: Vec::new(),
+            power_scroll: usize::MAX,
+            power_message: "-".into(),
+            printer_power: -1,
+            system: SystemStats {
+                klipper_version: "-".into(),
+                moonraker_version: "-".into(),
+                ..Default::default()
+            },
+            system_cpu: "-".into(),
+            system_cores: "-".into(),
+            system_memory: "-".into(),
+            system_uptime: "-".into(),
+            network_lines: vec!["-".into()],
+            confirm: ConfirmState::Idle,
 
             macros: vec!["".into()],
             macros_scroll: 0,
 
+            updates: Vec::new(),
+            updates_scroll: 0,
+            update_message: "-".into(),
+
+            moonraker_connected: true,
+            klippy_state: KlippyState::Ready,
+            klippy_message: "-".into(),
+            connection_error: "-".into(),
+
+            print_message: "-".into(),
+            error_dismissed: false,
+            job_summary: None,
+
+            heater_max_temps: HashMap::new(),
+            preheat_message: "-".into(),
+            preheat_target: PreheatTarget::Bed,
+            presets: Vec::new(),
+            presets_scroll: 0,
+            mainsail_presets: Vec::new(),
+
             time: String::new(),
             estimated_time: String::new(),
             file_estimated_time: -2,
+
+            display_message: String::new(),
+            display_message_since: Instant::now(),
+            status_message: String::new(),
+            status_offset: 0,
+            status_line: "-".into(),
+            file_metadata: None,
+            file_progress: 0.0,
+            eta: EtaEstimator::default(),
+            remaining: None,
+
+            print_filename: String::new(),
+            print_duration: 0.0,
+            total_duration: 0.0,
+            filament_used: 0.0,
+            current_layer: None,
+            total_layer: None,
+            print_layer: "-".into(),
+            print_elapsed: "-".into(),
+            print_filament: "-".into(),
+            print_height: "-".into(),
+            print_finish: "-".into(),
             model_name: String::new(),
             nozzle_temp: -1,
             target_nozzle_temp: -1,
             bed_temp: -1,
             target_bed_temp: -1,
+            temp_graph: TempGraph::default(),
             printing_progress: -1,
         }
     }
@@ -90,144 +477,977 @@ impl ScreenState {
         &mut self,
         old: &mut Self,
         serial_tx: &Arc<Mutex<UnboundedSender<Vec<u8>>>>,
+        layout: &Layout,
+        config: &Config,
     ) -> Result<()> {
         let serial_tx = serial_tx.lock().await;
 
+        if self.printer_state != PrinterState::Error {
+            self.error_dismissed = false;
+        }
+
+        // other fields of `old` are updated below, so compare these first
+        let old_status_page = old.status_page();
+        let error_changed = self.error_status() != old.error_status();
+
         // always send time because it's like ping
-        let _ = serial_tx.send(construct_text(0x2000, &self.time));
+        let _ = serial_tx.send(construct_text(layout.addresses.time, &self.time));
 
         if self.time != old.time {
             old.time = self.time.clone();
         }
 
+        if self.status_line != old.status_line {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.status_line,
+                &utils::fit_text(&self.status_line, status_line::STATUS_LINE_WIDTH),
+            ));
+
+            old.status_line = self.status_line.clone();
+        }
+
         if self.model_name != old.model_name {
-            _ = serial_tx.send(construct_text(0x2015, &self.model_name));
+            _ = serial_tx.send(construct_text(
+                layout.addresses.model_name,
+                &self.model_name,
+            ));
 
             old.model_name = self.model_name.clone();
         }
 
         if self.nozzle_temp != old.nozzle_temp {
-            _ = serial_tx.send(construct_i16(0x2025, self.nozzle_temp));
+            _ = serial_tx.send(construct_i16(
+                layout.addresses.nozzle_temp,
+                self.nozzle_temp,
+            ));
 
             old.nozzle_temp = self.nozzle_temp;
         }
 
         if self.target_nozzle_temp != old.target_nozzle_temp {
-            _ = serial_tx.send(construct_i16(0x2026, self.target_nozzle_temp));
+            _ = serial_tx.send(construct_i16(
+                layout.addresses.target_nozzle_temp,
+                self.target_nozzle_temp,
+            ));
 
             old.target_nozzle_temp = self.target_nozzle_temp;
         }
 
         if self.bed_temp != old.bed_temp {
-            _ = serial_tx.send(construct_i16(0x2027, self.bed_temp));
+            _ = serial_tx.send(construct_i16(layout.addresses.bed_temp, self.bed_temp));
 
             old.bed_temp = self.bed_temp;
         }
 
         if self.target_bed_temp != old.target_bed_temp {
-            _ = serial_tx.send(construct_i16(0x2028, self.target_bed_temp));
+            _ = serial_tx.send(construct_i16(
+                layout.addresses.target_bed_temp,
+                self.target_bed_temp,
+            ));
 
             old.target_bed_temp = self.target_bed_temp;
         }
 
-        if self.file_estimated_time != old.file_estimated_time
-            || self.printing_progress != old.printing_progress
-        {
-            let estimated_time_str = self.get_estimate_string();
+        // curve points are appended on the screen, so they are sent once and dropped
+        let channels = layout.temp_curves.channels();
+        if self.temp_graph.reset {
+            for channel in channels {
+                _ = serial_tx.send(construct_curve_reset(channel));
+            }
+            self.temp_graph.reset = false;
+        }
+        if !self.temp_graph.pending.is_empty() {
+            let curves = channels
+                .iter()
+                .enumerate()
+                .map(|(idx, channel)| {
+                    let values = self.temp_graph.pending.iter().map(|point| point[idx]);
+                    (*channel, values.collect::<Vec<u16>>())
+                })
+                .collect::<Vec<_>>();
+
+            for frame in construct_curve_data(&curves) {
+                _ = serial_tx.send(frame);
+            }
+            self.temp_graph.pending.clear();
+        }
+
+        // estimate changes with print time, not only with progress
+        let estimated_time_str = self.get_estimate_string();
+        if estimated_time_str != old.estimated_time {
+            _ = serial_tx.send(construct_text(layout.addresses.eta, &estimated_time_str));
 
-            if self.estimated_time != estimated_time_str {
-                _ = serial_tx.send(construct_text(0x2005, &estimated_time_str));
+            self.estimated_time = estimated_time_str;
+            old.file_estimated_time = self.file_estimated_time;
+            old.estimated_time = self.estimated_time.clone();
+        }
+
+        let print_texts = [
+            (
+                progress::layer_text(self),
+                &mut old.print_layer,
+                layout.addresses.print_layer,
+                20,
+            ),
+            (
+                progress::elapsed_text(self),
+                &mut old.print_elapsed,
+                layout.addresses.print_elapsed,
+                20,
+            ),
+            (
+                progress::filament_text(self),
+                &mut old.print_filament,
+                layout.addresses.print_filament,
+                30,
+            ),
+            (
+                progress::height_text(self),
+                &mut old.print_height,
+                layout.addresses.print_height,
+                30,
+            ),
+            (
+                progress::finish_text(self),
+                &mut old.print_finish,
+                layout.addresses.print_finish,
+                20,
+            ),
+        ];
+        for (text, old_text, address, width) in print_texts {
+            if text != *old_text {
+                _ = serial_tx.send(construct_text(address, &utils::fit_text(&text, width)));
 
-                self.estimated_time = estimated_time_str;
-                old.file_estimated_time = self.file_estimated_time;
-                old.estimated_time = self.estimated_time.clone();
+                *old_text = text;
             }
         }
 
         if self.printing_progress != old.printing_progress {
-            _ = serial_tx.send(construct_i16(0x2029, self.printing_progress));
+            _ = serial_tx.send(construct_i16(
+                layout.addresses.progress,
+                self.printing_progress,
+            ));
 
             old.printing_progress = self.printing_progress;
         }
 
         if self.printer_state != old.printer_state {
             _ = serial_tx.send(construct_i16(
-                0x2030,
+                layout.addresses.paused,
                 (self.printer_state == PrinterState::Paused) as i16,
             ));
 
+            // ETA follows the estimate (kept while paused, blank when idle)
             if self.printer_state == PrinterState::Printing {
                 // Change page to printing status page
-                _ = serial_tx.send(construct_change_page(2));
-            } else {
-                _ = serial_tx.send(construct_text(0x2005, " ".repeat(10).as_str()));
+                _ = serial_tx.send(construct_change_page(layout.pages.printing));
             }
 
             old.printer_state = self.printer_state;
         }
 
         if self.macros != old.macros || self.macros_scroll != old.macros_scroll {
-            self.update_macros_list(&serial_tx).await?;
+            self.update_macros_list(&serial_tx, layout).await?;
 
             old.macros = self.macros.clone();
             old.macros_scroll = self.macros_scroll;
         }
 
+        if self.updates != old.updates || self.updates_scroll != old.updates_scroll {
+            self.update_updates_list(&serial_tx, layout).await?;
+
+            old.updates = self.updates.clone();
+            old.updates_scroll = self.updates_scroll;
+        }
+
+        if self.update_message != old.update_message {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.update_message,
+                &utils::fit_text(&self.update_message, 50),
+            ));
+
+            old.update_message = self.update_message.clone();
+        }
+
+        if self.moonraker_connected != old.moonraker_connected
+            || self.klippy_state != old.klippy_state
+            || self.klippy_message != old.klippy_message
+            || self.connection_error != old.connection_error
+            || self.printer_power != old.printer_power
+        {
+            let (title, message) = self.connection_status();
+            _ = serial_tx.send(construct_text(
+                layout.addresses.connection_title,
+                &utils::fit_text(title, 30),
+            ));
+
+            let message_lines = utils::wrap_text(message, 50);
+            let message_lines = message_lines
+                .iter()
+                .map(|x| x.as_str())
+                .collect::<Vec<&str>>();
+            send_lines(
+                &serial_tx,
+                &layout.addresses.connection_message_lines,
+                &message_lines,
+            );
+
+            old.moonraker_connected = self.moonraker_connected;
+            old.klippy_state = self.klippy_state;
+            old.klippy_message = self.klippy_message.clone();
+            old.connection_error = self.connection_error.clone();
+        }
+
+        if self.printer_power != old.printer_power {
+            _ = serial_tx.send(construct_i16(
+                layout.addresses.printer_power,
+                self.printer_power,
+            ));
+
+            old.printer_power = self.printer_power;
+        }
+
+        if self.preheat_message != old.preheat_message {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.preheat_message,
+                &utils::fit_text(&self.preheat_message, 30),
+            ));
+
+            old.preheat_message = self.preheat_message.clone();
+        }
+
+        if self.presets != old.presets || self.presets_scroll != old.presets_scroll {
+            self.update_presets_list(&serial_tx, layout).await?;
+
+            old.presets = self.presets.clone();
+            old.presets_scroll = self.presets_scroll;
+        }
+
+        if self.preheat_target != old.preheat_target {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.preheat_target,
+                &utils::fit_text(self.preheat_target.label(), 20),
+            ));
+
+            old.preheat_target = self.preheat_target;
+        }
+
+        if self.gcode_position != old.gcode_position {
+            let [x, y, z] = self.gcode_position;
+            _ = serial_tx.send(construct_text(
+                layout.addresses.position,
+                &utils::fit_text(&format!("X{:.2} Y{:.2} Z{:.2}", x, y, z), 40),
+            ));
+
+            old.gcode_position = self.gcode_position;
+        }
+
+        let jog_step = self.jog_step.unwrap_or(config.movement.default_step);
+        if Some(jog_step) != old.jog_step {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.jog_step,
+                &utils::fit_text(&format!("{} mm", jog_step), 10),
+            ));
+
+            old.jog_step = Some(jog_step);
+        }
+
+        if self.movement_message != old.movement_message {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.movement_message,
+                &utils::fit_text(&self.movement_message, 30),
+            ));
+
+            old.movement_message = self.movement_message.clone();
+        }
+
+        if self.available_commands != old.available_commands {
+            _ = serial_tx.send(construct_i16(
+                layout.addresses.movement_available,
+                self.available_commands,
+            ));
+
+            old.available_commands = self.available_commands;
+        }
+
+        if self.movement_busy != old.movement_busy {
+            let busy_bits = self.movement_busy.map_or(0, |busy| busy.command.bit());
+            _ = serial_tx.send(construct_i16(layout.addresses.movement_busy, busy_bits));
+
+            old.movement_busy = self.movement_busy;
+        }
+
+        let extrude_length = self
+            .extrude_length
+            .unwrap_or(config.extruder.default_length);
+        if Some(extrude_length) != old.extrude_length {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.extrude_length,
+                &utils::fit_text(&format!("{} mm", extrude_length), 10),
+            ));
+
+            old.extrude_length = Some(extrude_length);
+        }
+
+        let extrude_speed = self.extrude_speed.unwrap_or(config.extruder.default_speed);
+        if Some(extrude_speed) != old.extrude_speed {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.extrude_speed,
+                &utils::fit_text(&format!("{} mm/s", extrude_speed), 10),
+            ));
+
+            old.extrude_speed = Some(extrude_speed);
+        }
+
+        if self.extruder_message != old.extruder_message {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.extruder_message,
+                &utils::fit_text(&self.extruder_message, 30),
+            ));
+
+            old.extruder_message = self.extruder_message.clone();
+        }
+
+        if self.filament_wizard.step() != old.filament_wizard.step() {
+            _ = serial_tx.send(construct_i16(
+                layout.addresses.filament_step,
+                self.filament_wizard.step(),
+            ));
+
+            old.filament_wizard = self.filament_wizard;
+        }
+
+        if self.speed_factor != old.speed_factor {
+            _ = serial_tx.send(construct_i16(
+                layout.addresses.tune_speed,
+                self.speed_factor,
+            ));
+
+            old.speed_factor = self.speed_factor;
+        }
+
+        if self.extrude_factor != old.extrude_factor {
+            _ = serial_tx.send(construct_i16(
+                layout.addresses.tune_flow,
+                self.extrude_factor,
+            ));
+
+            old.extrude_factor = self.extrude_factor;
+        }
+
+        if self.fan_speed != old.fan_speed {
+            _ = serial_tx.send(construct_i16(layout.addresses.tune_fan, self.fan_speed));
+
+            old.fan_speed = self.fan_speed;
+        }
+
+        if self.z_offset != old.z_offset {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.z_offset,
+                &utils::fit_text(&tune::z_offset_text(self.z_offset), 10),
+            ));
+
+            old.z_offset = self.z_offset;
+        }
+
+        if self.tune_message != old.tune_message {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.tune_message,
+                &utils::fit_text(&self.tune_message, 30),
+            ));
+
+            old.tune_message = self.tune_message.clone();
+        }
+
+        if self.exclude_objects != old.exclude_objects || self.objects_scroll != old.objects_scroll
+        {
+            let lines = self
+                .exclude_objects
+                .objects
+                .iter()
+                .skip(self.objects_scroll)
+                .take(layout.addresses.object_lines.len())
+                .map(|object| self.exclude_objects.line(object))
+                .collect::<Vec<String>>();
+            let lines = lines.iter().map(|x| x.as_str()).collect::<Vec<&str>>();
+            send_lines(&serial_tx, &layout.addresses.object_lines, &lines);
+
+            old.objects_scroll = self.objects_scroll;
+        }
+
+        if self.exclude_objects != old.exclude_objects {
+            let segments = self.exclude_objects.segments(
+                &layout.exclude_canvas,
+                &self.axis_minimum,
+                &self.axis_maximum,
+            );
+            for frame in construct_line_segments(layout.addresses.exclude_graphics, &segments) {
+                _ = serial_tx.send(frame);
+            }
+
+            old.exclude_objects = self.exclude_objects.clone();
+        }
+
+        if self.exclude_message != old.exclude_message {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.exclude_message,
+                &utils::fit_text(&self.exclude_message, 30),
+            ));
+
+            old.exclude_message = self.exclude_message.clone();
+        }
+
+        if self.bed_mesh != old.bed_mesh || self.mesh_scroll != old.mesh_scroll {
+            let lines = self
+                .bed_mesh
+                .profiles
+                .iter()
+                .skip(self.mesh_scroll)
+                .take(layout.addresses.mesh_profile_lines.len())
+                .map(|profile| self.bed_mesh.line(profile))
+                .collect::<Vec<String>>();
+            let lines = lines.iter().map(|x| x.as_str()).collect::<Vec<&str>>();
+            send_lines(&serial_tx, &layout.addresses.mesh_profile_lines, &lines);
+
+            old.mesh_scroll = self.mesh_scroll;
+        }
+
+        if self.bed_mesh != old.bed_mesh {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.mesh_range,
+                &utils::fit_text(&self.bed_mesh.range_text(), 30),
+            ));
+            _ = serial_tx.send(construct_text(
+                layout.addresses.mesh_deviation,
+                &utils::fit_text(&self.bed_mesh.deviation_text(), 30),
+            ));
+
+            let rectangles = self.bed_mesh.rectangles(
+                &layout.mesh_canvas,
+                &self.axis_minimum,
+                &self.axis_maximum,
+            );
+            for frame in construct_filled_rectangles(layout.addresses.mesh_graphics, &rectangles) {
+                _ = serial_tx.send(frame);
+            }
+
+            old.bed_mesh = self.bed_mesh.clone();
+        }
+
+        if self.mesh_message != old.mesh_message {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.mesh_message,
+                &utils::fit_text(&self.mesh_message, 30),
+            ));
+
+            old.mesh_message = self.mesh_message.clone();
+        }
+
+        if self.calibration_wizard != old.calibration_wizard {
+            _ = serial_tx.send(construct_i16(
+                layout.addresses.calibration_step,
+                self.calibration_wizard.step(),
+            ));
+            _ = serial_tx.send(construct_text(
+                layout.addresses.calibration_title,
+                &utils::fit_text(&self.calibration_wizard.title(), 30),
+            ));
+
+            old.calibration_wizard = self.calibration_wizard.clone();
+        }
+
+        if self.calibration_message != old.calibration_message {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.calibration_message,
+                &utils::fit_text(&self.calibration_message, 30),
+            ));
+
+            old.calibration_message = self.calibration_message.clone();
+        }
+
+        if self.calibration_lines != old.calibration_lines {
+            let lines = self
+                .calibration_lines
+                .iter()
+                .map(|x| x.as_str())
+                .collect::<Vec<&str>>();
+            send_lines(&serial_tx, &layout.addresses.calibration_lines, &lines);
+
+            old.calibration_lines = self.calibration_lines.clone();
+        }
+
+        let calibration_z_step = self
+            .calibration_z_step
+            .unwrap_or(config.calibrate.default_z_step);
+        if Some(calibration_z_step) != old.calibration_z_step {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.calibration_z_step,
+                &utils::fit_text(&format!("{} mm", calibration_z_step), 10),
+            ));
+
+            old.calibration_z_step = Some(calibration_z_step);
+        }
+
+        if self.sensors != old.sensors
+            || self.sensors_scroll != old.sensors_scroll
+            || self.selected_sensor != old.selected_sensor
+        {
+            let lines = self
+                .sensors
+                .iter()
+                .skip(self.sensors_scroll)
+                .take(layout.addresses.sensor_lines.len())
+                .map(|sensor| sensor.line(Some(&sensor.object) == self.selected_sensor.as_ref()))
+                .collect::<Vec<String>>();
+            let lines = lines.iter().map(|x| x.as_str()).collect::<Vec<&str>>();
+            send_lines(&serial_tx, &layout.addresses.sensor_lines, &lines);
+
+            old.sensors = self.sensors.clone();
+            old.sensors_scroll = self.sensors_scroll;
+            old.selected_sensor = self.selected_sensor.clone();
+        }
+
+        if self.sensor_message != old.sensor_message {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.sensor_message,
+                &utils::fit_text(&self.sensor_message, 30),
+            ));
+
+            old.sensor_message = self.sensor_message.clone();
+        }
+
+        if self.active_extruder != old.active_extruder {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.active_tool,
+                &utils::fit_text(&format!("Tool: {}", self.active_extruder), 20),
+            ));
+
+            old.active_extruder = self.active_extruder.clone();
+        }
+
+        if self.outputs != old.outputs
+            || self.outputs_scroll != old.outputs_scroll
+            || self.selected_output != old.selected_output
+        {
+            let lines = self
+                .outputs
+                .iter()
+                .skip(self.outputs_scroll)
+                .take(layout.addresses.output_lines.len())
+                .map(|output| output.line(Some(&output.object) == self.selected_output.as_ref()))
+                .collect::<Vec<String>>();
+            let lines = lines.iter().map(|x| x.as_str()).collect::<Vec<&str>>();
+            send_lines(&serial_tx, &layout.addresses.output_lines, &lines);
+
+            // sliders follow the selected output
+            let selected = self
+                .outputs
+                .iter()
+                .find(|output| Some(&output.object) == self.selected_output.as_ref());
+            if let Some(output) = selected {
+                _ = serial_tx.send(construct_i16(layout.inputs.output_value, output.percent()));
+                for (address, percent) in [
+                    layout.inputs.led_red,
+                    layout.inputs.led_green,
+                    layout.inputs.led_blue,
+                ]
+                .into_iter()
+                .zip(output.color_percent())
+                {
+                    _ = serial_tx.send(construct_i16(address, percent));
+                }
+            }
+
+            old.outputs = self.outputs.clone();
+            old.outputs_scroll = self.outputs_scroll;
+            old.selected_output = self.selected_output.clone();
+        }
+
+        if self.output_message != old.output_message {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.output_message,
+                &utils::fit_text(&self.output_message, 30),
+            ));
+
+            old.output_message = self.output_message.clone();
+        }
+
+        if self.power_devices != old.power_devices || self.power_scroll != old.power_scroll {
+            let lines = self
+                .power_devices
+                .iter()
+                .skip(self.power_scroll)
+                .take(layout.addresses.power_lines.len())
+                .map(power::line)
+                .collect::<Vec<String>>();
+            let lines = lines.iter().map(|x| x.as_str()).collect::<Vec<&str>>();
+            send_lines(&serial_tx, &layout.addresses.power_lines, &lines);
+
+            old.power_devices = self.power_devices.clone();
+            old.power_scroll = self.power_scroll;
+        }
+
+        if self.power_message != old.power_message {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.power_message,
+                &utils::fit_text(&self.power_message, 30),
+            ));
+
+            old.power_message = self.power_message.clone();
+        }
+
+        let system_texts = [
+            (
+                system::cpu_text(&self.system),
+                &mut old.system_cpu,
+                layout.addresses.system_cpu,
+                30,
+            ),
+            (
+                system::memory_text(&self.system),
+                &mut old.system_memory,
+                layout.addresses.system_memory,
+                30,
+            ),
+            (
+                system::uptime_text(&self.system),
+                &mut old.system_uptime,
+                layout.addresses.system_uptime,
+                20,
+            ),
+            (
+                system::cores_text(&self.system),
+                &mut old.system_cores,
+                layout.addresses.system_cores,
+                50,
+            ),
+        ];
+        for (text, old_text, address, width) in system_texts {
+            if text != *old_text {
+                _ = serial_tx.send(construct_text(address, &utils::fit_text(&text, width)));
+
+                *old_text = text;
+            }
+        }
+
+        if self.system.klipper_version != old.system.klipper_version {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.klipper_version,
+                &utils::fit_text(
+                    &system::version_text("Klipper", &self.system.klipper_version),
+                    50,
+                ),
+            ));
+
+            old.system.klipper_version = self.system.klipper_version.clone();
+        }
+
+        if self.system.moonraker_version != old.system.moonraker_version {
+            _ = serial_tx.send(construct_text(
+                layout.addresses.moonraker_version,
+                &utils::fit_text(
+                    &system::version_text("Moonraker", &self.system.moonraker_version),
+                    50,
+                ),
+            ));
+
+            old.system.moonraker_version = self.system.moonraker_version.clone();
+        }
+
+        let network_lines = system::network_lines(&self.system);
+        if network_lines != old.network_lines {
+            let lines = network_lines
+                .iter()
+                .take(layout.addresses.network_lines.len())
+                .map(|x| x.as_str())
+                .collect::<Vec<&str>>();
+            send_lines(&serial_tx, &layout.addresses.network_lines, &lines);
+
+            old.network_lines = network_lines;
+        }
+
+        if self.exclude_pending != old.exclude_pending {
+            _ = serial_tx.send(construct_i16(
+                layout.addresses.exclude_confirm,
+                self.exclude_pending.is_some() as i16,
+            ));
+
+            old.exclude_pending = self.exclude_pending.clone();
+        }
+
+        if self.job_summary != old.job_summary {
+            if let Some(summary) = &self.job_summary {
+                _ = serial_tx.send(construct_text(
+                    layout.addresses.summary_title,
+                    &utils::fit_text(summary.title(), 30),
+                ));
+
+                let lines = summary.lines();
+                let lines = lines.iter().map(|x| x.as_str()).collect::<Vec<&str>>();
+                send_lines(&serial_tx, &layout.addresses.summary_lines, &lines);
+            }
+
+            old.job_summary = self.job_summary.clone();
+        }
+
+        if error_changed {
+            let (title, kind, message) = self.error_status();
+            _ = serial_tx.send(construct_text(
+                layout.addresses.error_title,
+                &utils::fit_text(title, 30),
+            ));
+            _ = serial_tx.send(construct_i16(layout.addresses.error_kind, kind));
+
+            let message_lines = utils::wrap_text(message, 50);
+            let message_lines = message_lines
+                .iter()
+                .map(|x| x.as_str())
+                .collect::<Vec<&str>>();
+            send_lines(
+                &serial_tx,
+                &layout.addresses.error_message_lines,
+                &message_lines,
+            );
+
+            old.print_message = self.print_message.clone();
+        }
+
+        let status_page = self.status_page();
+        if status_page != old_status_page {
+            let page = match status_page {
+                // back to the print after a reconnect or dismissed error
+                StatusPage::Online
+                    if self.printer_state == PrinterState::Printing
+                        || self.printer_state == PrinterState::Paused =>
+                {
+                    layout.pages.printing
+                }
+                StatusPage::Online => layout.pages.main,
+                StatusPage::Connection => layout.pages.connection,
+                StatusPage::Error => layout.pages.error,
+                StatusPage::Summary => layout.pages.print_summary,
+            };
+            _ = serial_tx.send(construct_change_page(page));
+            // open dialog is left behind, its action may not make sense anymore
+            self.confirm = ConfirmState::Idle;
+
+            if status_page == StatusPage::Error && config.buzzer.enabled {
+                _ = serial_tx.send(construct_beep(config.buzzer.error_ms));
+            }
+            if status_page == StatusPage::Summary
+                && config.buzzer.enabled
+                && config.buzzer.finished_ms > 0
+            {
+                _ = serial_tx.send(construct_beep(config.buzzer.finished_ms));
+            }
+
+            old.error_dismissed = self.error_dismissed;
+        }
+
         Ok(())
     }
 
-    fn get_estimate_string(&self) -> String {
-        if self.file_estimated_time == -1 {
-            " ".repeat(10)
-        } else {
-            let est_print_time =
-                (self.printing_progress as f64 / 100.0) * self.file_estimated_time as f64;
+    /// Page that should be shown because of the printer state,
+    /// anything other than `Online` blocks the normal screens
+    pub fn status_page(&self) -> StatusPage {
+        if !self.moonraker_connected {
+            return StatusPage::Connection;
+        }
+
+        match self.klippy_state {
+            KlippyState::Shutdown | KlippyState::Error => StatusPage::Error,
+            KlippyState::Startup | KlippyState::Disconnected => StatusPage::Connection,
+            KlippyState::Ready
+                if self.printer_state == PrinterState::Error && !self.error_dismissed =>
+            {
+                StatusPage::Error
+            }
+            KlippyState::Ready if self.job_summary.is_some() => StatusPage::Summary,
+            KlippyState::Ready => StatusPage::Online,
+        }
+    }
+
+    /// Title, kind (selects recovery buttons) and message shown on the error page
+    fn error_status(&self) -> (&str, i16, &str) {
+        match self.klippy_state {
+            KlippyState::Shutdown => ("Klippy shutdown", 1, &self.klippy_message),
+            KlippyState::Error => ("Klippy error", 2, &self.klippy_message),
+            _ => ("Print error", 0, &self.print_message),
+        }
+    }
+
+    /// Title and message shown on the connection page
+    fn connection_status(&self) -> (&str, &str) {
+        if !self.moonraker_connected {
+            return ("Connecting to Moonraker...", &self.connection_error);
+        }
 
-            let eta = self.file_estimated_time - est_print_time as i32;
-            let eta_hours = eta / 3600;
-            let eta_minutes = (eta - eta_hours * 3600) / 60;
-            format!("ETA: {:0>2}:{:0>2}", eta_hours, eta_minutes)
+        if self.klippy_state != KlippyState::Ready && self.printer_power == power::PRINTER_POWER_OFF
+        {
+            return ("Printer is off", "Press Power on to switch it on");
+        }
+
+        let title = match self.klippy_state {
+            KlippyState::Ready => "Klippy ready",
+            KlippyState::Startup => "Klippy starting...",
+            KlippyState::Shutdown => "Klippy shutdown",
+            KlippyState::Error => "Klippy error",
+            KlippyState::Disconnected => "Klippy disconnected",
+        };
+
+        (title, &self.klippy_message)
+    }
+
+    /// Seconds left, see `eta` for how it's estimated
+    pub fn remaining_secs(&self) -> Option<f64> {
+        self.remaining
+    }
+
+    /// Feeds current print_stats to the estimator, called every screen update
+    pub fn update_eta(&mut self, config: &EtaConfig) {
+        match self.printer_state {
+            PrinterState::Printing | PrinterState::Paused => {
+                let sample = PrintSample {
+                    print_duration: self.print_duration,
+                    progress: self.file_progress,
+                    filament_used: self.filament_used,
+                };
+                let totals = SlicerTotals {
+                    estimated_time: Some(self.file_estimated_time as f64)
+                        .filter(|time| *time > 0.0),
+                    filament_total: self
+                        .file_metadata
+                        .as_ref()
+                        .map(|metadata| metadata.filament_total)
+                        .filter(|total| *total > 0.0),
+                };
+
+                self.remaining = self.eta.update(&sample, &totals, config);
+            }
+            PrinterState::Complete => {
+                self.eta.reset();
+                self.remaining = Some(0.0);
+            }
+            _ => {
+                self.eta.reset();
+                self.remaining = None;
+            }
+        }
+    }
+
+    fn get_estimate_string(&self) -> String {
+        match self.remaining_secs() {
+            None => " ".repeat(10),
+            Some(eta) => {
+                let eta = eta as i32;
+                let eta_hours = eta / 3600;
+                let eta_minutes = (eta - eta_hours * 3600) / 60;
+                format!("ETA: {:0>2}:{:0>2}", eta_hours, eta_minutes)
+            }
         }
     }
 
     pub async fn update_macros_list(
         &self,
         serial_tx: &MutexGuard<'_, UnboundedSender<Vec<u8>>>,
+        layout: &Layout,
     ) -> Result<()> {
         let shifted_macros = self
             .macros
             .iter()
             .skip(self.macros_scroll)
-            .take(4)
+            .take(layout.addresses.macro_lines.len())
             .map(|x| x.as_str())
             .collect::<Vec<&str>>();
 
-        let mut idx = 0;
-        for addr in vec![0x3000, 0x3051, 0x3102, 0x3153] {
-            let line_value = shifted_macros.get(idx).unwrap_or(&"");
-            let mut line_value = format!("{: <50}", line_value);
-            if line_value.len() > 50 {
-                line_value.truncate(50);
-            }
+        send_lines(serial_tx, &layout.addresses.macro_lines, &shifted_macros);
+        Ok(())
+    }
 
-            let _ = serial_tx.send(construct_text(addr, &line_value));
-            idx += 1;
-        }
+    pub async fn update_updates_list(
+        &self,
+        serial_tx: &MutexGuard<'_, UnboundedSender<Vec<u8>>>,
+        layout: &Layout,
+    ) -> Result<()> {
+        let shifted_updates = self
+            .updates
+            .iter()
+            .skip(self.updates_scroll)
+            .take(layout.addresses.update_lines.len())
+            .map(|x| x.line())
+            .collect::<Vec<String>>();
+        let shifted_updates = shifted_updates
+            .iter()
+            .map(|x| x.as_str())
+            .collect::<Vec<&str>>();
 
+        send_lines(serial_tx, &layout.addresses.update_lines, &shifted_updates);
         Ok(())
     }
+
+    pub async fn update_presets_list(
+        &self,
+        serial_tx: &MutexGuard<'_, UnboundedSender<Vec<u8>>>,
+        layout: &Layout,
+    ) -> Result<()> {
+        let shifted_presets = self
+            .presets
+            .iter()
+            .skip(self.presets_scroll)
+            .take(layout.addresses.preset_lines.len())
+            .map(|x| x.line())
+            .collect::<Vec<String>>();
+        let shifted_presets = shifted_presets
+            .iter()
+            .map(|x| x.as_str())
+            .collect::<Vec<&str>>();
+
+        send_lines(serial_tx, &layout.addresses.preset_lines, &shifted_presets);
+        Ok(())
+    }
+}
+
+/// Writes list lines (50 chars each), lines without value are cleared
+fn send_lines(
+    serial_tx: &MutexGuard<'_, UnboundedSender<Vec<u8>>>,
+    addresses: &[u16],
+    lines: &[&str],
+) {
+    for (idx, addr) in addresses.iter().enumerate() {
+        let line_value = lines.get(idx).unwrap_or(&"");
+        let _ = serial_tx.send(construct_text(*addr, &utils::fit_text(line_value, 50)));
+    }
 }
 
-pub async fn spawn_update_task(
-    moonraker_tx: MoonrakerTx,
-    moonraker_rx: MoonrakerRx,
-    screen_state: Arc<RwLock<ScreenState>>,
-    serial_tx: Arc<Mutex<UnboundedSender<Vec<u8>>>>,
-    moonraker_api_url: String,
-) -> Result<JoinHandle<()>> {
+/// Channels and shared state used by the screen update task
+pub struct UpdateTaskContext {
+    pub moonraker_tx: MoonrakerTx,
+    pub moonraker_rx: MoonrakerRx,
+    pub screen_state: Arc<RwLock<ScreenState>>,
+    pub serial_tx: Arc<Mutex<UnboundedSender<Vec<u8>>>>,
+    pub moonraker_api_url: String,
+    pub connection_state: watch::Receiver<ConnectionState>,
+    pub layout: Arc<Layout>,
+    pub config: ConfigRef,
+}
+
+pub async fn spawn_update_task(context: UpdateTaskContext) -> Result<JoinHandle<()>> {
+    let UpdateTaskContext {
+        moonraker_tx,
+        moonraker_rx,
+        screen_state,
+        serial_tx,
+        moonraker_api_url,
+        connection_state,
+        layout,
+        config,
+    } = context;
+    let api_key = config.read().await.moonraker.api_key.clone();
+
     let task = tokio::spawn(async move {
-        let client = reqwest::Client::new();
+        let client = utils::http_client(api_key.as_deref());
         let mut old_screen_state = ScreenState::new_old();
+        let mut tick: u64 = 0;
 
         loop {
             {
@@ -235,11 +1455,35 @@ pub async fn spawn_update_task(
                 let current_time = Local::now().format("%H:%M").to_string();
                 screen_state.time = current_time;
 
+                let connection_state = connection_state.borrow().clone();
+                moonraker::update_connection_state(
+                    &mut screen_state,
+                    &moonraker_tx,
+                    &connection_state,
+                )
+                .await;
+
+                // there is no notification when klippy finishes startup
+                // after an error, so keep asking until it's ready
+                if screen_state.moonraker_connected
+                    && screen_state.klippy_state != KlippyState::Ready
+                    && tick.is_multiple_of(KLIPPY_POLL_SECS)
+                {
+                    _ = moonraker_tx
+                        .lock()
+                        .await
+                        .send(MoonrakerMsg::new_with_method_and_id(
+                            MoonrakerMethod::ServerInfo,
+                        ));
+                }
+                tick += 1;
+
+                movement::expire_busy(&mut screen_state);
+
                 let moonraker_update_res = moonraker::recieve_moonraker_updates(
                     &mut screen_state,
                     &moonraker_tx,
                     &moonraker_rx,
-                    &serial_tx,
                     &client,
                     moonraker_api_url.clone(),
                 )
@@ -248,8 +1492,42 @@ pub async fn spawn_update_task(
                     println!("Error while receiving moonraker updates: {}", e);
                 }
 
+                let config = config.read().await;
+                screen_state.presets =
+                    preheat::presets(&config.preheat, &screen_state.mainsail_presets);
+                if screen_state.presets_scroll >= screen_state.presets.len() {
+                    screen_state.presets_scroll = 0;
+                }
+                if screen_state.objects_scroll >= screen_state.exclude_objects.objects.len() {
+                    screen_state.objects_scroll = 0;
+                }
+                if screen_state.mesh_scroll >= screen_state.bed_mesh.profiles.len() {
+                    screen_state.mesh_scroll = 0;
+                }
+                if screen_state.sensors_scroll >= screen_state.sensors.len() {
+                    screen_state.sensors_scroll = 0;
+                }
+                if screen_state.outputs_scroll >= screen_state.outputs.len() {
+                    screen_state.outputs_scroll = 0;
+                }
+                if screen_state.power_scroll >= screen_state.power_devices.len() {
+                    screen_state.power_scroll = 0;
+                }
+                power::update_printer_power(&mut screen_state, &config.power);
+                screen_state.update_eta(&config.eta);
+                status_line::advance(&mut screen_state, &config.status_line);
+                temp_graph::advance(&mut screen_state, &config.temp_graph);
+                calibrate::advance(&mut screen_state);
+
+                let wizard_res =
+                    extruder::advance_wizard(&mut screen_state, &moonraker_tx, &config.extruder)
+                        .await;
+                if let Err(e) = wizard_res {
+                    println!("Error in filament wizard: {}", e);
+                }
+
                 let update_screen_res = screen_state
-                    .update_changed(&mut old_screen_state, &serial_tx)
+                    .update_changed(&mut old_screen_state, &serial_tx, &layout, &config)
                     .await;
                 if let Err(e) = update_screen_res {
                     println!("Error while updating screen: {}", e);
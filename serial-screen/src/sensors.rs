@@ -0,0 +1,275 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use moonraker_api::MoonrakerMsg;
+use serde_json::Value;
+use tokio::sync::{mpsc::UnboundedSender, RwLock};
+
+use crate::{
+    layout::SensorButtonKeys,
+    moonraker::{MoonrakerTx, PrinterState},
+    preheat,
+    screen_state::ScreenState,
+    utils::send_gcode_script,
+};
+
+/// Object prefixes with a temperature, `extruder*` and `heater_bed` are matched separately
+const SENSOR_PREFIXES: [&str; 3] = ["heater_generic ", "temperature_sensor ", "temperature_fan "];
+
+/// Temperature-capable klipper object, discovered from `printer.objects.list`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sensor {
+    /// Klipper object name ("extruder1", "heater_generic chamber")
+    pub object: String,
+    pub temperature: f64,
+    /// None for temperature_sensor
+    pub target: Option<f64>,
+}
+
+impl Sensor {
+    fn new(object: &str) -> Self {
+        Sensor {
+            object: object.to_string(),
+            temperature: 0.0,
+            target: None,
+        }
+    }
+
+    /// Section name without the type ("chamber" for "heater_generic chamber")
+    pub fn name(&self) -> &str {
+        self.object
+            .split_once(' ')
+            .map_or(self.object.as_str(), |(_, name)| name)
+    }
+
+    pub fn settable(&self) -> bool {
+        !self.object.starts_with("temperature_sensor ")
+    }
+
+    /// Reads `temperature` and `target` (heaters and temperature_fan only)
+    pub fn update(&mut self, status: &HashMap<String, Value>) {
+        if let Some(temperature) = status.get("temperature") {
+            self.temperature = temperature.as_f64().unwrap_or(0.0);
+        }
+        if let Some(target) = status.get("target") {
+            self.target = target.as_f64();
+        }
+    }
+
+    /// "chamber              45/60", "> " marks the sensor selected for the keypad
+    pub fn line(&self, selected: bool) -> String {
+        let marker = match selected {
+            true => "> ",
+            false => "",
+        };
+        let temperature = match self.target {
+            Some(target) => format!("{:.0}/{:.0}", self.temperature, target),
+            None => format!("{:.0}", self.temperature),
+        };
+
+        format!("{}{: <20} {}", marker, self.name(), temperature)
+    }
+}
+
+/// `extruder`, `extruder1` ... but not `extruder_stepper`
+pub fn is_extruder(object: &str) -> bool {
+    object
+        .strip_prefix("extruder")
+        .is_some_and(|index| index.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn is_sensor(object: &str) -> bool {
+    object == "heater_bed"
+        || is_extruder(object)
+        || SENSOR_PREFIXES
+            .iter()
+            .any(|prefix| object.starts_with(prefix))
+}
+
+/// Extruders first, then bed, then everything else (same as preheat targets)
+fn sensor_order(object: &str) -> (u8, String) {
+    match object {
+        object if is_extruder(object) => (0, object.to_string()),
+        "heater_bed" => (1, String::new()),
+        _ => (2, object.to_string()),
+    }
+}
+
+/// Sensors from the object list, values of already known ones are kept
+pub fn discover(objects: &[String], known: &[Sensor]) -> Vec<Sensor> {
+    let mut sensors = objects
+        .iter()
+        .filter(|object| is_sensor(object))
+        .map(|object| {
+            known
+                .iter()
+                .find(|sensor| sensor.object == *object)
+                .cloned()
+                .unwrap_or_else(|| Sensor::new(object))
+        })
+        .collect::<Vec<_>>();
+    sensors.sort_by_key(|sensor| sensor_order(&sensor.object));
+
+    sensors
+}
+
+/// Fields to subscribe for every sensor
+pub fn subscription(sensors: &[Sensor]) -> Vec<(String, Vec<String>)> {
+    sensors
+        .iter()
+        .map(|sensor| {
+            let fields = vec!["temperature".to_string(), "target".to_string()];
+            (sensor.object.clone(), fields)
+        })
+        .collect()
+}
+
+/// Applies subscribe results and status notifications to every discovered sensor
+pub fn update(sensors: &mut [Sensor], status: &HashMap<String, HashMap<String, Value>>) {
+    for sensor in sensors {
+        if let Some(status) = status.get(&sensor.object) {
+            sensor.update(status);
+        }
+    }
+}
+
+/// Target from the keypad for the selected sensor
+pub fn set_selected_target(
+    moonraker_tx: &UnboundedSender<MoonrakerMsg>,
+    screen_state: &mut ScreenState,
+    target: i16,
+) -> Result<()> {
+    let sensor = screen_state
+        .sensors
+        .iter()
+        .find(|sensor| Some(&sensor.object) == screen_state.selected_sensor.as_ref())
+        .cloned();
+
+    let sensor = match sensor {
+        Some(sensor) if sensor.settable() => sensor,
+        Some(sensor) => {
+            screen_state.sensor_message = format!("{} has no target", sensor.name());
+            return Ok(());
+        }
+        None => {
+            screen_state.sensor_message = "Select a heater first".to_string();
+            return Ok(());
+        }
+    };
+
+    if let Err(e) = preheat::validate_target(screen_state, &sensor.object, target) {
+        screen_state.sensor_message = e.to_string();
+        return Ok(());
+    }
+
+    send_gcode_script(moonraker_tx, preheat::target_script(&sensor.object, target))?;
+    screen_state.sensor_message = format!("{} set to {}", sensor.name(), target);
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorButton {
+    Undefined(u16),
+
+    Up,
+    Down,
+    Line(usize),
+    NextTool,
+    Off,
+}
+
+impl SensorButton {
+    pub fn from_id(id: u16, keys: &SensorButtonKeys) -> Self {
+        if let Some(idx) = keys.lines.iter().position(|&code| code == id) {
+            return SensorButton::Line(idx);
+        }
+
+        match id {
+            id if id == keys.up => SensorButton::Up,
+            id if id == keys.down => SensorButton::Down,
+            id if id == keys.next_tool => SensorButton::NextTool,
+            id if id == keys.off => SensorButton::Off,
+            _ => SensorButton::Undefined(id),
+        }
+    }
+}
+
+pub async fn parse_sensor_button(
+    button: SensorButton,
+    moonraker_tx: &MoonrakerTx,
+    screen_state: &Arc<RwLock<ScreenState>>,
+) -> Result<()> {
+    let moonraker_tx = moonraker_tx.lock().await;
+    let mut screen_state = screen_state.write().await;
+
+    match button {
+        SensorButton::Up => {
+            if screen_state.sensors_scroll > 0 {
+                screen_state.sensors_scroll -= 1;
+            }
+        }
+        SensorButton::Down => {
+            if screen_state.sensors_scroll + 1 < screen_state.sensors.len() {
+                screen_state.sensors_scroll += 1;
+            }
+        }
+        SensorButton::Line(idx) => {
+            let sensor = screen_state
+                .sensors
+                .get(screen_state.sensors_scroll + idx)
+                .cloned();
+
+            if let Some(sensor) = sensor {
+                screen_state.sensor_message = match sensor.settable() {
+                    true => format!("Enter target for {}", sensor.name()),
+                    false => format!("{} is read only", sensor.name()),
+                };
+                screen_state.selected_sensor = Some(sensor.object);
+            }
+        }
+        SensorButton::NextTool => {
+            let extruders = screen_state
+                .sensors
+                .iter()
+                .map(|sensor| sensor.object.clone())
+                .filter(|object| is_extruder(object))
+                .collect::<Vec<_>>();
+
+            if extruders.len() < 2 {
+                screen_state.sensor_message = "Only one extruder".to_string();
+                return Ok(());
+            }
+            // switching tools moves the toolhead on most multi-extruder printers
+            if screen_state.printer_state == PrinterState::Printing
+                || screen_state.printer_state == PrinterState::Paused
+            {
+                screen_state.sensor_message = "Tool can't be changed while printing".to_string();
+                return Ok(());
+            }
+
+            let current = extruders
+                .iter()
+                .position(|object| *object == screen_state.active_extruder)
+                .unwrap_or(0);
+            let next = (current + 1) % extruders.len();
+
+            // toolchanger configs do the switch in T0, T1 ... macros
+            let tool_macro = format!("T{}", next);
+            let script = match screen_state.macros.contains(&tool_macro) {
+                true => tool_macro,
+                false => format!("ACTIVATE_EXTRUDER EXTRUDER={}", extruders[next]),
+            };
+            send_gcode_script(&moonraker_tx, script)?;
+            screen_state.sensor_message = format!("Switching to {}", extruders[next]);
+        }
+        SensorButton::Off => {
+            set_selected_target(&moonraker_tx, &mut screen_state, 0)?;
+        }
+        SensorButton::Undefined(id) => {
+            println!("Undefined sensor button pressed with ID: {}", id);
+        }
+    }
+
+    Ok(())
+}
@@ -10,6 +10,11 @@ pub fn construct_get_page() -> Vec<u8> {
     construct_read_buf(0x0014, 1)
 }
 
+/// Sounds the screen buzzer (T5L system variable 0xA0, counted in 8ms units)
+pub fn construct_beep(duration_ms: u16) -> Vec<u8> {
+    construct_write_buf(0x00A0, &(duration_ms / 8).max(1).to_be_bytes())
+}
+
 pub fn construct_i16(address: u16, value: i16) -> Vec<u8> {
     construct_write_buf(address, &value.to_be_bytes())
 }
@@ -18,6 +23,100 @@ pub fn construct_text(address: u16, value: &str) -> Vec<u8> {
     construct_write_buf(address, value.as_bytes())
 }
 
+/// DGUS basic graphic "line segment" command (0x000A), every segment is (color, [xs, ys, xe, ye]).
+pub fn construct_line_segments(address: u16, segments: &[(u16, [u16; 4])]) -> Vec<Vec<u8>> {
+    let mut words = Vec::with_capacity(segments.len() * 5);
+    for (color, points) in segments {
+        words.push(*color);
+        words.extend_from_slice(points);
+    }
+
+    construct_basic_graphic(address, 0x000A, segments.len(), words)
+}
+
+/// DGUS basic graphic "rectangle fill" command (0x0004), every rectangle is ([xs, ys, xe, ye], color).
+pub fn construct_filled_rectangles(address: u16, rectangles: &[([u16; 4], u16)]) -> Vec<Vec<u8>> {
+    let mut words = Vec::with_capacity(rectangles.len() * 5);
+    for (points, color) in rectangles {
+        words.extend_from_slice(points);
+        words.push(*color);
+    }
+
+    construct_basic_graphic(address, 0x0004, rectangles.len(), words)
+}
+
+/// Split into several writes as one frame can only carry ~250 bytes,
+/// the header is written last so the screen doesn't draw half-written data.
+fn construct_basic_graphic(
+    address: u16,
+    command: u16,
+    count: usize,
+    mut words: Vec<u16>,
+) -> Vec<Vec<u8>> {
+    const WORDS_PER_FRAME: usize = 120;
+
+    words.push(0xFF00); // end of data
+
+    let mut frames = words
+        .chunks(WORDS_PER_FRAME)
+        .enumerate()
+        .map(|(idx, chunk)| {
+            let bytes = chunk
+                .iter()
+                .flat_map(|word| word.to_be_bytes())
+                .collect::<Vec<u8>>();
+            construct_write_buf(address + 2 + (idx * WORDS_PER_FRAME) as u16, &bytes)
+        })
+        .collect::<Vec<_>>();
+
+    let mut header = command.to_be_bytes().to_vec();
+    header.extend_from_slice(&(count as u16).to_be_bytes());
+    frames.push(construct_write_buf(address, &header));
+
+    frames
+}
+
+/// DGUS dynamic curve buffer write (0x0310), every channel is (channel 0-7, values).
+/// Values are appended to the channel ring buffers, 28 words per channel go into one frame
+/// so 4 channels fit under the ~250 byte frame limit.
+pub fn construct_curve_data(channels: &[(u8, Vec<u16>)]) -> Vec<Vec<u8>> {
+    const WORDS_PER_CHANNEL: usize = 28;
+
+    let length = channels
+        .iter()
+        .map(|(_, values)| values.len())
+        .max()
+        .unwrap_or(0);
+
+    (0..length)
+        .step_by(WORDS_PER_CHANNEL)
+        .map(|start| {
+            let blocks = channels
+                .iter()
+                .filter(|(_, values)| start < values.len())
+                .map(|(channel, values)| {
+                    let end = (start + WORDS_PER_CHANNEL).min(values.len());
+                    (*channel, &values[start..end])
+                })
+                .collect::<Vec<_>>();
+
+            let mut buffer = vec![0x5A, 0xA5, blocks.len() as u8, 0x00];
+            for (channel, values) in blocks {
+                buffer.push(channel);
+                buffer.push(values.len() as u8);
+                buffer.extend(values.iter().flat_map(|value| value.to_be_bytes()));
+            }
+
+            construct_write_buf(0x0310, &buffer)
+        })
+        .collect()
+}
+
+/// Empties a curve channel (write pointer and data length at 0x0300 + 2 * channel)
+pub fn construct_curve_reset(channel: u8) -> Vec<u8> {
+    construct_write_buf(0x0300 + 2 * channel as u16, &[0x00, 0x00, 0x00, 0x00])
+}
+
 pub fn construct_write_buf(address: u16, buffer: &[u8]) -> Vec<u8> {
     let data_length = buffer.len();
 
@@ -0,0 +1,64 @@
+use tokio::time::{Duration, Instant};
+
+use crate::{config::StatusLineConfig, screen_state::ScreenState};
+
+/// Length of the status line VP
+pub const STATUS_LINE_WIDTH: usize = 30;
+/// Spaces between the end and the start of a scrolling message
+const MARQUEE_GAP: usize = 5;
+
+/// `width` chars of `text` starting at `offset`, long text wraps around like a marquee
+pub fn marquee(text: &str, width: usize, offset: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+
+    let looped = format!("{}{}", text, " ".repeat(MARQUEE_GAP))
+        .chars()
+        .collect::<Vec<_>>();
+    (0..width)
+        .map(|idx| looped[(offset + idx) % looped.len()])
+        .collect()
+}
+
+/// New M117/SET_DISPLAY_TEXT message, the timeout starts again
+pub fn set_display_message(screen_state: &mut ScreenState, message: &str) {
+    let message = message.trim();
+    if message != screen_state.display_message {
+        screen_state.display_message = message.to_string();
+        screen_state.display_message_since = Instant::now();
+    }
+}
+
+/// Display message until it times out, then print_stats message if there is one
+fn current_message(screen_state: &ScreenState, config: &StatusLineConfig) -> String {
+    let timed_out = config.timeout_secs > 0
+        && screen_state.display_message_since.elapsed() > Duration::from_secs(config.timeout_secs);
+
+    if !screen_state.display_message.is_empty() && !timed_out {
+        return screen_state.display_message.clone();
+    }
+
+    screen_state.print_message.trim().to_string()
+}
+
+/// Computes the shown part of the status line and moves the marquee, called every screen update
+pub fn advance(screen_state: &mut ScreenState, config: &StatusLineConfig) {
+    let message = current_message(screen_state, config);
+    if message != screen_state.status_message {
+        screen_state.status_message = message;
+        screen_state.status_offset = 0;
+    }
+
+    let length = screen_state.status_message.chars().count();
+    screen_state.status_line = marquee(
+        &screen_state.status_message,
+        STATUS_LINE_WIDTH,
+        screen_state.status_offset,
+    );
+
+    if length > STATUS_LINE_WIDTH {
+        screen_state.status_offset =
+            (screen_state.status_offset + config.marquee_step) % (length + MARQUEE_GAP);
+    }
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -7,6 +8,19 @@ pub struct PrinterObjectsRoot {
     pub objects: Vec<String>,
 }
 
+/// Result of `printer.objects.query`, objects differ between queries
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrinterQueryRoot {
+    pub eventtime: f64,
+    pub status: HashMap<String, Value>,
+}
+
+/// Subscribe result for objects that are only known at runtime (discovered sensors)
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrinterStatusMapRoot {
+    pub status: HashMap<String, HashMap<String, Value>>,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PrinterStateRoot {
@@ -26,6 +40,32 @@ pub struct Status {
     pub print_stats: PrintStats,
     #[serde(rename = "toolhead")]
     pub toolhead: Toolhead,
+    #[serde(default)]
+    pub webhooks: Webhooks,
+    #[serde(rename = "gcode_move", default)]
+    pub gcode_move: GcodeMove,
+    #[serde(rename = "idle_timeout", default)]
+    pub idle_timeout: IdleTimeout,
+    /// Only with [fan] in klipper config
+    #[serde(default)]
+    pub fan: Fan,
+    /// Only with [exclude_object] in klipper config, parsed by `ExcludeObjects::update`
+    #[serde(rename = "exclude_object", default)]
+    pub exclude_object: HashMap<String, Value>,
+    /// Only with [bed_mesh] in klipper config, parsed by `BedMesh::update`
+    #[serde(rename = "bed_mesh", default)]
+    pub bed_mesh: HashMap<String, Value>,
+    /// Parsed by `ManualProbe::update`
+    #[serde(rename = "manual_probe", default)]
+    pub manual_probe: HashMap<String, Value>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhooks {
+    pub state: String,
+    #[serde(rename = "state_message")]
+    pub state_message: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -33,6 +73,40 @@ pub struct Status {
 pub struct Toolhead {
     #[serde(rename = "homed_axes")]
     pub homed_axes: String,
+    #[serde(default)]
+    pub position: Vec<f64>,
+    #[serde(rename = "axis_minimum", default)]
+    pub axis_minimum: Vec<f64>,
+    #[serde(rename = "axis_maximum", default)]
+    pub axis_maximum: Vec<f64>,
+    /// Active extruder object
+    #[serde(default)]
+    pub extruder: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdleTimeout {
+    pub state: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GcodeMove {
+    #[serde(rename = "gcode_position")]
+    pub gcode_position: Vec<f64>,
+    #[serde(rename = "speed_factor", default)]
+    pub speed_factor: f64,
+    #[serde(rename = "extrude_factor", default)]
+    pub extrude_factor: f64,
+    #[serde(rename = "homing_origin", default)]
+    pub homing_origin: Vec<f64>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fan {
+    pub speed: f64,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -47,6 +121,8 @@ pub struct DisplayStatus {
 pub struct Extruder {
     pub target: f64,
     pub temperature: f64,
+    #[serde(rename = "can_extrude", default)]
+    pub can_extrude: bool,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -86,8 +162,9 @@ pub struct FileMetadataRoot {
     pub result: FileMetadataResult,
 }
 
+/// Slicers fill different fields, missing ones are left at 0
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct FileMetadataResult {
     pub size: i64,
     pub modified: f64,
@@ -129,3 +206,69 @@ pub struct FileMetadataResult {
     pub job_id: String,
     pub filename: String,
 }
+
+/// Result of `server.temperature_store`, heater/sensor name -> last 20 minutes (one sample per second)
+pub type TemperatureStore = HashMap<String, TemperatureStoreEntry>;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TemperatureStoreEntry {
+    pub temperatures: Vec<f64>,
+    /// Missing for temperature sensors
+    pub targets: Vec<f64>,
+}
+
+/// Result of `server.history.get_job`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryJobRoot {
+    pub job: HistoryJob,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HistoryJob {
+    #[serde(rename = "job_id")]
+    pub job_id: String,
+    pub filename: String,
+    /// "completed", "cancelled", "error", "klippy_shutdown" ...
+    pub status: String,
+    #[serde(rename = "total_duration")]
+    pub total_duration: f64,
+    #[serde(rename = "print_duration")]
+    pub print_duration: f64,
+    #[serde(rename = "filament_used")]
+    pub filament_used: f64,
+    pub metadata: HistoryJobMetadata,
+}
+
+/// File metadata at the time of the job
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HistoryJobMetadata {
+    #[serde(rename = "estimated_time")]
+    pub estimated_time: Option<f64>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerInfo {
+    #[serde(rename = "klippy_connected")]
+    pub klippy_connected: bool,
+    #[serde(rename = "klippy_state")]
+    pub klippy_state: String,
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    #[serde(rename = "moonraker_version", default)]
+    pub moonraker_version: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrinterInfo {
+    pub state: String,
+    #[serde(rename = "state_message")]
+    pub state_message: String,
+    #[serde(rename = "software_version", default)]
+    pub software_version: String,
+}
@@ -0,0 +1,180 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use moonraker_api::{MoonrakerMethod, MoonrakerMsg, MoonrakerParam};
+use tokio::sync::{mpsc::UnboundedSender, RwLock};
+
+use crate::{
+    layout::SummaryButtonKeys,
+    moonraker::{MoonrakerTx, PrinterState},
+    progress::format_duration,
+    screen_state::ScreenState,
+    structs::HistoryJob,
+};
+
+/// Print time within this ratio of the slicer estimate counts as matching it
+const ESTIMATE_TOLERANCE: f64 = 0.05;
+
+/// Outcome of the last print, shown until acknowledged
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobSummary {
+    pub filename: String,
+    /// Complete, Cancelled or Error
+    pub state: PrinterState,
+    pub total_duration: f64,
+    pub print_duration: f64,
+    /// mm
+    pub filament_used: f64,
+    pub estimated_time: Option<f64>,
+}
+
+impl JobSummary {
+    /// Summary from print_stats, used until (or if) moonraker history answers
+    pub fn from_print_stats(screen_state: &ScreenState) -> Self {
+        JobSummary {
+            filename: screen_state.print_filename.clone(),
+            state: screen_state.printer_state,
+            total_duration: screen_state.total_duration,
+            print_duration: screen_state.print_duration,
+            filament_used: screen_state.filament_used,
+            estimated_time: Some(screen_state.file_estimated_time as f64)
+                .filter(|time| *time > 0.0),
+        }
+    }
+
+    /// History has the final values (print_stats may be reset by then) and the estimate of the job itself
+    pub fn update_from_job(&mut self, job: &HistoryJob) {
+        if job.filename != self.filename {
+            return;
+        }
+
+        self.total_duration = job.total_duration;
+        self.print_duration = job.print_duration;
+        self.filament_used = job.filament_used;
+        if let Some(estimated_time) = job.metadata.estimated_time.filter(|time| *time > 0.0) {
+            self.estimated_time = Some(estimated_time);
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self.state {
+            PrinterState::Complete => "Print complete",
+            PrinterState::Cancelled => "Print cancelled",
+            _ => "Print failed",
+        }
+    }
+
+    /// "12% slower than estimate (1:10:00)", empty if the file had no estimate
+    fn estimate_text(&self) -> String {
+        let estimated_time = match self.estimated_time {
+            Some(estimated_time) if self.state == PrinterState::Complete => estimated_time,
+            _ => return String::new(),
+        };
+
+        let ratio = self.print_duration / estimated_time - 1.0;
+        let percent = (ratio.abs() * 100.0).round();
+        let estimate = format_duration(estimated_time);
+
+        if ratio.abs() <= ESTIMATE_TOLERANCE {
+            format!("Matched slicer estimate ({})", estimate)
+        } else if ratio > 0.0 {
+            format!("{}% slower than estimate ({})", percent, estimate)
+        } else {
+            format!("{}% faster than estimate ({})", percent, estimate)
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        vec![
+            self.filename.clone(),
+            format!("Total time {}", format_duration(self.total_duration)),
+            format!("Filament {:.2} m", self.filament_used / 1000.0),
+            self.estimate_text(),
+        ]
+    }
+}
+
+/// Print ended, standby after a reset or a print started from the summary doesn't count
+pub fn is_finished(previous: PrinterState, state: PrinterState) -> bool {
+    matches!(previous, PrinterState::Printing | PrinterState::Paused)
+        && matches!(
+            state,
+            PrinterState::Complete | PrinterState::Cancelled | PrinterState::Error
+        )
+}
+
+/// Shows the summary from print_stats and asks history for the final values of the job
+pub fn job_finished(
+    screen_state: &mut ScreenState,
+    moonraker_tx: &UnboundedSender<MoonrakerMsg>,
+    job_id: Option<String>,
+) {
+    screen_state.job_summary = Some(JobSummary::from_print_stats(screen_state));
+
+    if let Some(job_id) = job_id {
+        _ = moonraker_tx.send(MoonrakerMsg::new_param_id(
+            MoonrakerMethod::HistoryGetJob,
+            MoonrakerParam::HistoryJob { uid: job_id },
+        ));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryButton {
+    Undefined(u16),
+
+    PrintAgain,
+    CoolDown,
+    Ok,
+}
+
+impl SummaryButton {
+    pub fn from_id(id: u16, keys: &SummaryButtonKeys) -> Self {
+        match id {
+            id if id == keys.print_again => SummaryButton::PrintAgain,
+            id if id == keys.cool_down => SummaryButton::CoolDown,
+            id if id == keys.ok => SummaryButton::Ok,
+            _ => SummaryButton::Undefined(id),
+        }
+    }
+}
+
+/// Every button acknowledges the summary, the screen update task then leaves the page
+pub async fn parse_summary_button(
+    button: SummaryButton,
+    moonraker_tx: &MoonrakerTx,
+    screen_state: &Arc<RwLock<ScreenState>>,
+) -> Result<()> {
+    let moonraker_tx = moonraker_tx.lock().await;
+    let mut screen_state = screen_state.write().await;
+
+    match button {
+        SummaryButton::PrintAgain => {
+            if let Some(summary) = screen_state.job_summary.take() {
+                moonraker_tx.send(MoonrakerMsg::new_param_id(
+                    MoonrakerMethod::PrintStart,
+                    MoonrakerParam::FilesMetadata {
+                        filename: summary.filename,
+                    },
+                ))?;
+            }
+        }
+        SummaryButton::CoolDown => {
+            moonraker_tx.send(MoonrakerMsg::new_param_id(
+                MoonrakerMethod::GcodeScript,
+                MoonrakerParam::GcodeScript {
+                    script: "TURN_OFF_HEATERS\nM107".to_string(),
+                },
+            ))?;
+            screen_state.job_summary = None;
+        }
+        SummaryButton::Ok => {
+            screen_state.job_summary = None;
+        }
+        SummaryButton::Undefined(id) => {
+            println!("Undefined summary button pressed with ID: {}", id);
+        }
+    }
+
+    Ok(())
+}
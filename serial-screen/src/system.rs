@@ -0,0 +1,205 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use moonraker_api::{
+    params::{
+        NetworkInterface, NetworkStats, NotifyProcStatUpdateRes, ProcStats, SystemCpuUsage,
+        SystemInfo, SystemMemory,
+    },
+    MoonrakerMethod, MoonrakerMsg,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Host stats for the system page, from `notify_proc_stat_update`, `machine.proc_stats`
+/// and `machine.system_info`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SystemStats {
+    pub cpu_temp: Option<f64>,
+    pub cpu_usage: Option<SystemCpuUsage>,
+    pub memory: Option<SystemMemory>,
+    /// Interface name -> (rx, tx) bytes per second
+    pub throughput: HashMap<String, (f64, f64)>,
+    /// Interface name -> addresses
+    pub interfaces: HashMap<String, NetworkInterface>,
+    /// Uptime is counted locally, moonraker sends it only in `machine.proc_stats`
+    pub boot_time: Option<Instant>,
+    /// `software_version` of printer.info
+    pub klipper_version: String,
+    /// `moonraker_version` of server.info
+    pub moonraker_version: String,
+
+    /// Byte counters and moonraker time of the last notification
+    pub network: HashMap<String, NetworkStats>,
+    pub time: Option<f64>,
+}
+
+impl SystemStats {
+    pub fn proc_stat_update(&mut self, stats: NotifyProcStatUpdateRes) {
+        let time = stats.moonraker_stats.time;
+
+        self.throughput = match self.time {
+            Some(last_time) if time > last_time => stats
+                .network
+                .iter()
+                .filter_map(|(name, counters)| {
+                    let last = self.network.get(name)?;
+                    // counters go back when an interface is reset
+                    let rx = (counters.rx_bytes - last.rx_bytes).max(0) as f64;
+                    let tx = (counters.tx_bytes - last.tx_bytes).max(0) as f64;
+                    let secs = time - last_time;
+
+                    Some((name.clone(), (rx / secs, tx / secs)))
+                })
+                .collect(),
+            _ => HashMap::new(),
+        };
+
+        self.cpu_temp = stats.cpu_temp;
+        self.cpu_usage = Some(stats.system_cpu_usage);
+        self.memory = Some(stats.system_memory);
+        self.network = stats.network;
+        self.time = Some(time);
+    }
+
+    /// Result of `machine.proc_stats`, fills the page before the first notification
+    pub fn set_proc_stats(&mut self, stats: ProcStats) {
+        self.cpu_temp = stats.cpu_temp;
+        self.cpu_usage = Some(stats.system_cpu_usage);
+        self.memory = Some(stats.system_memory);
+        self.boot_time = Instant::now().checked_sub(Duration::from_secs_f64(stats.system_uptime));
+    }
+
+    pub fn set_system_info(&mut self, info: SystemInfo) {
+        self.interfaces = info.network;
+    }
+}
+
+/// System info is refreshed every time the page is opened (addresses may change)
+pub fn request_system_info(moonraker_tx: &UnboundedSender<MoonrakerMsg>) -> Result<()> {
+    moonraker_tx.send(MoonrakerMsg::new_with_method_and_id(
+        MoonrakerMethod::MachineSystemInfo,
+    ))?;
+    moonraker_tx.send(MoonrakerMsg::new_with_method_and_id(
+        MoonrakerMethod::MachineProcStats,
+    ))?;
+
+    Ok(())
+}
+
+/// "CPU 52C  Load 12%", temperature is left out if the host has no sensor
+pub fn cpu_text(stats: &SystemStats) -> String {
+    let usage = match &stats.cpu_usage {
+        Some(usage) => usage.cpu,
+        None => return String::new(),
+    };
+
+    match stats.cpu_temp {
+        Some(temp) => format!("CPU {:.0}C  Load {:.0}%", temp, usage),
+        None => format!("CPU Load {:.0}%", usage),
+    }
+}
+
+/// "Cores 10% 15% 8% 12%"
+pub fn cores_text(stats: &SystemStats) -> String {
+    let cores = match &stats.cpu_usage {
+        Some(usage) => usage.cores(),
+        None => return String::new(),
+    };
+
+    let cores = cores
+        .iter()
+        .map(|usage| format!("{:.0}%", usage))
+        .collect::<Vec<_>>();
+    format!("Cores {}", cores.join(" "))
+}
+
+/// "RAM 412/3792 MB (11%)", moonraker reports kB
+pub fn memory_text(stats: &SystemStats) -> String {
+    let memory = match &stats.memory {
+        Some(memory) if memory.total > 0 => memory,
+        _ => return String::new(),
+    };
+
+    format!(
+        "RAM {}/{} MB ({:.0}%)",
+        memory.used / 1024,
+        memory.total / 1024,
+        memory.used as f64 / memory.total as f64 * 100.0
+    )
+}
+
+/// "Up 3d 04:12" or "Up 04:12" (hours:minutes)
+pub fn uptime_text(stats: &SystemStats) -> String {
+    let secs = match stats.boot_time {
+        Some(boot_time) => boot_time.elapsed().as_secs(),
+        None => return String::new(),
+    };
+    let (days, hours, minutes) = (secs / 86400, (secs % 86400) / 3600, (secs % 3600) / 60);
+
+    match days {
+        0 => format!("Up {:0>2}:{:0>2}", hours, minutes),
+        _ => format!("Up {}d {:0>2}:{:0>2}", days, hours, minutes),
+    }
+}
+
+/// "Klipper v0.12.0-114-g6a4c3e1", empty until the version is known
+pub fn version_text(name: &str, version: &str) -> String {
+    match version.is_empty() {
+        true => String::new(),
+        false => format!("{} {}", name, version),
+    }
+}
+
+fn format_rate(bytes_per_sec: f64) -> String {
+    match bytes_per_sec {
+        rate if rate < 1024.0 => format!("{:.0} B/s", rate),
+        rate if rate < 1024.0 * 1024.0 => format!("{:.1} KB/s", rate / 1024.0),
+        rate => format!("{:.1} MB/s", rate / 1024.0 / 1024.0),
+    }
+}
+
+/// First IPv4 address, IPv6 only when there is none (link local ones are skipped)
+fn address(interface: &NetworkInterface) -> &str {
+    let addresses = interface
+        .ip_addresses
+        .iter()
+        .filter(|address| !address.is_link_local);
+
+    addresses
+        .clone()
+        .find(|address| address.family == "ipv4")
+        .or_else(|| addresses.clone().next())
+        .map_or("", |address| address.address.as_str())
+}
+
+/// "wlan0   192.168.1.27    rx 12.3 KB/s tx 1.2 KB/s" for every interface except loopback,
+/// throughput needs two proc stat notifications (`moonraker.proc_stats`)
+pub fn network_lines(stats: &SystemStats) -> Vec<String> {
+    let mut names = stats
+        .throughput
+        .keys()
+        .chain(stats.interfaces.keys())
+        .filter(|name| name.as_str() != "lo")
+        .cloned()
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+
+    names
+        .iter()
+        .map(|name| {
+            let address = stats.interfaces.get(name).map_or("", address);
+            let line = format!("{: <7} {: <15}", name, address);
+
+            match stats.throughput.get(name) {
+                Some((rx, tx)) => {
+                    format!("{} rx {} tx {}", line, format_rate(*rx), format_rate(*tx))
+                }
+                None => line.trim_end().to_string(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,107 @@
+use tokio::time::{Duration, Instant};
+
+use crate::{
+    config::TempGraphConfig,
+    moonraker::KlippyState,
+    screen_state::ScreenState,
+    structs::{TemperatureStore, TemperatureStoreEntry},
+};
+
+/// Moonraker's temperature_store has one sample per second
+const STORE_SAMPLE_SECS: u64 = 1;
+/// Screen update runs every second but not exactly, a sample a bit early still counts
+const TICK_SLACK: Duration = Duration::from_millis(500);
+
+/// Nozzle, nozzle target, bed, bed target
+pub type TempPoint = [u16; 4];
+
+/// Samples for the DGUS curve channels, the screen keeps the history in its curve buffers
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TempGraph {
+    /// temperature_store series waiting to be downsampled, in `TempPoint` order
+    backfill: Option<[Vec<f64>; 4]>,
+    /// Points not sent to the screen yet
+    pub pending: Vec<TempPoint>,
+    /// Curve buffers are cleared before backfilled history is written
+    pub reset: bool,
+    last_sample: Option<Instant>,
+}
+
+impl TempGraph {
+    /// History from `server.temperature_store`, replaces what the screen has
+    pub fn set_history(&mut self, store: &TemperatureStore) {
+        let empty = TemperatureStoreEntry::default();
+        let extruder = store.get("extruder").unwrap_or(&empty);
+        let heater_bed = store.get("heater_bed").unwrap_or(&empty);
+
+        self.backfill = Some([
+            extruder.temperatures.clone(),
+            extruder.targets.clone(),
+            heater_bed.temperatures.clone(),
+            heater_bed.targets.clone(),
+        ]);
+    }
+}
+
+fn point_value(temp: f64) -> u16 {
+    temp.round().clamp(0.0, u16::MAX as f64) as u16
+}
+
+/// Every `sample_secs`-th sample of the history, newest last, at most `backfill_points`
+fn downsample(history: &[Vec<f64>; 4], config: &TempGraphConfig) -> Vec<TempPoint> {
+    // bed history is missing without heater_bed, its series stay at 0
+    let length = history[0].len();
+    let value =
+        |series: &Vec<f64>, idx: usize| point_value(series.get(idx).copied().unwrap_or(0.0));
+    let step = (config.sample_secs / STORE_SAMPLE_SECS).max(1) as usize;
+
+    let mut points = (0..length)
+        .rev()
+        .step_by(step)
+        .take(config.backfill_points)
+        .map(|idx| {
+            [
+                value(&history[0], idx),
+                value(&history[1], idx),
+                value(&history[2], idx),
+                value(&history[3], idx),
+            ]
+        })
+        .collect::<Vec<_>>();
+    points.reverse();
+
+    points
+}
+
+/// Queues backfilled history or a live sample every `sample_secs`, called every screen update
+pub fn advance(screen_state: &mut ScreenState, config: &TempGraphConfig) {
+    let graph = &mut screen_state.temp_graph;
+
+    if let Some(history) = graph.backfill.take() {
+        graph.pending = downsample(&history, config);
+        graph.reset = true;
+        graph.last_sample = Some(Instant::now());
+        return;
+    }
+
+    // temperatures are zeroed while klippy is away, that's not worth drawing
+    if screen_state.klippy_state != KlippyState::Ready {
+        return;
+    }
+
+    let interval = Duration::from_secs(config.sample_secs);
+    if graph
+        .last_sample
+        .is_some_and(|last| last.elapsed() + TICK_SLACK < interval)
+    {
+        return;
+    }
+    graph.last_sample = Some(Instant::now());
+
+    graph.pending.push([
+        point_value(screen_state.nozzle_temp as f64),
+        point_value(screen_state.target_nozzle_temp as f64),
+        point_value(screen_state.bed_temp as f64),
+        point_value(screen_state.target_bed_temp as f64),
+    ]);
+}
@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use tokio::sync::RwLock;
+
+use crate::{
+    config::ConfigRef,
+    layout::TuneButtonKeys,
+    moonraker::{MoonrakerTx, PrinterState},
+    preheat,
+    screen_state::ScreenState,
+    utils::send_gcode_script,
+};
+
+/// M220 range in percent
+const SPEED_RANGE: (i16, i16) = (10, 300);
+/// M221 range in percent
+const FLOW_RANGE: (i16, i16) = (50, 150);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TuneButton {
+    Undefined(u16),
+
+    SpeedUp,
+    SpeedDown,
+    FlowUp,
+    FlowDown,
+    FanUp,
+    FanDown,
+    NozzleUp,
+    NozzleDown,
+    BedUp,
+    BedDown,
+    ZUp,
+    ZDown,
+    SaveZOffset,
+}
+
+impl TuneButton {
+    pub fn from_id(id: u16, keys: &TuneButtonKeys) -> Self {
+        match id {
+            id if id == keys.speed_up => TuneButton::SpeedUp,
+            id if id == keys.speed_down => TuneButton::SpeedDown,
+            id if id == keys.flow_up => TuneButton::FlowUp,
+            id if id == keys.flow_down => TuneButton::FlowDown,
+            id if id == keys.fan_up => TuneButton::FanUp,
+            id if id == keys.fan_down => TuneButton::FanDown,
+            id if id == keys.nozzle_up => TuneButton::NozzleUp,
+            id if id == keys.nozzle_down => TuneButton::NozzleDown,
+            id if id == keys.bed_up => TuneButton::BedUp,
+            id if id == keys.bed_down => TuneButton::BedDown,
+            id if id == keys.z_up => TuneButton::ZUp,
+            id if id == keys.z_down => TuneButton::ZDown,
+            id if id == keys.save_z_offset => TuneButton::SaveZOffset,
+            _ => TuneButton::Undefined(id),
+        }
+    }
+}
+
+/// `gcode_move` factors are fractions (1.0 = 100%)
+pub fn factor_percent(factor: f64) -> i16 {
+    (factor * 100.0).round() as i16
+}
+
+/// Z offset applied with `SET_GCODE_OFFSET` is the Z of `gcode_move.homing_origin`
+pub fn z_offset_text(z_offset: f64) -> String {
+    format!("{:+.3} mm", z_offset)
+}
+
+/// Moves the babystep into probe `z_offset` (or endstop position), SAVE_CONFIG restarts klipper
+fn save_z_offset_script(has_probe: bool) -> String {
+    let apply = match has_probe {
+        true => "Z_OFFSET_APPLY_PROBE",
+        false => "Z_OFFSET_APPLY_ENDSTOP",
+    };
+
+    format!("{}\nSAVE_CONFIG", apply)
+}
+
+fn step_value(value: i16, step: i16, range: (i16, i16)) -> i16 {
+    (value + step).clamp(range.0, range.1)
+}
+
+pub async fn parse_tune_button(
+    button: TuneButton,
+    moonraker_tx: &MoonrakerTx,
+    screen_state: &Arc<RwLock<ScreenState>>,
+    config: &ConfigRef,
+) -> Result<()> {
+    let moonraker_tx = moonraker_tx.lock().await;
+    let mut screen_state = screen_state.write().await;
+    let tune = config.read().await.tune.clone();
+
+    let speed_step = tune.speed_step as i16;
+    let flow_step = tune.flow_step as i16;
+    let fan_step = tune.fan_step as i16;
+    let temp_step = tune.temp_step as i16;
+
+    match button {
+        TuneButton::SpeedUp | TuneButton::SpeedDown => {
+            let step = match button {
+                TuneButton::SpeedUp => speed_step,
+                _ => -speed_step,
+            };
+            let speed = step_value(screen_state.speed_factor, step, SPEED_RANGE);
+
+            send_gcode_script(&moonraker_tx, format!("M220 S{}", speed))?;
+            screen_state.tune_message = format!("Speed {}%", speed);
+        }
+        TuneButton::FlowUp | TuneButton::FlowDown => {
+            let step = match button {
+                TuneButton::FlowUp => flow_step,
+                _ => -flow_step,
+            };
+            let flow = step_value(screen_state.extrude_factor, step, FLOW_RANGE);
+
+            send_gcode_script(&moonraker_tx, format!("M221 S{}", flow))?;
+            screen_state.tune_message = format!("Flow {}%", flow);
+        }
+        TuneButton::FanUp | TuneButton::FanDown => {
+            let step = match button {
+                TuneButton::FanUp => fan_step,
+                _ => -fan_step,
+            };
+            let fan = step_value(screen_state.fan_speed, step, (0, 100));
+
+            send_gcode_script(&moonraker_tx, format!("M106 S{}", (fan * 255 + 50) / 100))?;
+            screen_state.tune_message = format!("Fan {}%", fan);
+        }
+        TuneButton::NozzleUp | TuneButton::NozzleDown | TuneButton::BedUp | TuneButton::BedDown => {
+            let (heater, current) = match button {
+                TuneButton::NozzleUp | TuneButton::NozzleDown => {
+                    ("extruder", screen_state.target_nozzle_temp)
+                }
+                _ => ("heater_bed", screen_state.target_bed_temp),
+            };
+            let target = match button {
+                TuneButton::NozzleUp | TuneButton::BedUp => current + temp_step,
+                _ => (current - temp_step).max(0),
+            };
+
+            if let Err(e) = preheat::validate_target(&screen_state, heater, target) {
+                screen_state.tune_message = e.to_string();
+                return Ok(());
+            }
+
+            send_gcode_script(&moonraker_tx, preheat::set_heater_script(heater, target))?;
+            screen_state.tune_message = format!("{} set to {}", heater, target);
+        }
+        TuneButton::ZUp | TuneButton::ZDown => {
+            let step = match button {
+                TuneButton::ZUp => tune.z_step,
+                _ => -tune.z_step,
+            };
+
+            send_gcode_script(
+                &moonraker_tx,
+                format!("SET_GCODE_OFFSET Z_ADJUST={:+.3} MOVE=1", step),
+            )?;
+            screen_state.tune_message = String::new();
+        }
+        TuneButton::SaveZOffset => {
+            // SAVE_CONFIG restarts klipper, so it would abort the print
+            if screen_state.printer_state == PrinterState::Printing
+                || screen_state.printer_state == PrinterState::Paused
+            {
+                screen_state.tune_message = "Save Z offset after the print".to_string();
+                return Ok(());
+            }
+            if screen_state.z_offset.abs() < 0.0005 {
+                screen_state.tune_message = "Z offset is not adjusted".to_string();
+                return Ok(());
+            }
+
+            send_gcode_script(&moonraker_tx, save_z_offset_script(screen_state.has_probe))?;
+            screen_state.tune_message = "Z offset saved, restarting".to_string();
+        }
+        TuneButton::Undefined(id) => {
+            println!("Undefined tune button pressed with ID: {}", id);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use moonraker_api::params::UpdateStatus;
+use tokio::sync::{mpsc::UnboundedSender, Mutex, RwLock};
+
+use crate::{
+    layout::{Layout, UpdateButtonKeys},
+    moonraker::{MoonrakerTx, PrinterState},
+    screen_state::ScreenState,
+};
+
+/// Component managed by moonraker's update_manager
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateEntry {
+    pub name: String,
+    pub version: String,
+    pub remote_version: String,
+    pub has_update: bool,
+}
+
+impl UpdateEntry {
+    pub fn line(&self) -> String {
+        if self.has_update {
+            format!("{}: {} > {}", self.name, self.version, self.remote_version)
+        } else {
+            format!("{}: {} (up to date)", self.name, self.version)
+        }
+    }
+}
+
+pub fn entries_from_status(status: UpdateStatus) -> Vec<UpdateEntry> {
+    let mut entries = status
+        .version_info
+        .into_iter()
+        .map(|(name, info)| {
+            let has_update = info.has_update();
+            let (version, remote_version) = match info.package_count {
+                Some(package_count) => (String::new(), format!("{} packages", package_count)),
+                None => (
+                    info.version.unwrap_or_else(|| "?".to_string()),
+                    info.remote_version.unwrap_or_else(|| "?".to_string()),
+                ),
+            };
+
+            UpdateEntry {
+                name,
+                version,
+                remote_version,
+                has_update,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // components with updates first
+    entries.sort_by(|a, b| b.has_update.cmp(&a.has_update).then(a.name.cmp(&b.name)));
+    entries
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateButton {
+    Undefined(u16),
+
+    Up,
+    Down,
+    Line(usize),
+    Refresh,
+    UpgradeAll,
+}
+
+impl UpdateButton {
+    pub fn from_id(id: u16, keys: &UpdateButtonKeys) -> Self {
+        if let Some(idx) = keys.lines.iter().position(|&code| code == id) {
+            return UpdateButton::Line(idx);
+        }
+
+        match id {
+            id if id == keys.up => UpdateButton::Up,
+            id if id == keys.down => UpdateButton::Down,
+            id if id == keys.refresh => UpdateButton::Refresh,
+            id if id == keys.upgrade_all => UpdateButton::UpgradeAll,
+            _ => UpdateButton::Undefined(id),
+        }
+    }
+}
+
+pub async fn parse_update_button(
+    button: UpdateButton,
+    moonraker_tx: &MoonrakerTx,
+    screen_state: &Arc<RwLock<ScreenState>>,
+    serial_tx: &Arc<Mutex<UnboundedSender<Vec<u8>>>>,
+    layout: &Layout,
+) -> Result<()> {
+    let serial_tx = serial_tx.lock().await;
+    let moonraker_tx = moonraker_tx.lock().await;
+    let mut screen_state = screen_state.write().await;
+
+    // moonraker refuses to update while printing anyway
+    let printing = screen_state.printer_state == PrinterState::Printing
+        || screen_state.printer_state == PrinterState::Paused;
+
+    match button {
+        UpdateButton::Up => {
+            if screen_state.updates_scroll > 0 {
+                screen_state.updates_scroll -= 1;
+                screen_state.update_updates_list(&serial_tx, layout).await?;
+            }
+        }
+        UpdateButton::Down => {
+            if screen_state.updates_scroll + 1 < screen_state.updates.len() {
+                screen_state.updates_scroll += 1;
+                screen_state.update_updates_list(&serial_tx, layout).await?;
+            }
+        }
+        UpdateButton::Line(idx) => {
+            let entry = screen_state.updates.get(screen_state.updates_scroll + idx);
+
+            if let Some(entry) = entry {
+                if !entry.has_update || printing {
+                    return Ok(());
+                }
+
+                moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
+                    moonraker_api::MoonrakerMethod::MachineUpdateUpgrade,
+                    moonraker_api::MoonrakerParam::MachineUpdate {
+                        name: Some(entry.name.clone()),
+                    },
+                ))?;
+            }
+        }
+        UpdateButton::Refresh => {
+            if printing {
+                return Ok(());
+            }
+
+            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
+                moonraker_api::MoonrakerMethod::MachineUpdateRefresh,
+                moonraker_api::MoonrakerParam::MachineUpdate { name: None },
+            ))?;
+            screen_state.update_message = "Checking for updates...".to_string();
+        }
+        UpdateButton::UpgradeAll => {
+            if printing || !screen_state.updates.iter().any(|entry| entry.has_update) {
+                return Ok(());
+            }
+
+            moonraker_tx.send(moonraker_api::MoonrakerMsg::new_param_id(
+                moonraker_api::MoonrakerMethod::MachineUpdateUpgrade,
+                moonraker_api::MoonrakerParam::MachineUpdate { name: None },
+            ))?;
+        }
+        UpdateButton::Undefined(id) => {
+            println!("Undefined update button pressed with ID: {}", id);
+        }
+    }
+
+    Ok(())
+}
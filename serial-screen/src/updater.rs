@@ -1,8 +1,14 @@
 use anyhow::Result;
 use reqwest::header::HeaderMap;
+use semver::Version;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{os::unix::fs::PermissionsExt, path::Path};
 
-use crate::version::VERSION;
+use crate::{
+    config::{ConfigRef, UpdateChannel},
+    version::VERSION,
+};
 
 #[cfg(any(target_arch = "x86_64"))]
 pub const PLATFORM: &str = "x86_64";
@@ -13,8 +19,29 @@ pub const PLATFORM: &str = "arm-gnueabihf";
 #[cfg(any(target_arch = "aarch64"))]
 pub const PLATFORM: &str = "aarch64";
 
+/// Minisign public key of release signatures, embedded by `publish.sh`
+const BUILTIN_PUBLIC_KEY: Option<&str> = option_env!("SERIAL_SCREEN_UPDATE_KEY");
+
+const INSTALL_PATH: &str = "/opt/serial-screen/serial-screen";
+const UPDATE_PATH: &str = "/opt/serial-screen/serial-screen.new";
+const ROLLBACK_PATH: &str = "/opt/serial-screen/serial-screen.old";
+/// Exists (and holds start attempts count) until updated binary proves it can run
+const PENDING_MARKER_PATH: &str = "/opt/serial-screen/update-pending";
+/// Version that was rolled back, it's not installed again (newer releases are)
+const SKIPPED_VERSION_PATH: &str = "/opt/serial-screen/skipped-version";
+
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+const SIGNATURE_ASSET: &str = "SHA256SUMS.minisig";
+
+/// Updated binary is rolled back after this many failed starts
+const MAX_START_ATTEMPTS: u32 = 3;
+/// Updated binary is considered working after running this long
+const HEALTHY_AFTER_SECS: u64 = 60;
+
 #[derive(Debug, Clone, Deserialize)]
-struct GithubReleasesRoot {
+struct GithubRelease {
+    tag_name: String,
+    draft: bool,
     assets: Vec<GithubReleaseAsset>,
 }
 
@@ -25,10 +52,70 @@ struct GithubReleaseAsset {
 }
 
 const GITHUB_RELEASES_URL: &str =
-    "https://api.github.com/repos/filipton/dgus-moonraker-screen/releases/latest";
+    "https://api.github.com/repos/filipton/dgus-moonraker-screen/releases";
+
+/// Must be called before the config is loaded when running (a new release may reject it).
+/// Every start counts as an attempt until the update is confirmed.
+pub fn check_pending_update() -> Result<()> {
+    if count_start_attempt()? {
+        tokio::spawn(async {
+            tokio::time::sleep(std::time::Duration::from_secs(HEALTHY_AFTER_SECS)).await;
+            if std::fs::remove_file(PENDING_MARKER_PATH).is_ok() {
+                println!("Update to {} confirmed", VERSION);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Rolls back to the previous binary if the updated one keeps failing to start,
+/// returns whether an update is still pending
+pub fn count_start_attempt() -> Result<bool> {
+    let attempts = match std::fs::read_to_string(PENDING_MARKER_PATH) {
+        Ok(attempts) => attempts.trim().parse::<u32>().unwrap_or(0) + 1,
+        Err(_) => return Ok(false),
+    };
+
+    if attempts > MAX_START_ATTEMPTS {
+        println!(
+            "\x1b[91mUpdated binary failed to start {} times, rolling back!\x1b[0m",
+            MAX_START_ATTEMPTS
+        );
 
-pub async fn check_for_updates() {
-    let file_exists = tokio::fs::try_exists("/opt/serial-screen/serial-screen").await;
+        if let Err(e) = rollback() {
+            // nothing to go back to, keep this version instead of failing every start
+            println!("\x1b[91mFailed to roll back: {}\x1b[0m", e);
+            _ = std::fs::remove_file(PENDING_MARKER_PATH);
+            return Ok(false);
+        }
+        std::process::exit(1);
+    }
+
+    std::fs::write(PENDING_MARKER_PATH, attempts.to_string())?;
+    Ok(true)
+}
+
+/// Restores binary that was replaced by the last update
+pub fn rollback() -> Result<()> {
+    if !Path::new(ROLLBACK_PATH).exists() {
+        return Err(anyhow::anyhow!("No previous version at {}", ROLLBACK_PATH));
+    }
+
+    // this binary is the one being rolled back
+    std::fs::write(SKIPPED_VERSION_PATH, VERSION)?;
+    std::fs::rename(ROLLBACK_PATH, INSTALL_PATH)?;
+    _ = std::fs::remove_file(PENDING_MARKER_PATH);
+    println!(
+        "Restored previous version, restart serial-screen to use it ({} won't be installed again)",
+        VERSION
+    );
+
+    Ok(())
+}
+
+pub async fn check_for_updates(config: ConfigRef) {
+    let file_exists = tokio::fs::try_exists(INSTALL_PATH).await;
     if file_exists.is_err() || file_exists.unwrap() == false {
         println!("\x1b[93mNot checking for updates, not installed through setup script!\x1b[0m");
         return;
@@ -42,53 +129,301 @@ pub async fn check_for_updates() {
     )]);
 
     tokio::task::spawn(async move {
-        let resp = client
-            .get(GITHUB_RELEASES_URL)
-            .headers(headers.clone())
-            .send()
-            .await;
+        loop {
+            let updates = config.read().await.updates.clone();
+            let public_key = updates
+                .public_key
+                .or_else(|| BUILTIN_PUBLIC_KEY.map(|k| k.to_string()));
 
-        if let Ok(resp) = resp {
-            let json = resp.json::<GithubReleasesRoot>().await;
-            if let Ok(releases) = json {
-                let current_arch_release = releases
-                    .assets
-                    .iter()
-                    .find(|asset| asset.name.contains(PLATFORM));
-
-                if let Some(current_arch_release) = current_arch_release {
-                    if current_arch_release.name != VERSION {
-                        let res = update(&client, headers, &current_arch_release.browser_download_url)
-                            .await;
-
-                        println!("Failed to update: {:?}", res);
-                    }
+            if updates.channel != UpdateChannel::Off {
+                let res = check_and_update(&client, &headers, updates.channel, public_key).await;
+                if let Err(e) = res {
+                    println!("Failed to update: {}", e);
                 }
             }
-        } else {
-            println!("Failed to check for updates: {:?}", resp);
-        }
 
-        tokio::time::sleep(std::time::Duration::from_secs(60 * 5)).await;
+            tokio::time::sleep(std::time::Duration::from_secs(
+                60 * updates.check_interval_min,
+            ))
+            .await;
+        }
     });
 }
 
-async fn update(client: &reqwest::Client, headers: HeaderMap, url: &str) -> Result<()> {
+async fn check_and_update(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    channel: UpdateChannel,
+    public_key: Option<String>,
+) -> Result<()> {
+    let release = match get_newest_release(client, headers, channel).await? {
+        Some(release) => release,
+        None => return Ok(()),
+    };
+
+    let current_version = Version::parse(VERSION)?;
+    let release_version = parse_tag(&release.tag_name)?;
+    if release_version <= current_version {
+        return Ok(());
+    }
+    if skipped_version().as_ref() == Some(&release_version) {
+        return Ok(());
+    }
+
+    let public_key = public_key.ok_or_else(|| {
+        anyhow::anyhow!(
+            "{} is available, but there is no public key to verify it (set updates.public_key)",
+            release_version
+        )
+    })?;
+    let public_key = minisign_verify::PublicKey::from_base64(&public_key)
+        .map_err(|e| anyhow::anyhow!("Invalid public key: {}", e))?;
+
+    let binary_asset = find_asset(&release, |name| name.contains(PLATFORM))?;
+    let checksums_asset = find_asset(&release, |name| name == CHECKSUMS_ASSET)?;
+    let signature_asset = find_asset(&release, |name| name == SIGNATURE_ASSET)?;
+
+    let checksums = download(client, headers, &checksums_asset.browser_download_url).await?;
+    let signature = download(client, headers, &signature_asset.browser_download_url).await?;
+    let signature = minisign_verify::Signature::decode(std::str::from_utf8(&signature)?)
+        .map_err(|e| anyhow::anyhow!("Invalid signature: {}", e))?;
+    public_key
+        .verify(&checksums, &signature, false)
+        .map_err(|e| anyhow::anyhow!("{} signature verification failed: {}", CHECKSUMS_ASSET, e))?;
+
+    let expected_checksum = find_checksum(std::str::from_utf8(&checksums)?, &binary_asset.name)?;
+
+    println!(
+        "Updating from {} to {}...",
+        current_version, release_version
+    );
+    let binary = download(client, headers, &binary_asset.browser_download_url).await?;
+    let checksum = hex::encode(Sha256::digest(&binary));
+    if checksum != expected_checksum {
+        return Err(anyhow::anyhow!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            binary_asset.name,
+            expected_checksum,
+            checksum
+        ));
+    }
+
+    install(&binary, &release_version).await?;
+
+    println!("Updated to {}, restarting...", release_version);
+    std::process::exit(0);
+}
+
+async fn get_newest_release(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    channel: UpdateChannel,
+) -> Result<Option<GithubRelease>> {
+    match channel {
+        UpdateChannel::Off => Ok(None),
+        UpdateChannel::Stable => {
+            let release = client
+                .get(format!("{}/latest", GITHUB_RELEASES_URL))
+                .headers(headers.clone())
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<GithubRelease>()
+                .await?;
+
+            Ok(Some(release))
+        }
+        UpdateChannel::Prerelease => {
+            let releases = client
+                .get(GITHUB_RELEASES_URL)
+                .headers(headers.clone())
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<Vec<GithubRelease>>()
+                .await?;
+
+            Ok(newest_release(releases))
+        }
+    }
+}
+
+/// Highest version among published releases (prereleases included), tags that aren't
+/// semver are ignored
+fn newest_release(releases: Vec<GithubRelease>) -> Option<GithubRelease> {
+    releases
+        .into_iter()
+        .filter(|release| !release.draft)
+        .filter_map(|release| Some((parse_tag(&release.tag_name).ok()?, release)))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| release)
+}
+
+fn skipped_version() -> Option<Version> {
+    let version = std::fs::read_to_string(SKIPPED_VERSION_PATH).ok()?;
+    Version::parse(version.trim()).ok()
+}
+
+fn parse_tag(tag: &str) -> Result<Version> {
+    Version::parse(tag.trim_start_matches('v'))
+        .map_err(|e| anyhow::anyhow!("Invalid release tag {}: {}", tag, e))
+}
+
+fn find_asset(
+    release: &GithubRelease,
+    predicate: impl Fn(&str) -> bool,
+) -> Result<&GithubReleaseAsset> {
+    release
+        .assets
+        .iter()
+        .find(|asset| predicate(&asset.name))
+        .ok_or_else(|| anyhow::anyhow!("Release {} is missing an asset", release.tag_name))
+}
+
+/// Parses `sha256sum` output format: `<hex checksum>  <file name>`
+fn find_checksum(checksums: &str, asset_name: &str) -> Result<String> {
+    checksums
+        .lines()
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .find(|(_, name)| name.trim().trim_start_matches('*') == asset_name)
+        .map(|(checksum, _)| checksum.to_lowercase())
+        .ok_or_else(|| anyhow::anyhow!("No checksum for {} in {}", asset_name, CHECKSUMS_ASSET))
+}
+
+async fn download(client: &reqwest::Client, headers: &HeaderMap, url: &str) -> Result<Vec<u8>> {
     let bytes = client
         .get(url)
-        .headers(headers)
+        .headers(headers.clone())
         .send()
         .await?
+        .error_for_status()?
         .bytes()
         .await?;
 
-    println!("Updating to latest version...");
+    Ok(bytes.to_vec())
+}
 
-    tokio::fs::write("/opt/serial-screen/serial-screen-update", bytes).await?;
-    std::process::Command::new("bash")
-        .arg("-c")
-        .arg("'(sleep 1 ; mv /opt/serial-screen/serial-screen-update /opt/serial-screen/serial-screen ; chmod +x /opt/serial-screen/serial-screen) &'")
-        .spawn()?;
+/// Replaces the binary with a single rename (atomic on the same filesystem, there is always
+/// a binary at `INSTALL_PATH`), a copy of the old one is kept at `ROLLBACK_PATH`
+async fn install(binary: &[u8], version: &Version) -> Result<()> {
+    {
+        let mut file = tokio::fs::File::create(UPDATE_PATH).await?;
+        tokio::io::AsyncWriteExt::write_all(&mut file, binary).await?;
+        file.sync_all().await?;
+    }
+    tokio::fs::set_permissions(UPDATE_PATH, std::fs::Permissions::from_mode(0o755)).await?;
 
-    std::process::exit(0);
+    // make sure it runs on this machine before replacing anything
+    let output = tokio::process::Command::new(UPDATE_PATH)
+        .arg("--version")
+        .output()
+        .await?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    if !output.contains(&version.to_string()) {
+        _ = tokio::fs::remove_file(UPDATE_PATH).await;
+        return Err(anyhow::anyhow!(
+            "Downloaded binary reports unexpected version: {}",
+            output.trim()
+        ));
+    }
+
+    // copy keeps permissions
+    tokio::fs::copy(INSTALL_PATH, ROLLBACK_PATH).await?;
+    tokio::fs::File::open(ROLLBACK_PATH)
+        .await?
+        .sync_all()
+        .await?;
+    tokio::fs::rename(UPDATE_PATH, INSTALL_PATH).await?;
+
+    tokio::fs::write(PENDING_MARKER_PATH, "0").await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHECKSUMS: &str = "\
+3f2a9c0e5b7d41e6a8c2f0b9d4e7a1c3b5d8f0e2a4c6b8d0f1e3a5c7b9d1f3e5  serial-screen-x86_64
+A1B2C3D4E5F60718293A4B5C6D7E8F90A1B2C3D4E5F60718293A4B5C6D7E8F90 *serial-screen-aarch64
+";
+
+    fn release(tag_name: &str, draft: bool) -> GithubRelease {
+        GithubRelease {
+            tag_name: tag_name.to_string(),
+            draft,
+            assets: vec![],
+        }
+    }
+
+    #[test]
+    fn find_checksum_reads_sha256sum_lines() {
+        assert_eq!(
+            find_checksum(CHECKSUMS, "serial-screen-x86_64").unwrap(),
+            "3f2a9c0e5b7d41e6a8c2f0b9d4e7a1c3b5d8f0e2a4c6b8d0f1e3a5c7b9d1f3e5"
+        );
+
+        // binary mode marker, uppercase hex
+        assert_eq!(
+            find_checksum(CHECKSUMS, "serial-screen-aarch64").unwrap(),
+            "a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f90"
+        );
+    }
+
+    #[test]
+    fn find_checksum_fails_for_missing_asset() {
+        assert!(find_checksum(CHECKSUMS, "serial-screen-arm-gnueabihf").is_err());
+        assert!(find_checksum(CHECKSUMS, "serial-screen").is_err());
+        assert!(find_checksum("", "serial-screen-x86_64").is_err());
+    }
+
+    #[test]
+    fn parse_tag_accepts_v_prefix() {
+        assert_eq!(parse_tag("v1.2.3").unwrap(), Version::new(1, 2, 3));
+        assert_eq!(parse_tag("1.2.3").unwrap(), Version::new(1, 2, 3));
+        assert_eq!(
+            parse_tag("v1.3.0-beta.1").unwrap(),
+            Version::parse("1.3.0-beta.1").unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_tag_rejects_non_semver() {
+        assert!(parse_tag("nightly").is_err());
+        assert!(parse_tag("v1.2").is_err());
+        assert!(parse_tag("").is_err());
+    }
+
+    #[test]
+    fn newest_release_prefers_newer_prerelease() {
+        let releases = vec![
+            release("v1.2.0", false),
+            release("v1.3.0-beta.1", false),
+            release("v1.1.0", false),
+        ];
+        assert_eq!(newest_release(releases).unwrap().tag_name, "v1.3.0-beta.1");
+    }
+
+    #[test]
+    fn newest_release_prefers_stable_over_its_prerelease() {
+        let releases = vec![
+            release("v1.3.0-rc.1", false),
+            release("v1.3.0", false),
+            release("v1.3.0-rc.2", false),
+        ];
+        assert_eq!(newest_release(releases).unwrap().tag_name, "v1.3.0");
+    }
+
+    #[test]
+    fn newest_release_skips_drafts_and_invalid_tags() {
+        let releases = vec![
+            release("v1.2.0", false),
+            release("v2.0.0", true),
+            release("latest", false),
+        ];
+        assert_eq!(newest_release(releases).unwrap().tag_name, "v1.2.0");
+
+        assert!(newest_release(vec![release("nightly", false)]).is_none());
+        assert!(newest_release(vec![]).is_none());
+    }
 }
@@ -15,21 +15,136 @@ pub fn center_pad(s: &str, pad_char: &str, width: usize) -> String {
     format!("{}{}{}", pad_char.repeat(l), s, pad_char.repeat(r))
 }
 
+/// Pads text with spaces (or cuts it) to exactly `width` bytes,
+/// so it overwrites whole text VP on the screen
+pub fn fit_text(s: &str, width: usize) -> String {
+    let mut end = s.len().min(width);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    // padding is counted in bytes, `{: <width$}` would count chars
+    let cut = &s[..end];
+    format!("{}{}", cut, " ".repeat(width - cut.len()))
+}
+
+/// Splits text into lines of at most `width` chars, breaking on whitespace when possible
+pub fn wrap_text(s: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in s.split_whitespace() {
+        let mut word = word;
+        while word.chars().count() > width {
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+
+            let split = word
+                .char_indices()
+                .nth(width)
+                .map_or(word.len(), |(i, _)| i);
+            lines.push(word[..split].to_string());
+            word = &word[split..];
+        }
+
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// HTTP client for moonraker REST API, authorized with `api_key` if set
+pub fn http_client(api_key: Option<&str>) -> reqwest::Client {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(api_key) = api_key.and_then(|k| reqwest::header::HeaderValue::from_str(k).ok()) {
+        headers.insert("X-Api-Key", api_key);
+    }
+
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap_or_default()
+}
+
+/// `discovered` objects (sensors, fans, LEDs ...) and their fields are only known
+/// after `printer.objects.list`, the subscription is made again with them
 pub async fn subscribe_websocket_events(
     tx: Arc<Mutex<UnboundedSender<MoonrakerMsg>>>,
+    discovered: &[(String, Vec<String>)],
 ) -> Result<()> {
     let mut objects: HashMap<String, Option<Vec<String>>> = HashMap::new();
     objects.insert("display_status".to_string(), None);
     objects.insert("print_stats".to_string(), None);
     objects.insert("toolhead".to_string(), None);
+    objects.insert("idle_timeout".to_string(), Some(vec!["state".into()]));
+    objects.insert(
+        "gcode_move".to_string(),
+        Some(vec![
+            "gcode_position".into(),
+            "speed_factor".into(),
+            "extrude_factor".into(),
+            "homing_origin".into(),
+        ]),
+    );
+    objects.insert("fan".to_string(), Some(vec!["speed".into()]));
+    objects.insert(
+        "exclude_object".to_string(),
+        Some(vec![
+            "objects".into(),
+            "excluded_objects".into(),
+            "current_object".into(),
+        ]),
+    );
+    objects.insert(
+        "bed_mesh".to_string(),
+        Some(vec![
+            "profile_name".into(),
+            "mesh_min".into(),
+            "mesh_max".into(),
+            "probed_matrix".into(),
+            "profiles".into(),
+        ]),
+    );
+    objects.insert(
+        "manual_probe".to_string(),
+        Some(vec![
+            "is_active".into(),
+            "z_position".into(),
+            "z_position_lower".into(),
+            "z_position_upper".into(),
+        ]),
+    );
+    objects.insert(
+        "webhooks".to_string(),
+        Some(vec!["state".into(), "state_message".into()]),
+    );
     objects.insert(
         "extruder".to_string(),
-        Some(vec!["target".into(), "temperature".into()]),
+        Some(vec![
+            "target".into(),
+            "temperature".into(),
+            "can_extrude".into(),
+        ]),
     );
     objects.insert(
         "heater_bed".to_string(),
         Some(vec!["target".into(), "temperature".into()]),
     );
+    for (object, fields) in discovered {
+        objects
+            .entry(object.clone())
+            .or_insert_with(|| Some(fields.clone()));
+    }
 
     // subscribe to printer updates
     tx.lock().await.send(MoonrakerMsg::new_param_id(
@@ -39,3 +154,28 @@ pub async fn subscribe_websocket_events(
 
     Ok(())
 }
+
+/// Runs gcode script (or macro) through `printer.gcode.script`
+pub fn send_gcode_script(tx: &UnboundedSender<MoonrakerMsg>, script: String) -> Result<()> {
+    tx.send(MoonrakerMsg::new_param_id(
+        moonraker_api::methods::MoonrakerMethod::GcodeScript,
+        moonraker_api::params::MoonrakerParam::GcodeScript { script },
+    ))?;
+
+    Ok(())
+}
+
+/// Klipper config is not subscribed (it only changes after restart), it's queried once when klippy is ready
+pub async fn query_configfile_settings(
+    tx: Arc<Mutex<UnboundedSender<MoonrakerMsg>>>,
+) -> Result<()> {
+    let mut objects: HashMap<String, Option<Vec<String>>> = HashMap::new();
+    objects.insert("configfile".to_string(), Some(vec!["settings".into()]));
+
+    tx.lock().await.send(MoonrakerMsg::new_param_id(
+        moonraker_api::methods::MoonrakerMethod::PrinterObjectsQuery,
+        moonraker_api::params::MoonrakerParam::PrinterObjectsSubscribe { objects },
+    ))?;
+
+    Ok(())
+}